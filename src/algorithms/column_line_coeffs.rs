@@ -1,7 +1,11 @@
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+use num_traits::One;
+use stwo_prover::core::fields::cm31::CM31;
+use stwo_prover::core::fields::FieldExpOps;
 
 /// Compute the parameters of `column_line_coeffs` without applying alpha.
 ///
@@ -20,7 +24,10 @@ pub fn column_line_coeffs(
     y: &QM31Var,
     evals: &[QM31Var],
 ) -> Result<Vec<(CM31Var, CM31Var)>> {
-    let y_second_inverse = y.second.inverse(table);
+    let cs = y.cs();
+
+    let y_second_inverse = CM31Var::new_hint(&cs, y.second.value()?.inverse())?;
+    verify_y_imag_inv(table, y, &y_second_inverse)?;
     let y_first_times_y_second_inv = &y.first * &y_second_inverse;
 
     let mut ab = vec![];
@@ -33,3 +40,72 @@ pub fn column_line_coeffs(
 
     Ok(ab)
 }
+
+/// Verify that `y_imag_inv` is the inverse of `Im(p.y)`.
+pub fn verify_y_imag_inv(table: &TableVar, y: &QM31Var, y_imag_inv: &CM31Var) -> Result<()> {
+    let expected_one = y_imag_inv * (table, &y.second);
+    if expected_one.value()? != CM31::one() {
+        bail!("the hinted y_imag_inv is not the inverse of Im(p.y)");
+    }
+    expected_one.is_one();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::column_line_coeffs::verify_y_imag_inv;
+    use crate::dsl::primitives::cm31::CM31Var;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::utils::rand_qm31;
+    use crate::dsl::primitives::table::TableVar;
+    use crate::tests_utils::gadget::assert_rejects_tampered_hints;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::bvar::AllocVar;
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use bitcoin_script_dsl::test_program;
+    use num_traits::One;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::fields::cm31::CM31;
+    use stwo_prover::core::fields::FieldExpOps;
+
+    #[test]
+    fn test_verify_y_imag_inv() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let y = rand_qm31(&mut prng);
+            let y_imag_inv = y.1.inverse();
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let y_var = QM31Var::new_hint(&cs, y).unwrap();
+            let y_imag_inv_var = CM31Var::new_hint(&cs, y_imag_inv).unwrap();
+
+            verify_y_imag_inv(&table, &y_var, &y_imag_inv_var).unwrap();
+            test_program(cs, script! {}).unwrap();
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let y_var = QM31Var::new_hint(&cs, y).unwrap();
+            let wrong_y_imag_inv_var = CM31Var::new_hint(&cs, y_imag_inv + CM31::one()).unwrap();
+
+            assert!(verify_y_imag_inv(&table, &y_var, &wrong_y_imag_inv_var).is_err());
+
+            let build = || {
+                let cs = ConstraintSystem::new_ref();
+                let table = TableVar::new_constant(&cs, ()).unwrap();
+                let y_var = QM31Var::new_hint(&cs, y).unwrap();
+                let y_imag_inv_var = CM31Var::new_hint(&cs, y_imag_inv).unwrap();
+                verify_y_imag_inv(&table, &y_var, &y_imag_inv_var).unwrap();
+                cs
+            };
+            let tampered = y_imag_inv + CM31::one();
+            assert_rejects_tampered_hints(
+                build,
+                &[y_imag_inv.0, y_imag_inv.1],
+                &[tampered.0, tampered.1],
+            );
+        }
+    }
+}
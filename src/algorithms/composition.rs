@@ -0,0 +1,94 @@
+use crate::dsl::primitives::qm31::QM31Var;
+use anyhow::Result;
+
+/// Verify that the composition polynomial's OODS value, reconstructed from its four partial
+/// evaluations, equals the evaluated constraints.
+///
+/// Input:
+/// - `evaluated_constraints`, which is the constraint numerator times the inverse of the
+///   constraint denominator at the OODS point
+/// - `composition_partial_evals`, the OODS values of the four composition columns
+pub fn assert_composition_matches(
+    evaluated_constraints: &QM31Var,
+    composition_partial_evals: [&QM31Var; 4],
+) -> Result<()> {
+    QM31Var::verify_from_partial_evals(composition_partial_evals, evaluated_constraints)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::composition::assert_composition_matches;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::utils::rand_qm31;
+    use crate::dsl::primitives::table::TableVar;
+    use crate::tests_utils::gadget::assert_rejects_tampered_hints;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::bvar::AllocVar;
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use bitcoin_script_dsl::test_program;
+    use num_traits::One;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::fields::FieldExpOps;
+
+    #[test]
+    fn test_assert_composition_matches() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let constraint_num = rand_qm31(&mut prng);
+        let constraint_denom = rand_qm31(&mut prng);
+        let expected = constraint_num * constraint_denom.inverse();
+
+        // split the expected composition value into four partial evaluations
+        let mut partial_evals = [
+            QM31::default(),
+            rand_qm31(&mut prng),
+            rand_qm31(&mut prng),
+            rand_qm31(&mut prng),
+        ];
+        partial_evals[0] = expected
+            - partial_evals[1] * QM31::from_u32_unchecked(0, 1, 0, 0)
+            - partial_evals[2] * QM31::from_u32_unchecked(0, 0, 1, 0)
+            - partial_evals[3] * QM31::from_u32_unchecked(0, 0, 0, 1);
+        assert_eq!(QM31::from_partial_evals(partial_evals), expected);
+
+        let build = |claimed: [QM31; 4]| {
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let constraint_num_var = QM31Var::new_hint(&cs, constraint_num).unwrap();
+            let constraint_denom_var = QM31Var::new_hint(&cs, constraint_denom).unwrap();
+            let evaluated_constraints =
+                &constraint_denom_var.inverse(&table) * (&table, &constraint_num_var);
+
+            let claimed_vars = claimed.map(|v| QM31Var::new_hint(&cs, v).unwrap());
+
+            let res = assert_composition_matches(
+                &evaluated_constraints,
+                [
+                    &claimed_vars[0],
+                    &claimed_vars[1],
+                    &claimed_vars[2],
+                    &claimed_vars[3],
+                ],
+            );
+            (cs, res)
+        };
+
+        let (cs, res) = build(partial_evals);
+        res.unwrap();
+        test_program(cs, script! {}).unwrap();
+
+        let mut tampered = partial_evals;
+        tampered[0] += QM31::one();
+        let (_, res) = build(tampered);
+        assert!(res.is_err());
+
+        assert_rejects_tampered_hints(
+            || build(partial_evals).0,
+            &partial_evals[0].to_m31_array(),
+            &tampered[0].to_m31_array(),
+        );
+    }
+}
@@ -7,6 +7,22 @@ use bitcoin_script_dsl::bvar::{AllocVar, BVar};
 use bitcoin_script_dsl::options::Options;
 use bitcoin_script_dsl::stack::Stack;
 use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fri::FOLD_STEP;
+
+/// Configuration of the FRI folding, in which each step folds `2^fold_step` evaluations into one.
+#[derive(Clone, Copy, Debug)]
+pub struct FoldConfig {
+    /// The log of the number of evaluations folded in each step.
+    pub fold_step: usize,
+}
+
+impl Default for FoldConfig {
+    fn default() -> Self {
+        Self {
+            fold_step: FOLD_STEP as usize,
+        }
+    }
+}
 
 pub fn ibutterfly(
     table: &TableVar,
@@ -22,6 +38,90 @@ pub fn ibutterfly(
     (new_v0, new_v1)
 }
 
+/// Fold `2^fold_step` sibling evaluations into one.
+///
+/// The evaluations are in bit-reversed order, so adjacent evaluations are twins. `itwids[i]` holds
+/// the inverse twiddles for the `i`-th round of pairwise folding, and the `i`-th round uses
+/// `alpha^(2^i)` as the folding coefficient.
+pub fn fold_step(
+    table: &TableVar,
+    config: &FoldConfig,
+    values: &[QM31Var],
+    itwids: &[Vec<M31Var>],
+    alpha: &QM31Var,
+) -> QM31Var {
+    assert_eq!(values.len(), 1 << config.fold_step);
+    assert_eq!(itwids.len(), config.fold_step);
+
+    let mut cur = values.to_vec();
+    let mut cur_alpha = alpha.clone();
+
+    for (i, round_itwids) in itwids.iter().enumerate() {
+        assert_eq!(round_itwids.len(), cur.len() / 2);
+
+        if i != 0 {
+            cur_alpha = &cur_alpha * (table, &cur_alpha);
+        }
+
+        cur = cur
+            .chunks_exact(2)
+            .zip(round_itwids.iter())
+            .map(|(pair, itwid)| {
+                let (f0, f1) = ibutterfly(table, &pair[0], &pair[1], itwid);
+                &f0 + &(&f1 * (table, &cur_alpha))
+            })
+            .collect();
+    }
+
+    cur.pop().unwrap()
+}
+
+/// Fold `2^fold_step` sibling evaluations into one as in `fold_step`, and also output the
+/// position at the next layer, `pos >> fold_step`, so that chained folds can continue from it
+/// without precomputed positions.
+pub fn fold_step_with_position(
+    table: &TableVar,
+    config: &FoldConfig,
+    pos: &M31Var,
+    values: &[QM31Var],
+    itwids: &[Vec<M31Var>],
+    alpha: &QM31Var,
+) -> (QM31Var, M31Var) {
+    let folded = fold_step(table, config, values, itwids, alpha);
+    let next_pos = shift_position(pos, config.fold_step);
+    (folded, next_pos)
+}
+
+/// Compute `pos >> n` by hinting the lower `n` bits and the remainder, and checking that they
+/// recompose `pos`.
+pub fn shift_position(pos: &M31Var, n: usize) -> M31Var {
+    let cs = pos.cs();
+
+    let mut hints = vec![];
+    let mut cur = pos.value.0;
+
+    for _ in 0..n {
+        hints.push(cur & 1);
+        cur >>= 1;
+    }
+    hints.push(cur);
+
+    let mut variables = vec![];
+    variables.push(pos.variable);
+    for &hint in hints.iter() {
+        variables.push(M31Var::new_hint(&cs, M31::from(hint)).unwrap().variable);
+    }
+
+    cs.insert_script_complex(
+        shift_position_gadget,
+        variables,
+        &Options::new().with_u32("n", n as u32),
+    )
+    .unwrap();
+
+    M31Var::new_function_output(&cs, M31::from(cur)).unwrap()
+}
+
 pub fn decompose_positions(pos: &M31Var, n: usize) -> Vec<M31Var> {
     let cs = pos.cs();
 
@@ -122,6 +222,27 @@ fn decompose_positions_gadget(_: &mut Stack, options: &Options) -> Result<Script
     })
 }
 
+fn shift_position_gadget(_: &mut Stack, options: &Options) -> Result<Script> {
+    let n = options.get_u32("n")?;
+
+    Ok(script! {
+        // stack:
+        // - pos
+        // - n bits
+        // - remainder
+
+        OP_DUP OP_TOALTSTACK
+
+        for _ in 0..n {
+            OP_DUP OP_ADD
+            OP_SWAP check_0_or_1 OP_ADD
+        }
+
+        OP_EQUALVERIFY
+        OP_FROMALTSTACK
+    })
+}
+
 fn skip_one_and_extract_bits_gadget(_: &mut Stack, options: &Options) -> Result<Script> {
     let n = options.get_u32("n")?;
 
@@ -149,15 +270,27 @@ fn check_0_or_1() -> Script {
 
 #[cfg(test)]
 mod test {
-    use crate::algorithms::folding::{decompose_positions, skip_one_and_extract_bits};
+    use crate::algorithms::folding::{
+        decompose_positions, fold_step, fold_step_with_position, skip_one_and_extract_bits,
+        FoldConfig,
+    };
     use crate::dsl::primitives::m31::M31Var;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::utils::rand_qm31;
+    use crate::dsl::primitives::table::TableVar;
     use crate::treepp::*;
-    use bitcoin_script_dsl::bvar::AllocVar;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
     use bitcoin_script_dsl::constraint_system::ConstraintSystem;
     use bitcoin_script_dsl::test_program;
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::backend::CpuBackend;
+    use stwo_prover::core::circle::Coset;
     use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::FieldExpOps;
+    use stwo_prover::core::fri::fold_line;
+    use stwo_prover::core::poly::line::{LineDomain, LineEvaluation};
+    use stwo_prover::core::utils::bit_reverse_index;
 
     #[test]
     fn test_decompose_positions() {
@@ -220,4 +353,159 @@ mod test {
             .unwrap()
         }
     }
+
+    #[test]
+    fn test_fold_step() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for log_size in 1..=2 {
+            let config = FoldConfig {
+                fold_step: log_size,
+            };
+
+            let domain = LineDomain::new(Coset::half_odds(log_size as u32));
+            let values = (0..1 << log_size)
+                .map(|_| rand_qm31(&mut prng))
+                .collect::<Vec<_>>();
+            let alpha = rand_qm31(&mut prng);
+
+            // reference folding by stwo, where the i-th fold uses alpha^(2^i)
+            let mut eval =
+                LineEvaluation::<CpuBackend>::new(domain, values.iter().copied().collect());
+            let mut itwids = vec![];
+            let mut cur_alpha = alpha;
+            for _ in 0..log_size {
+                let cur_domain = eval.domain();
+                itwids.push(
+                    (0..eval.len() / 2)
+                        .map(|i| {
+                            cur_domain
+                                .at(bit_reverse_index(i << 1, cur_domain.log_size()))
+                                .inverse()
+                        })
+                        .collect::<Vec<_>>(),
+                );
+                eval = fold_line(&eval, cur_alpha);
+                cur_alpha = cur_alpha * cur_alpha;
+            }
+            assert_eq!(eval.len(), 1);
+            let expected = eval.values.at(0);
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let values_vars = values
+                .iter()
+                .map(|&v| QM31Var::new_hint(&cs, v).unwrap())
+                .collect::<Vec<_>>();
+            let itwids_vars = itwids
+                .iter()
+                .map(|round| {
+                    round
+                        .iter()
+                        .map(|&v| M31Var::new_hint(&cs, v).unwrap())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let alpha_var = QM31Var::new_hint(&cs, alpha).unwrap();
+
+            let res = fold_step(&table, &config, &values_vars, &itwids_vars, &alpha_var);
+            assert_eq!(res.value().unwrap(), expected);
+            cs.set_program_output(&res).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    { expected }
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fold_step_with_position() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let log_size = 10;
+        let domain = LineDomain::new(Coset::half_odds(log_size));
+        let config = FoldConfig::default();
+
+        // the inverse twiddle for the pair containing `pos`, as used by stwo's `fold_line`
+        let itwid = |eval: &LineEvaluation<CpuBackend>, pos: usize| {
+            let domain = eval.domain();
+            domain
+                .at(bit_reverse_index(pos & !1, domain.log_size()))
+                .inverse()
+        };
+
+        for _ in 0..10 {
+            let pos: usize = prng.gen_range(0..1 << log_size);
+
+            let values = (0..1 << log_size)
+                .map(|_| rand_qm31(&mut prng))
+                .collect::<Vec<_>>();
+            let alphas = [rand_qm31(&mut prng), rand_qm31(&mut prng)];
+
+            // reference folding by stwo, with a fresh alpha for each layer
+            let eval_0 = LineEvaluation::<CpuBackend>::new(domain, values.into_iter().collect());
+            let eval_1 = fold_line(&eval_0, alphas[0]);
+            let eval_2 = fold_line(&eval_1, alphas[1]);
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let pos_var = M31Var::new_program_input(&cs, M31::from(pos as u32)).unwrap();
+
+            let values_vars = [pos & !1, pos | 1]
+                .map(|i| QM31Var::new_hint(&cs, eval_0.values.at(i)).unwrap())
+                .to_vec();
+            let itwid_var = M31Var::new_hint(&cs, itwid(&eval_0, pos)).unwrap();
+            let alpha_var = QM31Var::new_hint(&cs, alphas[0]).unwrap();
+
+            let (folded_1_var, pos_1_var) = fold_step_with_position(
+                &table,
+                &config,
+                &pos_var,
+                &values_vars,
+                &[vec![itwid_var]],
+                &alpha_var,
+            );
+            assert_eq!(folded_1_var.value().unwrap(), eval_1.values.at(pos >> 1));
+            assert_eq!(pos_1_var.value, M31::from((pos >> 1) as u32));
+
+            // the folded value is placed next to its sibling at the next layer
+            let sibling_var = QM31Var::new_hint(&cs, eval_1.values.at((pos >> 1) ^ 1)).unwrap();
+            let next_values_vars = if (pos >> 1) & 1 == 0 {
+                vec![folded_1_var, sibling_var]
+            } else {
+                vec![sibling_var, folded_1_var]
+            };
+            let itwid_var = M31Var::new_hint(&cs, itwid(&eval_1, pos >> 1)).unwrap();
+            let alpha_var = QM31Var::new_hint(&cs, alphas[1]).unwrap();
+
+            let (folded_2_var, pos_2_var) = fold_step_with_position(
+                &table,
+                &config,
+                &pos_1_var,
+                &next_values_vars,
+                &[vec![itwid_var]],
+                &alpha_var,
+            );
+            let folded_2 = eval_2.values.at(pos >> 2);
+            assert_eq!(folded_2_var.value().unwrap(), folded_2);
+            assert_eq!(pos_2_var.value, M31::from((pos >> 2) as u32));
+
+            cs.set_program_output(&folded_2_var).unwrap();
+            cs.set_program_output(&pos_2_var).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    { folded_2 }
+                    { (pos >> 2) as u32 }
+                },
+            )
+            .unwrap();
+        }
+    }
 }
@@ -13,3 +13,5 @@ pub mod column_line_coeffs;
 pub mod folding;
 
 pub mod quotient;
+
+pub mod composition;
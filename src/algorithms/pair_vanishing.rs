@@ -1,5 +1,6 @@
 use crate::algorithms::point::SecureCirclePointVar;
 use crate::dsl::primitives::cm31::CM31Var;
+use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
 
 pub fn prepare_pair_vanishing(
@@ -17,3 +18,139 @@ pub fn prepare_pair_vanishing(
 
     (x_second_div_y_second, cross_term)
 }
+
+/// Prepare the pair vanishing polynomials for several points, with all the inversions of
+/// `y.second` done by a single batch inverse.
+///
+/// The results are the same as calling `prepare_pair_vanishing` for each point.
+pub fn prepare_pair_vanishing_batch(
+    points: &[SecureCirclePointVar],
+    table: &TableVar,
+) -> Vec<(CM31Var, CM31Var)> {
+    let y_seconds = points
+        .iter()
+        .map(|point| point.y.second.clone())
+        .collect::<Vec<CM31Var>>();
+    let y_second_invs = CM31Var::batch_inverse(&y_seconds, table);
+
+    let mut res = vec![];
+    for (point, y_second_inv) in points.iter().zip(y_second_invs.iter()) {
+        let x_second_div_y_second = &point.x.second * (table, y_second_inv);
+
+        let mut cross_term = &x_second_div_y_second * (table, &point.y.first);
+        cross_term = &cross_term - &point.x.first;
+
+        res.push((x_second_div_y_second, cross_term));
+    }
+    res
+}
+
+/// Evaluate the pair vanishing polynomial of two secure-field excluded points at `z`, which is
+/// `(e0.y - e1.y) * z.x + (e1.x - e0.x) * z.y + (e0.x * e1.y - e0.y * e1.x)`.
+pub fn pair_vanishing_with_qm31_points(
+    table: &TableVar,
+    excluded0: &SecureCirclePointVar,
+    excluded1: &SecureCirclePointVar,
+    z: &SecureCirclePointVar,
+) -> QM31Var {
+    let y_diff = &excluded0.y - &excluded1.y;
+    let x_diff = &excluded1.x - &excluded0.x;
+
+    let mut cross_term = &excluded0.x * (table, &excluded1.y);
+    cross_term = &cross_term - &(&excluded0.y * (table, &excluded1.x));
+
+    let mut res = &y_diff * (table, &z.x);
+    res = &res + &(&x_diff * (table, &z.y));
+    &res + &cross_term
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::pair_vanishing::{
+        pair_vanishing_with_qm31_points, prepare_pair_vanishing, prepare_pair_vanishing_batch,
+    };
+    use crate::algorithms::point::SecureCirclePointVar;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::TableVar;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use bitcoin_script_dsl::test_program;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::circle::{CirclePoint, SECURE_FIELD_CIRCLE_ORDER};
+    use stwo_prover::core::constraints::pair_vanishing;
+
+    #[test]
+    fn test_pair_vanishing_with_qm31_points() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let excluded0 = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let excluded1 = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let z = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+
+            let expected = pair_vanishing(excluded0, excluded1, z);
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let mut point_vars = vec![];
+            for point in [excluded0, excluded1, z] {
+                point_vars.push(SecureCirclePointVar {
+                    x: QM31Var::new_hint(&cs, point.x).unwrap(),
+                    y: QM31Var::new_hint(&cs, point.y).unwrap(),
+                });
+            }
+
+            let res = pair_vanishing_with_qm31_points(
+                &table,
+                &point_vars[0],
+                &point_vars[1],
+                &point_vars[2],
+            );
+            assert_eq!(res.value().unwrap(), expected);
+
+            cs.set_program_output(&res).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    { expected }
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_prepare_pair_vanishing_batch() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+
+        let mut point_vars = vec![];
+        for _ in 0..4 {
+            let point = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            point_vars.push(SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, point.y).unwrap(),
+            });
+        }
+
+        let batched = prepare_pair_vanishing_batch(&point_vars, &table);
+        assert_eq!(batched.len(), point_vars.len());
+
+        for (point_var, (a, b)) in point_vars.iter().zip(batched.iter()) {
+            let (expected_a, expected_b) = prepare_pair_vanishing(point_var, &table);
+            assert_eq!(expected_a.value().unwrap(), a.value().unwrap());
+            assert_eq!(expected_b.value().unwrap(), b.value().unwrap());
+
+            expected_a.equalverify(a).unwrap();
+            expected_b.equalverify(b).unwrap();
+        }
+
+        test_program(cs, script! {}).unwrap();
+    }
+}
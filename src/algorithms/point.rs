@@ -2,16 +2,115 @@ use crate::dsl::primitives::channel::HashVarWithChannel;
 use crate::dsl::primitives::m31::M31Var;
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
+use crate::treepp::*;
+use anyhow::{bail, Result};
 use bitcoin_script_dsl::builtins::hash::HashVar;
 use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+use bitcoin_script_dsl::constraint_system::ConstraintSystemRef;
+use num_traits::Zero;
+use std::ops::Neg;
 use stwo_prover::core::circle::CirclePoint;
 use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::poly::circle::CanonicCoset;
 
 pub struct SecureCirclePointVar {
     pub x: QM31Var,
     pub y: QM31Var,
 }
 
+impl SecureCirclePointVar {
+    pub fn cs(&self) -> ConstraintSystemRef {
+        self.x.cs().and(&self.y.cs())
+    }
+
+    pub fn equalverify(&self, rhs: &SecureCirclePointVar) -> Result<()> {
+        self.x.equalverify(&rhs.x)?;
+        self.y.equalverify(&rhs.y)
+    }
+
+    /// Output 1 if the two points are equal, and 0 otherwise.
+    pub fn is_equal(&self, rhs: &SecureCirclePointVar) -> M31Var {
+        let cs = self.cs().and(&rhs.cs());
+
+        let res = self.x.value().unwrap() == rhs.x.value().unwrap()
+            && self.y.value().unwrap() == rhs.y.value().unwrap();
+
+        cs.insert_script(
+            secure_circle_point_is_equal_gadget,
+            self.x
+                .variables()
+                .iter()
+                .chain(self.y.variables().iter())
+                .chain(rhs.x.variables().iter())
+                .chain(rhs.y.variables().iter())
+                .copied(),
+        )
+        .unwrap();
+
+        M31Var::new_function_output(&cs, M31::from(res as u32)).unwrap()
+    }
+
+    /// Add two points, i.e., `(x0 * x1 - y0 * y1, x0 * y1 + y0 * x1)`.
+    pub fn add(&self, rhs: &SecureCirclePointVar, table: &TableVar) -> SecureCirclePointVar {
+        let x0x1 = &self.x * (table, &rhs.x);
+        let y0y1 = &self.y * (table, &rhs.y);
+        let x0y1 = &self.x * (table, &rhs.y);
+        let y0x1 = &self.y * (table, &rhs.x);
+
+        SecureCirclePointVar {
+            x: &x0x1 - &y0y1,
+            y: &x0y1 + &y0x1,
+        }
+    }
+
+    /// Subtract a point, by adding its negation.
+    pub fn sub(&self, rhs: &SecureCirclePointVar, table: &TableVar) -> SecureCirclePointVar {
+        self.add(&-rhs, table)
+    }
+}
+
+impl Neg for &SecureCirclePointVar {
+    type Output = SecureCirclePointVar;
+
+    /// Negate the point, which is its conjugate `(x, -y)`.
+    fn neg(self) -> Self::Output {
+        SecureCirclePointVar {
+            x: self.x.clone(),
+            y: -&self.y,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CirclePointVar {
+    pub x: M31Var,
+    pub y: M31Var,
+}
+
+impl CirclePointVar {
+    /// Output the point itself and its conjugate `(x, -y)`, which form the twin points.
+    pub fn twin_points(&self) -> (CirclePointVar, CirclePointVar) {
+        let conjugate = CirclePointVar {
+            x: self.x.clone(),
+            y: -&self.y,
+        };
+        (self.clone(), conjugate)
+    }
+}
+
+fn secure_circle_point_is_equal_gadget() -> Script {
+    script! {
+        for i in 0..8 {
+            { 8 - i } OP_ROLL OP_EQUAL OP_TOALTSTACK
+        }
+        OP_FROMALTSTACK
+        for _ in 1..8 {
+            OP_FROMALTSTACK OP_BOOLAND
+        }
+    }
+}
+
 pub fn get_oods_point(hash: &mut HashVar, table: &TableVar) -> SecureCirclePointVar {
     let t = hash.draw_felt();
     let t_doubled = &t + &t;
@@ -28,6 +127,33 @@ pub fn get_oods_point(hash: &mut HashVar, table: &TableVar) -> SecureCirclePoint
     SecureCirclePointVar { x, y }
 }
 
+/// Verify that the point `(x, y)` is the image of `t` under the rational map used to sample the
+/// oods point, i.e., `(1 + t^2) * x = 1 - t^2` and `(1 + t^2) * y = 2t`.
+pub fn verify_oods_rational_map(
+    t: &QM31Var,
+    point: &SecureCirclePointVar,
+    table: &TableVar,
+) -> Result<()> {
+    let t_doubled = t + t;
+    let t_squared = t * (table, t);
+
+    let t_squared_plus_1 = t_squared.add1();
+    let one_minus_t_squared = -&t_squared.sub1();
+
+    let lhs_x = &t_squared_plus_1 * (table, &point.x);
+    let lhs_y = &t_squared_plus_1 * (table, &point.y);
+
+    if lhs_x.value()? != one_minus_t_squared.value()? {
+        bail!("the x coordinate does not match the rational map");
+    }
+    if lhs_y.value()? != t_doubled.value()? {
+        bail!("the y coordinate does not match the rational map");
+    }
+
+    lhs_x.equalverify(&one_minus_t_squared)?;
+    lhs_y.equalverify(&t_doubled)
+}
+
 pub fn add_constant_m31_point_x_only(
     point: &SecureCirclePointVar,
     table: &TableVar,
@@ -78,3 +204,546 @@ pub fn add_constant_m31_point(
 
     SecureCirclePointVar { x, y }
 }
+
+/// Verify that the masked point is the oods point shifted by `mask_offset` steps of the trace
+/// domain of size `2^log_size`, which is how the mask points are derived from the oods point.
+pub fn verify_mask_shift(
+    oods_point: &SecureCirclePointVar,
+    masked_point: &SecureCirclePointVar,
+    table: &TableVar,
+    log_size: u32,
+    mask_offset: isize,
+) -> Result<()> {
+    let shift = CanonicCoset::new(log_size).step().mul_signed(mask_offset);
+    let expected = add_constant_m31_point(oods_point, table, shift);
+
+    if expected.x.value()? != masked_point.x.value()?
+        || expected.y.value()? != masked_point.y.value()?
+    {
+        bail!("the masked point is not the oods point shifted by the mask offset");
+    }
+    expected.equalverify(masked_point)
+}
+
+/// Evaluate the vanishing polynomial of the canonic coset of size `2^log_size` at the point.
+pub fn coset_vanishing(point: &SecureCirclePointVar, table: &TableVar, log_size: u32) -> QM31Var {
+    let coset = CanonicCoset::new(log_size).coset;
+    let shift = -coset.initial + coset.step_size.half().to_point();
+    let mut cur_x = add_constant_m31_point_x_only(point, table, shift);
+    for _ in 1..coset.log_size {
+        cur_x = &cur_x * (table, &cur_x);
+        cur_x = &cur_x + &cur_x;
+        cur_x = cur_x.sub1();
+    }
+    cur_x
+}
+
+/// Evaluate the vanishing polynomial of the canonic coset of size `2^log_size` at the point and
+/// at its complex conjugate.
+///
+/// The vanishing polynomial has M31 coefficients, so its value at the conjugate is the conjugate
+/// of its value at the point, and the doublings are only done once.
+pub fn coset_vanishing_twin(
+    point: &SecureCirclePointVar,
+    table: &TableVar,
+    log_size: u32,
+) -> (QM31Var, QM31Var) {
+    let vanishing = coset_vanishing(point, table, log_size);
+    let conjugated = QM31Var {
+        first: vanishing.first.clone(),
+        second: -&vanishing.second,
+    };
+    (vanishing, conjugated)
+}
+
+/// Evaluate the vanishing polynomial of the query domain, i.e., the canonic coset of size
+/// `2^(log_size + log_blowup)`, at an m31 point.
+pub fn query_domain_vanishing(
+    point: &CirclePointVar,
+    table: &TableVar,
+    log_size: u32,
+    log_blowup: u32,
+) -> M31Var {
+    let cs = point.x.cs().and(&table.cs());
+    let one = M31Var::new_constant(&cs, M31::from(1)).unwrap();
+
+    // the canonic coset is shifted by half of its step, so the shift in `coset_vanishing` is the
+    // identity and the point can be doubled directly
+    let mut cur_x = point.x.clone();
+    for _ in 1..log_size + log_blowup {
+        cur_x = &cur_x * (table, &cur_x);
+        cur_x = &cur_x + &cur_x;
+        cur_x = &cur_x - &one;
+    }
+    cur_x
+}
+
+/// Verify that the point is not in the canonic coset of size `2^log_size`, by checking that the
+/// coset vanishing polynomial is nonzero at the point, which is shown by its inverse.
+pub fn assert_not_in_domain(
+    point: &SecureCirclePointVar,
+    table: &TableVar,
+    log_size: u32,
+) -> Result<()> {
+    let vanishing = coset_vanishing(point, table, log_size);
+    if vanishing.value()? == QM31::zero() {
+        bail!("the point is in the canonic coset of size 2^{}", log_size);
+    }
+    let _ = vanishing.inverse(table);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::point::{
+        assert_not_in_domain, coset_vanishing, coset_vanishing_twin, query_domain_vanishing,
+        verify_mask_shift, verify_oods_rational_map, CirclePointVar, SecureCirclePointVar,
+    };
+    use crate::dsl::primitives::m31::M31Var;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::TableVar;
+    use crate::tests_utils::gadget::assert_rejects_tampered_hints;
+    use crate::tests_utils::report::report_bitcoin_script_size;
+    use crate::treepp::*;
+    use crate::utils::get_rand_qm31;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::compiler::Compiler;
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use bitcoin_script_dsl::test_program;
+    use num_traits::{One, Zero};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::channel::{Channel, Sha256Channel};
+    use stwo_prover::core::circle::{CirclePoint, SECURE_FIELD_CIRCLE_ORDER};
+    use stwo_prover::core::constraints;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::qm31::{SecureField, QM31};
+    use stwo_prover::core::fields::ComplexConjugate;
+    use stwo_prover::core::poly::circle::CanonicCoset;
+
+    #[test]
+    fn test_secure_circle_point_equal() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let a = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let b = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            assert_ne!(a, b);
+
+            let cs = ConstraintSystem::new_ref();
+
+            let a_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, a.x).unwrap(),
+                y: QM31Var::new_hint(&cs, a.y).unwrap(),
+            };
+            let a2_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, a.x).unwrap(),
+                y: QM31Var::new_hint(&cs, a.y).unwrap(),
+            };
+            let b_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, b.x).unwrap(),
+                y: QM31Var::new_hint(&cs, b.y).unwrap(),
+            };
+
+            let equal = a_var.is_equal(&a2_var);
+            let not_equal = a_var.is_equal(&b_var);
+            a_var.equalverify(&a2_var).unwrap();
+
+            cs.set_program_output(&equal).unwrap();
+            cs.set_program_output(&not_equal).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    1
+                    0
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_twin_points() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let point = CanonicCoset::new(20)
+                .circle_domain()
+                .at(prng.gen_range(0..1 << 20));
+            let expected_y = -point.y;
+
+            let cs = ConstraintSystem::new_ref();
+            let point_var = CirclePointVar {
+                x: M31Var::new_hint(&cs, point.x).unwrap(),
+                y: M31Var::new_hint(&cs, point.y).unwrap(),
+            };
+
+            let (left, right) = point_var.twin_points();
+            assert_eq!(right.y.value, expected_y);
+
+            cs.set_program_output(&left.x).unwrap();
+            cs.set_program_output(&left.y).unwrap();
+            cs.set_program_output(&right.x).unwrap();
+            cs.set_program_output(&right.y).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    { point.x }
+                    { point.y }
+                    { point.x }
+                    { expected_y }
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_verify_mask_shift() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let log_size = 5;
+        let step = CanonicCoset::new(log_size).step();
+
+        for mask_offset in [0, 1, 2] {
+            let oods_point = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let masked_point = oods_point + step.mul_signed(mask_offset).into_ef();
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let oods_point_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, oods_point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, oods_point.y).unwrap(),
+            };
+            let masked_point_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, masked_point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, masked_point.y).unwrap(),
+            };
+
+            verify_mask_shift(
+                &oods_point_var,
+                &masked_point_var,
+                &table,
+                log_size,
+                mask_offset,
+            )
+            .unwrap();
+            test_program(cs, script! {}).unwrap();
+
+            // a masked point for a different offset is rejected
+            let wrong_point = oods_point + step.mul_signed(mask_offset + 1).into_ef();
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let oods_point_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, oods_point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, oods_point.y).unwrap(),
+            };
+            let wrong_point_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, wrong_point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, wrong_point.y).unwrap(),
+            };
+
+            assert!(verify_mask_shift(
+                &oods_point_var,
+                &wrong_point_var,
+                &table,
+                log_size,
+                mask_offset,
+            )
+            .is_err());
+
+            let build = || {
+                let cs = ConstraintSystem::new_ref();
+                let table = TableVar::new_constant(&cs, ()).unwrap();
+
+                let oods_point_var = SecureCirclePointVar {
+                    x: QM31Var::new_hint(&cs, oods_point.x).unwrap(),
+                    y: QM31Var::new_hint(&cs, oods_point.y).unwrap(),
+                };
+                let masked_point_var = SecureCirclePointVar {
+                    x: QM31Var::new_hint(&cs, masked_point.x).unwrap(),
+                    y: QM31Var::new_hint(&cs, masked_point.y).unwrap(),
+                };
+                verify_mask_shift(
+                    &oods_point_var,
+                    &masked_point_var,
+                    &table,
+                    log_size,
+                    mask_offset,
+                )
+                .unwrap();
+                cs
+            };
+            assert_rejects_tampered_hints(
+                build,
+                &masked_point.x.to_m31_array(),
+                &wrong_point.x.to_m31_array(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_oods_rational_map() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let t = get_rand_qm31(&mut prng);
+            let t_squared = t * t;
+            let x = (QM31::one() - t_squared) / (QM31::one() + t_squared);
+            let y = (t + t) / (QM31::one() + t_squared);
+
+            let delta = get_rand_qm31(&mut prng);
+            for (x, y, valid) in [(x, y, true), (x + delta, y, false), (x, y + delta, false)] {
+                let cs = ConstraintSystem::new_ref();
+                let table = TableVar::new_constant(&cs, ()).unwrap();
+
+                let t_var = QM31Var::new_hint(&cs, t).unwrap();
+                let point_var = SecureCirclePointVar {
+                    x: QM31Var::new_hint(&cs, x).unwrap(),
+                    y: QM31Var::new_hint(&cs, y).unwrap(),
+                };
+
+                let res = verify_oods_rational_map(&t_var, &point_var, &table);
+                if valid {
+                    res.unwrap();
+                    test_program(cs, script! {}).unwrap();
+                } else {
+                    assert!(res.is_err());
+                }
+            }
+
+            let build = || {
+                let cs = ConstraintSystem::new_ref();
+                let table = TableVar::new_constant(&cs, ()).unwrap();
+
+                let t_var = QM31Var::new_hint(&cs, t).unwrap();
+                let point_var = SecureCirclePointVar {
+                    x: QM31Var::new_hint(&cs, x).unwrap(),
+                    y: QM31Var::new_hint(&cs, y).unwrap(),
+                };
+                verify_oods_rational_map(&t_var, &point_var, &table).unwrap();
+                cs
+            };
+            assert_rejects_tampered_hints(build, &x.to_m31_array(), &(x + delta).to_m31_array());
+        }
+    }
+
+    #[test]
+    fn test_assert_not_in_domain() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+        let log_size = 5;
+
+        let mut channel = Sha256Channel::default();
+        channel.mix_nonce(prng.gen());
+        let oods_point = CirclePoint::<SecureField>::get_random_point(&mut channel);
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+        let oods_point_var = SecureCirclePointVar {
+            x: QM31Var::new_hint(&cs, oods_point.x).unwrap(),
+            y: QM31Var::new_hint(&cs, oods_point.y).unwrap(),
+        };
+        assert_not_in_domain(&oods_point_var, &table, log_size).unwrap();
+        test_program(cs, script! {}).unwrap();
+
+        // a point in the domain crafted as the oods point is rejected
+        let domain_point = CanonicCoset::new(log_size)
+            .coset
+            .at(prng.gen_range(0..1 << log_size))
+            .into_ef();
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+        let domain_point_var = SecureCirclePointVar {
+            x: QM31Var::new_hint(&cs, domain_point.x).unwrap(),
+            y: QM31Var::new_hint(&cs, domain_point.y).unwrap(),
+        };
+        assert!(assert_not_in_domain(&domain_point_var, &table, log_size).is_err());
+
+        let build = || {
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let oods_point_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, oods_point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, oods_point.y).unwrap(),
+            };
+            assert_not_in_domain(&oods_point_var, &table, log_size).unwrap();
+            cs
+        };
+        assert_rejects_tampered_hints(
+            build,
+            &[oods_point.x.to_m31_array(), oods_point.y.to_m31_array()].concat(),
+            &[domain_point.x.to_m31_array(), domain_point.y.to_m31_array()].concat(),
+        );
+    }
+
+    #[test]
+    fn test_secure_circle_point_neg_and_sub() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let a = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let b = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let expected_neg = -b;
+            let expected_sub = a - b;
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let a_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, a.x).unwrap(),
+                y: QM31Var::new_hint(&cs, a.y).unwrap(),
+            };
+            let b_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, b.x).unwrap(),
+                y: QM31Var::new_hint(&cs, b.y).unwrap(),
+            };
+
+            let neg = -&b_var;
+            assert_eq!(neg.x.value().unwrap(), expected_neg.x);
+            assert_eq!(neg.y.value().unwrap(), expected_neg.y);
+
+            let sub = a_var.sub(&b_var, &table);
+            assert_eq!(sub.x.value().unwrap(), expected_sub.x);
+            assert_eq!(sub.y.value().unwrap(), expected_sub.y);
+
+            cs.set_program_output(&neg.x).unwrap();
+            cs.set_program_output(&neg.y).unwrap();
+            cs.set_program_output(&sub.x).unwrap();
+            cs.set_program_output(&sub.y).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    { expected_neg.x }
+                    { expected_neg.y }
+                    { expected_sub.x }
+                    { expected_sub.y }
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_coset_vanishing_twin() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+        let log_size = 5;
+
+        for _ in 0..10 {
+            let point = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let conjugated_point = point.complex_conjugate();
+
+            let coset = CanonicCoset::new(log_size).coset;
+            let expected = constraints::coset_vanishing(coset, point);
+            let expected_conjugated = constraints::coset_vanishing(coset, conjugated_point);
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let point_var = SecureCirclePointVar {
+                x: QM31Var::new_hint(&cs, point.x).unwrap(),
+                y: QM31Var::new_hint(&cs, point.y).unwrap(),
+            };
+
+            let (vanishing, conjugated) = coset_vanishing_twin(&point_var, &table, log_size);
+            assert_eq!(vanishing.value().unwrap(), expected);
+            assert_eq!(conjugated.value().unwrap(), expected_conjugated);
+
+            cs.set_program_output(&vanishing).unwrap();
+            cs.set_program_output(&conjugated).unwrap();
+
+            test_program(
+                cs,
+                script! {
+                    { expected }
+                    { expected_conjugated }
+                },
+            )
+            .unwrap();
+        }
+
+        let point = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+        let point_var = SecureCirclePointVar {
+            x: QM31Var::new_hint(&cs, point.x).unwrap(),
+            y: QM31Var::new_hint(&cs, point.y).unwrap(),
+        };
+        let (vanishing, conjugated) = coset_vanishing_twin(&point_var, &table, log_size);
+        cs.set_program_output(&vanishing).unwrap();
+        cs.set_program_output(&conjugated).unwrap();
+        let twin = Compiler::compile(cs).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+        let point_var = SecureCirclePointVar {
+            x: QM31Var::new_hint(&cs, point.x).unwrap(),
+            y: QM31Var::new_hint(&cs, point.y).unwrap(),
+        };
+        let conjugated_point = point.complex_conjugate();
+        let conjugated_point_var = SecureCirclePointVar {
+            x: QM31Var::new_hint(&cs, conjugated_point.x).unwrap(),
+            y: QM31Var::new_hint(&cs, conjugated_point.y).unwrap(),
+        };
+        let vanishing = coset_vanishing(&point_var, &table, log_size);
+        let conjugated = coset_vanishing(&conjugated_point_var, &table, log_size);
+        cs.set_program_output(&vanishing).unwrap();
+        cs.set_program_output(&conjugated).unwrap();
+        let separate = Compiler::compile(cs).unwrap();
+
+        report_bitcoin_script_size("Point", "coset_vanishing_twin", twin.script.len());
+        report_bitcoin_script_size("Point", "coset_vanishing(x2)", separate.script.len());
+        assert!(twin.script.len() < separate.script.len());
+    }
+
+    #[test]
+    fn test_query_domain_vanishing() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+        let log_size = 5;
+        let log_blowup = 1;
+
+        let coset = CanonicCoset::new(log_size + log_blowup).coset;
+        for _ in 0..10 {
+            // a random m31 point on the circle, most of which are outside the query domain
+            let point = CanonicCoset::new(20)
+                .circle_domain()
+                .at(prng.gen_range(0..1 << 20));
+            let expected = constraints::coset_vanishing(coset, point);
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let point_var = CirclePointVar {
+                x: M31Var::new_hint(&cs, point.x).unwrap(),
+                y: M31Var::new_hint(&cs, point.y).unwrap(),
+            };
+
+            let res = query_domain_vanishing(&point_var, &table, log_size, log_blowup);
+            assert_eq!(res.value, expected);
+
+            cs.set_program_output(&res).unwrap();
+            test_program(cs, script! { { expected } }).unwrap();
+        }
+
+        // the query points themselves are in the domain
+        let domain = CanonicCoset::new(log_size + log_blowup).circle_domain();
+        for _ in 0..10 {
+            let point = domain.at(prng.gen_range(0..1 << (log_size + log_blowup)));
+
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+            let point_var = CirclePointVar {
+                x: M31Var::new_hint(&cs, point.x).unwrap(),
+                y: M31Var::new_hint(&cs, point.y).unwrap(),
+            };
+
+            let res = query_domain_vanishing(&point_var, &table, log_size, log_blowup);
+            assert_eq!(res.value, M31::zero());
+        }
+    }
+}
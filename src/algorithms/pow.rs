@@ -1,4 +1,5 @@
 use crate::channel::Sha256ChannelGadget;
+use crate::dsl::primitives::m31::M31Var;
 use crate::pow::PoWHint;
 use crate::treepp::*;
 use anyhow::Error;
@@ -8,6 +9,7 @@ use bitcoin_script_dsl::bvar::{AllocVar, BVar};
 use bitcoin_script_dsl::options::Options;
 use bitcoin_script_dsl::stack::Stack;
 use stwo_prover::core::channel::{Channel, Sha256Channel};
+use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
 
 pub fn verify_pow(hash: &mut HashVar, n_bits: u32, nonce: u64) -> anyhow::Result<()> {
@@ -114,3 +116,143 @@ fn verify_pow_gadget(_: &mut Stack, options: &Options) -> anyhow::Result<Script>
         // drop the dummy msb element if it is not needed
     })
 }
+
+/// Compute the number of trailing zero bits of the hash, i.e., the number of zero bits at the end
+/// of the hash that `verify_pow` checks against `n_bits`, and leave it as an M31 variable, so that
+/// the requirement can be enforced dynamically.
+///
+/// The hash is assumed to not be all zeroes.
+pub fn trailing_zero_bits(hash: &HashVar) -> anyhow::Result<M31Var> {
+    let digest = hash.value.to_vec();
+    assert_eq!(digest.len(), 32);
+
+    let num_zero_bytes = digest.iter().rev().take_while(|&&v| v == 0).count();
+    if num_zero_bytes == 32 {
+        return Err(Error::msg("The hash is all zeroes"));
+    }
+
+    let msb = digest[31 - num_zero_bytes];
+    let count = 8 * num_zero_bytes as u32 + msb.leading_zeros();
+
+    let cs = hash.cs();
+
+    let prefix_var = StrVar::new_hint(&cs, digest[..31 - num_zero_bytes].to_vec())?;
+    let msb_var = M31Var::new_hint(&cs, M31::from(msb as u32))?;
+
+    cs.insert_script(
+        trailing_zero_bits_gadget,
+        hash.variables()
+            .iter()
+            .chain(prefix_var.variables().iter())
+            .chain(msb_var.variables().iter())
+            .copied(),
+    )?;
+
+    M31Var::new_function_output(&cs, M31::from(count))
+}
+
+fn trailing_zero_bits_gadget() -> Script {
+    script! {
+        // Stack:
+        // - hash
+        // - prefix
+        // - msb (as a number)
+
+        // the msb must be a nonzero byte
+        OP_DUP 1 256 OP_WITHIN OP_VERIFY
+
+        // compute the number of leading zero bits of the msb
+        0
+        for i in 0..8 {
+            OP_OVER { 1 << i } OP_GREATERTHANOREQUAL OP_ADD
+        }
+        8 OP_SWAP OP_SUB
+        OP_SWAP
+
+        // convert the msb into a single byte
+        OP_DUP 128 OP_LESSTHAN OP_NOTIF
+            OP_DUP 128 OP_EQUAL OP_IF
+                OP_DROP { vec![0x80u8] }
+            OP_ELSE
+                128 OP_SWAP OP_SUB
+            OP_ENDIF
+        OP_ENDIF
+
+        // stack:
+        // - hash
+        // - leading zero bits of the msb
+        // - prefix
+        // - msb (as a byte)
+        OP_ROT OP_SWAP OP_CAT
+
+        // compute the number of zero bytes
+        OP_SIZE 32 OP_SWAP OP_SUB
+        OP_DUP 0 OP_GREATERTHANOREQUAL OP_VERIFY
+        OP_SWAP
+
+        // append the zero bytes
+        for i in 0..31 {
+            1 OP_PICK { i } OP_GREATERTHAN OP_IF
+                { vec![0u8] } OP_CAT
+            OP_ENDIF
+        }
+
+        3 OP_ROLL OP_EQUALVERIFY
+
+        // count = 8 * (number of zero bytes) + leading zero bits of the msb
+        OP_DUP OP_ADD OP_DUP OP_ADD OP_DUP OP_ADD
+        OP_ADD
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::pow::trailing_zero_bits;
+    use crate::dsl::primitives::m31::M31Var;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::builtins::hash::HashVar;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use bitcoin_script_dsl::test_program;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::channel::{Channel, Sha256Channel};
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+
+    #[test]
+    fn test_trailing_zero_bits() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut channel_digest = [0u8; 32];
+        prng.fill(&mut channel_digest);
+        let channel_digest = Sha256Hash::from(channel_digest.as_slice());
+
+        let mut n_tested = 0;
+        let mut nonce = 0u64;
+        // the last few nonces are required to have at least a full zero byte
+        while n_tested < 10 {
+            let mut channel = Sha256Channel::default();
+            channel.update_digest(channel_digest);
+            channel.mix_nonce(nonce);
+            nonce += 1;
+
+            let expected = channel.trailing_zeros();
+            if n_tested >= 6 && expected < 8 {
+                continue;
+            }
+
+            let cs = ConstraintSystem::new_ref();
+            let hash = HashVar::new_hint(&cs, channel.digest().as_ref().to_vec()).unwrap();
+
+            let count = trailing_zero_bits(&hash).unwrap();
+            assert_eq!(count.value().unwrap(), M31::from(expected));
+
+            let expected_var = M31Var::new_constant(&cs, M31::from(expected)).unwrap();
+            count.equalverify(&expected_var).unwrap();
+
+            test_program(cs, script! {}).unwrap();
+            n_tested += 1;
+        }
+    }
+}
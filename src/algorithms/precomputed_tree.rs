@@ -1,3 +1,4 @@
+use crate::algorithms::folding::skip_one_and_extract_bits;
 use crate::dsl::primitives::m31::M31Var;
 use crate::precomputed_merkle_tree::{PrecomputedMerkleTree, PrecomputedMerkleTreeProof};
 use crate::treepp::*;
@@ -7,6 +8,8 @@ use bitcoin_script_dsl::builtins::hash::HashVar;
 use bitcoin_script_dsl::bvar::{AllocVar, BVar};
 use bitcoin_script_dsl::options::Options;
 use bitcoin_script_dsl::stack::Stack;
+use stwo_prover::core::circle::CirclePointIndex;
+use stwo_prover::core::fields::m31::M31;
 
 pub struct PrecomputedVar {
     pub circle_point_x_var: M31Var,
@@ -77,6 +80,83 @@ pub fn query_and_verify_precomputed_merkle_tree(
     })
 }
 
+/// Compute the inverse twiddle factors for a query directly from the domain generator, as a
+/// fallback of `query_and_verify_precomputed_merkle_tree` for small domains.
+///
+/// The result is in the same order as `PrecomputedMerkleTree::query(pos).twiddles_elements`.
+pub fn compute_query_twiddles(pos: &M31Var, logn: usize) -> Vec<M31Var> {
+    compute_query_point_and_twiddles(pos, logn).twiddles_var
+}
+
+/// Compute the circle point and the inverse twiddle factors for a query directly from the domain
+/// generator, so that a verifier can be self-contained without a precomputed Merkle tree root.
+///
+/// The result matches `PrecomputedMerkleTree::query(pos)`.
+pub fn compute_query_point_and_twiddles(pos: &M31Var, logn: usize) -> PrecomputedVar {
+    assert!(logn >= 1);
+
+    let cs = pos.cs();
+
+    // bits[k] is the k-th bit of (pos >> 1)
+    let bits = skip_one_and_extract_bits(pos, logn);
+
+    let select = |bit: &M31Var, a: &M31Var, b: &M31Var| -> M31Var { a + &(bit * &(b - a)) };
+    let conditional_neg = |bit: &M31Var, v: &M31Var| -> M31Var {
+        let t = bit * v;
+        v - &(&t + &t)
+    };
+
+    // compute r = initial + bit_reverse(pos >> 2, logn - 1) * step
+    let initial = CirclePointIndex::subgroup_gen(logn as u32 + 2).to_point();
+    let step = CirclePointIndex::subgroup_gen(logn as u32).to_point();
+
+    let mut x = M31Var::new_constant(&cs, initial.x).unwrap();
+    let mut y = M31Var::new_constant(&cs, initial.y).unwrap();
+    for (i, bit) in bits.iter().skip(1).enumerate() {
+        let c = step.mul(1u128 << (logn - 2 - i));
+        let c_x = M31Var::new_constant(&cs, c.x).unwrap();
+        let c_y = M31Var::new_constant(&cs, c.y).unwrap();
+
+        let added_x = &(&x * &c_x) - &(&y * &c_y);
+        let added_y = &(&x * &c_y) + &(&y * &c_x);
+
+        x = select(bit, &x, &added_x);
+        y = select(bit, &y, &added_y);
+    }
+
+    // the lowest bit adds half of the circle to r, which negates both coordinates
+    let circle_point_x_var = conditional_neg(&bits[0], &x);
+    let circle_point_y_var = conditional_neg(&bits[0], &y);
+
+    // the first layer uses the y coordinate, and each following layer doubles the x coordinate,
+    // which flips its sign with the next bit.
+    let mut twiddles = vec![circle_point_y_var.clone()];
+    if logn >= 2 {
+        let one = M31Var::new_constant(&cs, M31::from(1)).unwrap();
+
+        let mut cur = x;
+        twiddles.push(cur.clone());
+        for bit in bits.iter().take(logn - 1).skip(1) {
+            let square = &cur * &cur;
+            let double_x = &(&square + &square) - &one;
+            cur = conditional_neg(bit, &double_x);
+            twiddles.push(cur.clone());
+        }
+    }
+
+    let twiddles_var = twiddles
+        .iter()
+        .rev()
+        .map(|twiddle| twiddle.inverse_without_table())
+        .collect();
+
+    PrecomputedVar {
+        circle_point_x_var,
+        circle_point_y_var,
+        twiddles_var,
+    }
+}
+
 fn query_and_verify_precomputed_merkle_tree_gadget(
     _: &mut Stack,
     options: &Options,
@@ -129,3 +209,111 @@ fn query_and_verify_precomputed_merkle_tree_gadget(
         OP_EQUALVERIFY
     })
 }
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::precomputed_tree::{
+        compute_query_point_and_twiddles, compute_query_twiddles,
+    };
+    use crate::dsl::primitives::m31::M31Var;
+    use crate::precomputed_merkle_tree::PrecomputedMerkleTree;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use bitcoin_script_dsl::test_program;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_compute_query_twiddles() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for logn in 1..=10 {
+            let precomputed_merkle_tree = PrecomputedMerkleTree::new(logn);
+
+            for _ in 0..3 {
+                let pos = prng.gen_range(0..1 << (logn + 1));
+                let expected = precomputed_merkle_tree.query(pos).twiddles_elements;
+
+                let cs = ConstraintSystem::new_ref();
+                let pos_var = M31Var::new_program_input(&cs, M31::from(pos as u32)).unwrap();
+
+                let twiddles = compute_query_twiddles(&pos_var, logn);
+                assert_eq!(
+                    twiddles
+                        .iter()
+                        .map(|v| v.value().unwrap())
+                        .collect::<Vec<M31>>(),
+                    expected
+                );
+
+                for twiddle in twiddles.iter() {
+                    cs.set_program_output(twiddle).unwrap();
+                }
+
+                test_program(
+                    cs,
+                    script! {
+                        for elem in expected.iter() {
+                            { *elem }
+                        }
+                    },
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_query_point_and_twiddles() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for logn in 1..=10 {
+            let precomputed_merkle_tree = PrecomputedMerkleTree::new(logn);
+
+            for _ in 0..3 {
+                let pos = prng.gen_range(0..1 << (logn + 1));
+                let expected = precomputed_merkle_tree.query(pos);
+
+                let cs = ConstraintSystem::new_ref();
+                let pos_var = M31Var::new_program_input(&cs, M31::from(pos as u32)).unwrap();
+
+                let res = compute_query_point_and_twiddles(&pos_var, logn);
+                assert_eq!(
+                    res.circle_point_x_var.value().unwrap(),
+                    expected.circle_point.x
+                );
+                assert_eq!(
+                    res.circle_point_y_var.value().unwrap(),
+                    expected.circle_point.y
+                );
+                assert_eq!(
+                    res.twiddles_var
+                        .iter()
+                        .map(|v| v.value().unwrap())
+                        .collect::<Vec<M31>>(),
+                    expected.twiddles_elements
+                );
+
+                cs.set_program_output(&res.circle_point_x_var).unwrap();
+                cs.set_program_output(&res.circle_point_y_var).unwrap();
+                for twiddle in res.twiddles_var.iter() {
+                    cs.set_program_output(twiddle).unwrap();
+                }
+
+                test_program(
+                    cs,
+                    script! {
+                        { expected.circle_point.x }
+                        { expected.circle_point.y }
+                        for elem in expected.twiddles_elements.iter() {
+                            { *elem }
+                        }
+                    },
+                )
+                .unwrap();
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
+use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
 
 pub fn apply_twin(
@@ -18,6 +19,39 @@ pub fn apply_twin(
     (res_z, res_conjugated_z)
 }
 
+/// Compute the powers of the random coefficient, `[coeff, coeff^2, ..., coeff^max_power]`, in the
+/// layout that `aggregate_numerators` expects.
+pub fn coeff_powers(table: &TableVar, coeff: &QM31Var, max_power: usize) -> Vec<QM31Var> {
+    assert!(max_power >= 1);
+
+    let mut powers = vec![coeff.clone()];
+    for _ in 1..max_power {
+        let next = powers.last().unwrap() * (table, coeff);
+        powers.push(next);
+    }
+    powers
+}
+
+/// Aggregate the numerators of several columns using the powers of the random coefficient.
+///
+/// Given `coeff_powers = [alpha, alpha^2, ..., alpha^k]` and numerators `n_0, n_1, ..., n_m`
+/// (where `m <= k`), compute `alpha^m * n_0 + alpha^(m-1) * n_1 + ... + n_m`.
+pub fn aggregate_numerators(
+    table: &TableVar,
+    coeff_powers: &[QM31Var],
+    numerators: &[&CM31Var],
+) -> QM31Var {
+    let n = numerators.len();
+    assert!(n >= 2);
+    assert!(coeff_powers.len() >= n - 1);
+
+    let mut sum = &coeff_powers[n - 2] * (table, numerators[0]);
+    for (i, &numerator) in numerators.iter().enumerate().take(n - 1).skip(1) {
+        sum = &sum + &(&coeff_powers[n - 2 - i] * (table, numerator));
+    }
+    &sum + numerators[n - 1]
+}
+
 pub fn denominator_inverse_from_prepared(
     table: &TableVar,
     x_second_div_y_second: &CM31Var,
@@ -36,3 +70,224 @@ pub fn denominator_inverse_from_prepared(
 
     (inverse_result_for_z, inverse_result_for_conjugated_z)
 }
+
+/// Compute the denominator inverses for several prepared pair vanishing polynomials at once.
+///
+/// This applies Montgomery batch inversion over all the `2 * prepared.len()` denominators, so that
+/// only a single inverse hint is needed. The results are in the same order as calling
+/// `denominator_inverse_from_prepared` for each element of `prepared`.
+pub fn denominator_inverses_batched(
+    table: &TableVar,
+    prepared: &[(CM31Var, CM31Var)],
+    z_x: &M31Var,
+    z_y: &M31Var,
+) -> Vec<(CM31Var, CM31Var)> {
+    assert!(!prepared.is_empty());
+
+    let mut denominators = vec![];
+    for (x_second_div_y_second, cross_term) in prepared.iter() {
+        let cross_term_plus_z_x = cross_term + z_x;
+        let x_second_div_y_second_times_z_y = x_second_div_y_second * (table, z_y);
+
+        denominators.push(&cross_term_plus_z_x - &x_second_div_y_second_times_z_y);
+        denominators.push(&cross_term_plus_z_x + &x_second_div_y_second_times_z_y);
+    }
+
+    let inverses = CM31Var::batch_inverse(&denominators, table);
+
+    inverses
+        .chunks_exact(2)
+        .map(|v| (v[0].clone(), v[1].clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::pair_vanishing::prepare_pair_vanishing;
+    use crate::algorithms::point::SecureCirclePointVar;
+    use crate::algorithms::quotient::{
+        aggregate_numerators, coeff_powers, denominator_inverse_from_prepared,
+        denominator_inverses_batched,
+    };
+    use crate::dsl::primitives::cm31::CM31Var;
+    use crate::dsl::primitives::m31::M31Var;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::utils::{rand_cm31, rand_qm31};
+    use crate::dsl::primitives::table::TableVar;
+    use crate::tests_utils::report::report_bitcoin_script_size;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::compiler::Compiler;
+    use bitcoin_script_dsl::constraint_system::{ConstraintSystem, ConstraintSystemRef};
+    use bitcoin_script_dsl::test_program;
+    use num_traits::Zero;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::circle::{CirclePoint, SECURE_FIELD_CIRCLE_ORDER};
+    use stwo_prover::core::fields::cm31::CM31;
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::fields::FieldExpOps;
+    use stwo_prover::core::poly::circle::CanonicCoset;
+
+    fn generate_cs(prng: &mut ChaCha20Rng, batched: bool, n_masks: usize) -> ConstraintSystemRef {
+        generate_cs_with_results(prng, batched, n_masks).0
+    }
+
+    fn generate_cs_with_results(
+        prng: &mut ChaCha20Rng,
+        batched: bool,
+        n_masks: usize,
+    ) -> (ConstraintSystemRef, Vec<(CM31Var, CM31Var)>) {
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+
+        let mut prepared = vec![];
+        for _ in 0..n_masks {
+            let point = CirclePoint::get_point(prng.gen::<u128>() % SECURE_FIELD_CIRCLE_ORDER);
+            let point_var = SecureCirclePointVar {
+                x: QM31Var::new_constant(&cs, point.x).unwrap(),
+                y: QM31Var::new_constant(&cs, point.y).unwrap(),
+            };
+            prepared.push(prepare_pair_vanishing(&point_var, &table));
+        }
+
+        let z = CanonicCoset::new(10)
+            .circle_domain()
+            .at(prng.gen_range(0..1 << 10));
+        let z_x = M31Var::new_constant(&cs, z.x).unwrap();
+        let z_y = M31Var::new_constant(&cs, z.y).unwrap();
+
+        let res = if batched {
+            denominator_inverses_batched(&table, &prepared, &z_x, &z_y)
+        } else {
+            prepared
+                .iter()
+                .map(|(a, b)| denominator_inverse_from_prepared(&table, a, b, &z_x, &z_y))
+                .collect()
+        };
+        for (l, r) in res.iter() {
+            cs.set_program_output(l).unwrap();
+            cs.set_program_output(r).unwrap();
+        }
+
+        (cs, res)
+    }
+
+    #[test]
+    fn test_denominator_inverses_batched() {
+        for seed in 0..5 {
+            // the same seed samples the same points, so the two programs invert the same values
+            let (_, expected) =
+                generate_cs_with_results(&mut ChaCha20Rng::seed_from_u64(seed), false, 4);
+            let (cs, batched) =
+                generate_cs_with_results(&mut ChaCha20Rng::seed_from_u64(seed), true, 4);
+            assert_eq!(batched.len(), expected.len());
+
+            let expected = expected
+                .iter()
+                .map(|(l, r)| (l.value().unwrap(), r.value().unwrap()))
+                .collect::<Vec<_>>();
+            for ((l, r), (expected_l, expected_r)) in batched.iter().zip(expected.iter()) {
+                assert_eq!(l.value().unwrap(), *expected_l);
+                assert_eq!(r.value().unwrap(), *expected_r);
+            }
+
+            test_program(
+                cs,
+                script! {
+                    for (l, r) in expected.iter() {
+                        { *l }
+                        { *r }
+                    }
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_denominator_inverses_batched_script_size() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let individual = Compiler::compile(generate_cs(&mut prng, false, 2)).unwrap();
+        let batched = Compiler::compile(generate_cs(&mut prng, true, 2)).unwrap();
+
+        report_bitcoin_script_size(
+            "Quotient",
+            "denominator_inverse_from_prepared(x2)",
+            individual.script.len(),
+        );
+        report_bitcoin_script_size(
+            "Quotient",
+            "denominator_inverses_batched(2)",
+            batched.script.len(),
+        );
+    }
+
+    #[test]
+    fn test_aggregate_numerators() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for n_columns in 2..=5 {
+            let cs = ConstraintSystem::new_ref();
+            let table = TableVar::new_constant(&cs, ()).unwrap();
+
+            let alpha = rand_qm31(&mut prng);
+            let mut coeff_powers = vec![];
+            let mut cur = alpha;
+            for _ in 0..n_columns - 1 {
+                coeff_powers.push(QM31Var::new_constant(&cs, cur).unwrap());
+                cur *= alpha;
+            }
+
+            let numerators = (0..n_columns)
+                .map(|_| rand_cm31(&mut prng))
+                .collect::<Vec<CM31>>();
+            let numerator_vars = numerators
+                .iter()
+                .map(|v| CM31Var::new_hint(&cs, *v).unwrap())
+                .collect::<Vec<CM31Var>>();
+
+            let mut expected = QM31::zero();
+            for numerator in numerators.iter() {
+                expected = expected * alpha + QM31(*numerator, CM31::zero());
+            }
+
+            let res = aggregate_numerators(
+                &table,
+                &coeff_powers,
+                &numerator_vars.iter().collect::<Vec<&CM31Var>>(),
+            );
+            assert_eq!(res.value().unwrap(), expected);
+
+            let expected_var = QM31Var::new_constant(&cs, expected).unwrap();
+            res.equalverify(&expected_var).unwrap();
+
+            test_program(cs, script! {}).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_coeff_powers() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+
+        let coeff = rand_qm31(&mut prng);
+        let coeff_var = QM31Var::new_hint(&cs, coeff).unwrap();
+
+        let powers = coeff_powers(&table, &coeff_var, 6);
+        assert_eq!(powers.len(), 6);
+
+        for (k, power) in powers.iter().enumerate() {
+            let expected = coeff.pow(k as u128 + 1);
+            assert_eq!(power.value().unwrap(), expected);
+
+            let expected_var = QM31Var::new_constant(&cs, expected).unwrap();
+            power.equalverify(&expected_var).unwrap();
+        }
+
+        test_program(cs, script! {}).unwrap();
+    }
+}
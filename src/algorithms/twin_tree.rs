@@ -1,7 +1,7 @@
 use crate::dsl::primitives::m31::M31Var;
 use crate::merkle_tree::MerkleTreeTwinProof;
 use crate::treepp::*;
-use crate::utils::{hash, limb_to_be_bits_toaltstack_except_lowest_1bit};
+use crate::utils::{hash, hash_checked, limb_to_be_bits_toaltstack_except_lowest_1bit};
 use anyhow::Error;
 use anyhow::Result;
 use bitcoin_script_dsl::builtins::hash::HashVar;
@@ -94,7 +94,7 @@ fn query_and_verify_merkle_twin_tree_gadget(_: &mut Stack, options: &Options) ->
 
         for _ in 0..(logn - 1) {
             OP_FROMALTSTACK OP_NOTIF OP_SWAP OP_ENDIF
-            OP_CAT hash
+            OP_CAT { hash_checked(64) }
         }
 
         OP_EQUALVERIFY
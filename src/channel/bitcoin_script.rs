@@ -1,5 +1,5 @@
 use crate::treepp::*;
-use crate::utils::{hash, hash_qm31_gadget, trim_m31_gadget};
+use crate::utils::{hash, hash_checked, hash_qm31_gadget, trim_m31_gadget};
 use crate::OP_HINT;
 use rust_bitcoin_m31::MOD;
 
@@ -17,7 +17,26 @@ impl Sha256ChannelGadget {
     /// - new channel digest
     pub fn mix_digest() -> Script {
         script! {
-            OP_CAT hash
+            OP_CAT { hash_checked(64) }
+        }
+    }
+
+    /// Check that the commitment is the one absorbed into the channel, by recomputing the digest
+    /// after mixing it into the digest before the commitment.
+    ///
+    /// Input:
+    /// - commitment
+    /// - channel digest before the commitment
+    /// - channel digest after the commitment
+    ///
+    /// Output:
+    /// - commitment
+    pub fn assert_commitment_absorbed() -> Script {
+        script! {
+            OP_TOALTSTACK
+            OP_OVER OP_SWAP
+            { Self::mix_digest() }
+            OP_FROMALTSTACK OP_EQUALVERIFY
         }
     }
 
@@ -59,6 +78,22 @@ impl Sha256ChannelGadget {
         }
     }
 
+    /// Absorb a byte string of `len` bytes, hashed after the channel digest.
+    ///
+    /// Input:
+    /// - data (`len` bytes)
+    /// - old channel digest
+    ///
+    /// Output:
+    /// - new channel digest
+    pub fn mix_bytes(len: usize) -> Script {
+        script! {
+            OP_SWAP
+            OP_SIZE { len } OP_EQUALVERIFY
+            OP_CAT hash
+        }
+    }
+
     /// Draw a qm31 element using hints.
     ///
     /// Input:
@@ -75,6 +110,29 @@ impl Sha256ChannelGadget {
         }
     }
 
+    /// Pull a commitment from the hints, absorb it, and draw a qm31 element using hints, as done
+    /// for each FRI layer.
+    ///
+    /// Hint:
+    /// - commitment
+    /// - hints for drawing the qm31 element
+    ///
+    /// Input:
+    /// - old channel digest
+    ///
+    /// Output:
+    /// - commitment
+    /// - qm31
+    /// - new channel digest
+    pub fn mix_digest_and_draw_felt() -> Script {
+        script! {
+            OP_HINT OP_DUP OP_ROT
+            { Self::mix_digest() }
+            { Self::draw_felt_with_hint() }
+            4 OP_ROLL
+        }
+    }
+
     /// Draw queries from the channel, each of logn bits, using hints.
     ///
     /// Output:
@@ -103,6 +161,35 @@ impl Sha256ChannelGadget {
         }
     }
 
+    /// Draw queries from the channel, each of logn bits, using hints, and also keep the untrimmed
+    /// m31 elements for auditing.
+    ///
+    /// Output:
+    ///    for each of the numbers (m), the full m31 element and the trimmed query
+    ///    channel digest
+    pub fn draw_numbers_with_untrimmed_hint(m: usize, logn: usize) -> Script {
+        script! {
+            for _ in 0..(m / 8) {
+                OP_DUP hash OP_SWAP
+                OP_PUSHBYTES_1 OP_PUSHBYTES_0 OP_CAT hash
+                { Self::unpack_multi_m31(8) }
+                for i in 0..8 {
+                    { 2 * i } OP_ROLL OP_DUP { trim_m31_gadget(logn) }
+                }
+                16 OP_ROLL
+            }
+            if m % 8 != 0 {
+                OP_DUP hash OP_SWAP
+                OP_PUSHBYTES_1 OP_PUSHBYTES_0 OP_CAT hash
+                { Self::unpack_multi_m31(m % 8) }
+                for i in 0..m % 8 {
+                    { 2 * i } OP_ROLL OP_DUP { trim_m31_gadget(logn) }
+                }
+                { 2 * (m % 8) } OP_ROLL
+            }
+        }
+    }
+
     /// Reconstruct a 4-byte representation from a Bitcoin integer.
     ///
     /// Idea: extract the positive/negative symbol and pad it accordingly.
@@ -143,6 +230,44 @@ impl Sha256ChannelGadget {
         }
     }
 
+    /// Reconstruct a 4-byte representation from the lower 31 bits and the high bit, as hinted in
+    /// the canonical encoding mode.
+    ///
+    /// Input:
+    /// - lower 31 bits, as a non-negative Bitcoin integer
+    /// - high bit, 0 or 1
+    ///
+    /// Output:
+    /// - the 4-byte representation
+    ///
+    /// Altstack:
+    /// - the lower 31 bits
+    fn reconstruct_canonical() -> Script {
+        script! {
+            OP_DUP 0 2 OP_WITHIN OP_VERIFY
+            OP_SWAP
+            OP_DUP 0 OP_GREATERTHANOREQUAL OP_VERIFY
+            OP_DUP OP_TOALTSTACK
+
+            OP_SIZE 4 OP_LESSTHAN
+            OP_IF
+                OP_SIZE 2 OP_LESSTHAN OP_IF OP_PUSHBYTES_2 OP_PUSHBYTES_0 OP_PUSHBYTES_0 OP_CAT OP_ENDIF
+                OP_SIZE 3 OP_LESSTHAN OP_IF OP_PUSHBYTES_1 OP_PUSHBYTES_0 OP_CAT OP_ENDIF
+
+                OP_SWAP
+                OP_IF
+                    OP_PUSHBYTES_1 OP_LEFT
+                OP_ELSE
+                    OP_PUSHBYTES_1 OP_PUSHBYTES_0
+                OP_ENDIF
+                OP_CAT
+            OP_ELSE
+                OP_SWAP
+                OP_IF OP_NEGATE OP_ENDIF
+            OP_ENDIF
+        }
+    }
+
     /// Unpack multiple m31 and put them on the stack.
     pub fn unpack_multi_m31(m: usize) -> Script {
         script! {
@@ -174,14 +299,50 @@ impl Sha256ChannelGadget {
             }
         }
     }
+
+    /// Unpack multiple m31 and put them on the stack, with hints in the canonical encoding mode
+    /// (see `EncodingMode::Canonical`).
+    pub fn unpack_multi_m31_canonical(m: usize) -> Script {
+        script! {
+            for _ in 0..2 * m {
+                OP_HINT
+            }
+
+            for k in 0..m {
+                { 2 * m - k - 1 } OP_ROLL
+                { 2 * m - k - 1 } OP_ROLL
+                { Self::reconstruct_canonical() }
+            }
+
+            for _ in 0..m-1 {
+                OP_CAT
+            }
+
+            if m % 8 != 0 {
+                OP_HINT OP_CAT
+            }
+
+            OP_EQUALVERIFY
+
+            for _ in 0..m {
+                OP_FROMALTSTACK
+
+                OP_DUP { MOD } OP_EQUAL OP_IF
+                    OP_NOT
+                OP_ENDIF
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::channel::{generate_hints, ChannelWithHint, Sha256Channel, Sha256ChannelGadget};
+    use crate::channel::{
+        generate_hints, ChannelWithHint, EncodingMode, Sha256Channel, Sha256ChannelGadget,
+    };
     use crate::tests_utils::report::report_bitcoin_script_size;
     use crate::treepp::*;
-    use crate::utils::{get_rand_qm31, hash, hash_qm31, hash_qm31_gadget};
+    use crate::utils::{get_rand_qm31, hash, hash_qm31, hash_qm31_gadget, trim_m31};
     use bitcoin_script::script;
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
@@ -221,6 +382,61 @@ mod test {
         assert!(exec_result.success);
     }
 
+    #[test]
+    fn test_assert_commitment_absorbed() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let channel_script = Sha256ChannelGadget::assert_commitment_absorbed();
+        report_bitcoin_script_size(
+            "Channel",
+            "assert_commitment_absorbed",
+            channel_script.len(),
+        );
+
+        let mut init_state = [0u8; 32];
+        init_state.iter_mut().for_each(|v| *v = prng.gen());
+        let init_state = Sha256Hash::from(init_state.to_vec());
+
+        let mut commitment = [0u8; 32];
+        commitment.iter_mut().for_each(|v| *v = prng.gen());
+        let commitment = Sha256Hash::from(commitment.to_vec());
+
+        let mut swapped_commitment = [0u8; 32];
+        swapped_commitment.iter_mut().for_each(|v| *v = prng.gen());
+        let swapped_commitment = Sha256Hash::from(swapped_commitment.to_vec());
+
+        let mut channel = Sha256Channel::default();
+        channel.update_digest(init_state);
+        channel.update_digest(Sha256Hasher::concat_and_hash(
+            &commitment,
+            &channel.digest(),
+        ));
+
+        let final_state = channel.digest;
+
+        let script = script! {
+            { commitment }
+            { init_state }
+            { final_state }
+            { channel_script.clone() }
+            { commitment }
+            OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+
+        let script = script! {
+            { swapped_commitment }
+            { init_state }
+            { final_state }
+            { channel_script.clone() }
+            OP_DROP
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+    }
+
     #[test]
     fn test_mix_felt() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -281,6 +497,53 @@ mod test {
         assert!(exec_result.success);
     }
 
+    #[test]
+    fn test_mix_bytes() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for len in [1, 31, 64] {
+            let channel_script = Sha256ChannelGadget::mix_bytes(len);
+            report_bitcoin_script_size(
+                "Channel",
+                format!("mix_bytes({})", len).as_str(),
+                channel_script.len(),
+            );
+
+            let mut init_state = [0u8; 32];
+            init_state.iter_mut().for_each(|v| *v = prng.gen());
+            let init_state = Sha256Hash::from(init_state.to_vec());
+
+            let data = (0..len).map(|_| prng.gen::<u8>()).collect::<Vec<u8>>();
+
+            let mut channel = Sha256Channel::default();
+            channel.update_digest(init_state);
+            channel.mix_bytes(&data);
+
+            let final_state = channel.digest;
+
+            let script = script! {
+                { data.clone() }
+                { init_state }
+                { channel_script.clone() }
+                { final_state }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            // a blob of the wrong length is rejected
+            let script = script! {
+                { data[..len - 1].to_vec() }
+                { init_state }
+                { channel_script.clone() }
+                OP_DROP
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
     #[test]
     fn test_draw_8_elements() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -346,6 +609,47 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_mix_digest_and_draw_felt() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let channel_script = Sha256ChannelGadget::mix_digest_and_draw_felt();
+        report_bitcoin_script_size("Channel", "mix_digest_and_draw_felt", channel_script.len());
+
+        let mut a = [0u8; 32];
+        a.iter_mut().for_each(|v| *v = prng.gen());
+        let a = Sha256Hash::from(a.to_vec());
+
+        let mut commitment = [0u8; 32];
+        commitment.iter_mut().for_each(|v| *v = prng.gen());
+        let commitment = Sha256Hash::from(commitment.to_vec());
+
+        let mut channel = Sha256Channel::default();
+        channel.update_digest(a);
+        channel.update_digest(Sha256Hasher::concat_and_hash(
+            &commitment,
+            &channel.digest(),
+        ));
+        let (b, hint) = channel.draw_felt_and_hints();
+
+        let c = channel.digest;
+
+        let script = script! {
+            { commitment }
+            { hint }
+            { a }
+            { channel_script.clone() }
+            { c }
+            OP_EQUALVERIFY
+            { b }
+            qm31_equalverify
+            { commitment }
+            OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
+
     #[test]
     fn test_draw_many_numbers_with_hint() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -413,6 +717,48 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_draw_numbers_with_untrimmed_hint() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let channel_script = Sha256ChannelGadget::draw_numbers_with_untrimmed_hint(12, 10);
+        report_bitcoin_script_size(
+            "Channel",
+            "draw_12numbers_with_untrimmed_hint",
+            channel_script.len(),
+        );
+
+        for _ in 0..10 {
+            let mut a = [0u8; 32];
+            a.iter_mut().for_each(|v| *v = prng.gen());
+            let a = Sha256Hash::from(a.to_vec());
+
+            let mut channel = Sha256Channel::default();
+            channel.update_digest(a);
+            let (b, hint) = channel.draw_m31_and_hints(12);
+
+            let c = channel.digest;
+
+            let script = script! {
+                { hint }
+                { a }
+                { channel_script.clone() }
+                OP_TOALTSTACK
+                for i in 0..12 {
+                    { trim_m31(b[11 - i].0, 10) }
+                    OP_EQUALVERIFY
+                    { b[11 - i] }
+                    OP_EQUALVERIFY
+                }
+                OP_FROMALTSTACK
+                { c }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
     #[test]
     fn test_hash_felt() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -463,4 +809,93 @@ mod test {
         let exec_result = execute_script(script);
         assert!(!exec_result.success);
     }
+
+    #[test]
+    fn test_unpack_multi_m31_canonical() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        report_bitcoin_script_size(
+            "Channel",
+            "unpack_8_m31_canonical",
+            Sha256ChannelGadget::unpack_multi_m31_canonical(8).len(),
+        );
+
+        // zero, negative zero, the modulus, 2^31 - 1 with the high bit, near-modulus values,
+        // and values that fit in fewer than four bytes
+        let mut extract = vec![
+            0x00, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x80, //
+            0xff, 0xff, 0xff, 0x7f, //
+            0xff, 0xff, 0xff, 0xff, //
+            0xfe, 0xff, 0xff, 0x7f, //
+            0xfe, 0xff, 0xff, 0xff, //
+            0x01, 0x00, 0x00, 0x80, //
+            0x80, 0x00, 0x00, 0x00, //
+        ];
+
+        for m in [8, 4] {
+            let (b, hint) = generate_hints(m, &extract);
+
+            let script = script! {
+                { hint.encode(EncodingMode::Canonical) }
+                { extract.clone() }
+                { Sha256ChannelGadget::unpack_multi_m31_canonical(m) }
+                for i in 0..m {
+                    { b[i] }
+                    OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+
+        for _ in 0..10 {
+            extract.iter_mut().for_each(|v| *v = prng.gen());
+            let (b, hint) = generate_hints(8, &extract);
+
+            let script = script! {
+                { hint.encode(EncodingMode::Canonical) }
+                { extract.clone() }
+                { Sha256ChannelGadget::unpack_multi_m31_canonical(8) }
+                for i in 0..8 {
+                    { b[i] }
+                    OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_unpack_multi_m31_canonical_rejects_non_canonical_zero() {
+        let extract = vec![0x00, 0x00, 0x00, 0x80];
+
+        // zero must be hinted as the empty string, not as the negative zero
+        let script = script! {
+            { vec![0x80u8] }
+            1
+            { vec![0u8; 28] }
+            { [extract.clone(), vec![0u8; 28]].concat() }
+            { Sha256ChannelGadget::unpack_multi_m31_canonical(1) }
+            OP_DROP
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+
+        let script = script! {
+            0
+            1
+            { vec![0u8; 28] }
+            { [extract, vec![0u8; 28]].concat() }
+            { Sha256ChannelGadget::unpack_multi_m31_canonical(1) }
+            OP_DROP
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+    }
 }
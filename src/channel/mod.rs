@@ -8,11 +8,23 @@ use stwo_prover::core::fields::qm31::QM31;
 
 mod bitcoin_script;
 use crate::treepp::pushable::{Builder, Pushable};
+use crate::treepp::*;
 pub use bitcoin_script::*;
 
 pub use stwo_prover::core::channel::Sha256Channel;
 use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
 
+/// Construct a channel whose digest is the hash of a claimed value, so that the prover and the
+/// verifier initialize the channel in the same way.
+pub fn sha256_channel_from_claim(claim: M31) -> Sha256Channel {
+    let mut hasher = Sha256::new();
+    Digest::update(&mut hasher, claim.0.to_le_bytes());
+
+    let mut channel = Sha256Channel::default();
+    channel.update_digest(Sha256Hash::from(hasher.finalize().to_vec()));
+    channel
+}
+
 /// A wrapper trait to implement hint-related method for channels.
 pub trait ChannelWithHint: Channel {
     /// Draw raw m31 elements.
@@ -38,6 +50,9 @@ pub trait ChannelWithHint: Channel {
 
         (trimmed_results, res.1)
     }
+
+    /// Absorb an arbitrary byte string by hashing it after the channel digest.
+    fn mix_bytes(&mut self, data: &[u8]);
 }
 
 impl ChannelWithHint for Sha256Channel {
@@ -60,6 +75,13 @@ impl ChannelWithHint for Sha256Channel {
 
         generate_hints(m, &extract)
     }
+
+    fn mix_bytes(&mut self, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        Digest::update(&mut hasher, self.digest);
+        Digest::update(&mut hasher, data);
+        self.update_digest(Sha256Hash::from(hasher.finalize().to_vec()));
+    }
 }
 
 fn generate_hints(m: usize, extract: &[u8]) -> (Vec<M31>, DrawHints) {
@@ -91,7 +113,7 @@ fn generate_hints(m: usize, extract: &[u8]) -> (Vec<M31>, DrawHints) {
 }
 
 /// Basic hint structure for extracting a single qm31 element.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BitcoinIntegerEncodedData {
     /// negative zero (will be represented by 0x80).
     NegativeZero,
@@ -99,6 +121,26 @@ pub enum BitcoinIntegerEncodedData {
     Other(i64),
 }
 
+impl BitcoinIntegerEncodedData {
+    /// Return the integer value, where the negative zero is mapped to 0.
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            BitcoinIntegerEncodedData::NegativeZero => 0,
+            BitcoinIntegerEncodedData::Other(v) => *v,
+        }
+    }
+
+    /// Construct from an integer value, with a flag indicating whether a zero is the negative zero.
+    pub fn from_i64_with_sign(v: i64, negative_zero: bool) -> Self {
+        if negative_zero {
+            assert_eq!(v, 0);
+            BitcoinIntegerEncodedData::NegativeZero
+        } else {
+            BitcoinIntegerEncodedData::Other(v)
+        }
+    }
+}
+
 impl Default for BitcoinIntegerEncodedData {
     fn default() -> Self {
         Self::Other(0)
@@ -141,3 +183,80 @@ impl Pushable for DrawHints {
         builder
     }
 }
+
+/// The encoding of the hints for drawing m31 elements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncodingMode {
+    /// One Bitcoin integer per element, with the sign carrying the high bit, which requires the
+    /// negative zero (0x80) to be handled specially. Used by `unpack_multi_m31`.
+    #[default]
+    Compatible,
+    /// Two Bitcoin integers per element, the lower 31 bits (zero is always the empty string) and
+    /// the high bit. Used by `unpack_multi_m31_canonical`.
+    Canonical,
+}
+
+impl DrawHints {
+    /// Encode the hints in the given mode.
+    pub fn encode(&self, mode: EncodingMode) -> Script {
+        match mode {
+            EncodingMode::Compatible => script! {
+                { self.clone() }
+            },
+            EncodingMode::Canonical => {
+                let n = self.0.len();
+                let pairs = self
+                    .0
+                    .iter()
+                    .map(|data| match data {
+                        BitcoinIntegerEncodedData::NegativeZero => (0i64, 1i64),
+                        BitcoinIntegerEncodedData::Other(v) => (v.abs(), (*v < 0) as i64),
+                    })
+                    .collect::<Vec<_>>();
+                script! {
+                    for pair in pairs.iter() {
+                        { pair.0 }
+                        { pair.1 }
+                    }
+                    if n % 8 != 0 {
+                        { self.1.clone() }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{sha256_channel_from_claim, BitcoinIntegerEncodedData};
+    use stwo_prover::core::channel::Channel;
+    use stwo_prover::core::fields::m31::M31;
+
+    #[test]
+    fn test_bitcoin_integer_encoded_data_i64() {
+        for v in [0i64, 1, -1, 0x7fffffff, -0x7fffffff] {
+            let data = BitcoinIntegerEncodedData::from_i64_with_sign(v, false);
+            assert_eq!(data, BitcoinIntegerEncodedData::Other(v));
+            assert_eq!(data.to_i64(), v);
+        }
+
+        let data = BitcoinIntegerEncodedData::from_i64_with_sign(0, true);
+        assert_eq!(data, BitcoinIntegerEncodedData::NegativeZero);
+        assert_eq!(data.to_i64(), 0);
+    }
+
+    #[test]
+    fn test_sha256_channel_from_claim() {
+        let claim = M31::from(443693538);
+
+        let mut prover_channel = sha256_channel_from_claim(claim);
+        let mut verifier_channel = sha256_channel_from_claim(claim);
+        assert_eq!(prover_channel.digest(), verifier_channel.digest());
+        assert_eq!(prover_channel.draw_felt(), verifier_channel.draw_felt());
+
+        let mut other_channel = sha256_channel_from_claim(claim + M31::from(1));
+        assert_ne!(other_channel.digest(), verifier_channel.digest());
+        assert_ne!(other_channel.draw_felt(), verifier_channel.draw_felt());
+    }
+}
@@ -1,3 +1,5 @@
 pub mod plonk;
 
 pub mod primitives;
+
+pub mod verifier;
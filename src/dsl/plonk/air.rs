@@ -0,0 +1,151 @@
+use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
+use crate::dsl::plonk::logup_gadget::{
+    constant_commitment, evaluate_logup_constraints, verify_claimed_sum_divided, LookupElementsVar,
+};
+use crate::dsl::primitives::qm31::QM31Var;
+use crate::dsl::primitives::table::TableVar;
+use crate::dsl::verifier::{CompositionInputs, GenericVerifier, VerifierHints};
+use anyhow::{anyhow, bail, Result};
+use stwo_prover::core::prover::{N_QUERIES, PROOF_OF_WORK_BITS};
+
+/// The index of the constant tree, whose commitment is fixed by the circuit.
+const CONSTANT_TREE: usize = 2;
+
+/// The Plonk verifier assembled from the description of the Fibonacci Plonk AIR.
+///
+/// The trace tree holds the multiplicity and the a/b/c values, the interaction tree holds the four
+/// partial evaluations of the a/b fraction sum and of the c fraction sum, the latter also sampled
+/// at the previous row, and the constant tree holds the a/b/c wires and the op column.
+pub fn plonk_generic_verifier() -> GenericVerifier {
+    let log_sizes = |n_columns: usize| vec![LOG_N_ROWS; n_columns];
+
+    GenericVerifier {
+        column_log_sizes: vec![log_sizes(4), log_sizes(8), log_sizes(4)],
+        mask: vec![
+            vec![vec![0]; 4],
+            [vec![vec![0]; 4], vec![vec![0, -1]; 4]].concat(),
+            vec![vec![0]; 4],
+        ],
+        n_interaction_draws: 2,
+        pinned_commitments: vec![(CONSTANT_TREE, constant_commitment())],
+        composition: Box::new(plonk_constraints),
+        n_queries: N_QUERIES,
+        pow_bits: PROOF_OF_WORK_BITS,
+    }
+}
+
+/// Evaluate the arithmetic constraint and the two LogUp constraints of the Plonk component.
+fn plonk_constraints(table: &TableVar, inputs: &CompositionInputs) -> Result<Vec<QM31Var>> {
+    if inputs.draws.len() != 2 || inputs.extra.len() != 2 {
+        bail!("the Plonk AIR takes the lookup elements and the claimed sums");
+    }
+
+    let trace = &inputs.oods_values[0];
+    let constant = &inputs.oods_values[CONSTANT_TREE];
+
+    let mult_var = &trace[0][0];
+    let a_val_var = &trace[1][0];
+    let b_val_var = &trace[2][0];
+    let c_val_var = &trace[3][0];
+    let op_var = &constant[3][0];
+
+    let a_val_times_b_val = a_val_var * (table, b_val_var);
+    let res1 = c_val_var
+        - &(&(op_var * (table, &(&(a_val_var + b_val_var) - &a_val_times_b_val)))
+            + &a_val_times_b_val);
+
+    let lookup_elements = LookupElementsVar {
+        z: inputs.draws[0].clone(),
+        alpha: inputs.draws[1].clone(),
+    };
+
+    // the sampled values are ordered by column, so the c fraction sum at the current row and at
+    // the previous row are interleaved, as `evaluate_logup_constraints` expects
+    let interaction_values: [QM31Var; 12] = inputs.oods_values[1]
+        .concat()
+        .try_into()
+        .map_err(|_| anyhow!("the interaction tree must have twelve sampled values"))?;
+
+    let claimed_sum = &inputs.extra[0];
+    let claimed_sum_divided = &inputs.extra[1];
+    verify_claimed_sum_divided(claimed_sum, claimed_sum_divided, LOG_N_ROWS)?;
+
+    let (logup_res1, logup_res2) = evaluate_logup_constraints(
+        table,
+        &lookup_elements,
+        [&constant[0][0], &constant[1][0], &constant[2][0]],
+        [a_val_var, b_val_var, c_val_var],
+        mult_var,
+        &interaction_values,
+        claimed_sum_divided,
+    );
+
+    Ok(vec![res1, logup_res1, logup_res2])
+}
+
+impl From<&Hints> for VerifierHints {
+    fn from(hints: &Hints) -> Self {
+        let fiat_shamir_hints = &hints.fiat_shamir_hints;
+
+        Self {
+            commitments: fiat_shamir_hints.commitments.to_vec(),
+            oods_values: vec![
+                fiat_shamir_hints.trace_oods_values.clone(),
+                fiat_shamir_hints.interaction_oods_values.clone(),
+                fiat_shamir_hints.constant_oods_values.clone(),
+                fiat_shamir_hints.composition_oods_values.clone(),
+            ],
+            extra: vec![
+                fiat_shamir_hints.claimed_sum,
+                fiat_shamir_hints.claimed_sum_divided,
+            ],
+            fri_layer_commitments: fiat_shamir_hints.fri_layer_commitments.clone(),
+            last_layer: fiat_shamir_hints.last_layer.clone(),
+            pow_nonce: fiat_shamir_hints.pow_hint.nonce,
+            merkle_proofs: vec![
+                fiat_shamir_hints.merkle_proofs_traces.clone(),
+                fiat_shamir_hints.merkle_proofs_interactions.clone(),
+                fiat_shamir_hints.merkle_proofs_constants.clone(),
+                fiat_shamir_hints.merkle_proofs_compositions.clone(),
+            ],
+            fri_twin_proofs: hints
+                .per_query_fold_hints
+                .iter()
+                .map(|fold_hints| fold_hints.twin_proofs.clone())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dsl::plonk::air::plonk_generic_verifier;
+    use crate::dsl::plonk::hints::Hints;
+    use crate::dsl::verifier::VerifierHints;
+    use num_traits::One;
+    use stwo_prover::core::fields::qm31::QM31;
+
+    #[test]
+    fn test_plonk_generic_verifier() {
+        let verifier = plonk_generic_verifier();
+
+        let hints = Hints::instance();
+        let compiled = verifier.compile(&VerifierHints::from(&hints)).unwrap();
+
+        for idx in 0..verifier.num_programs() {
+            let result = compiled.execute(idx);
+            assert!(
+                result.error.is_none(),
+                "program {} fails: {:?}",
+                idx,
+                result.error
+            );
+            assert_eq!(result.final_stack, compiled.outputs[idx]);
+        }
+
+        // a composition value that does not match the constraints is rejected
+        let mut tampered = VerifierHints::from(&hints);
+        tampered.oods_values[3][0] += QM31::one();
+        assert!(verifier.compile(&tampered).is_err());
+    }
+}
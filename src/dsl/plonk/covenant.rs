@@ -1,12 +1,10 @@
 use crate::dsl::plonk::hints::Hints;
+use crate::dsl::verifier::SplitVerifier;
+pub use crate::dsl::verifier::Witness;
 use crate::treepp::*;
 use crate::utils::hash;
 use crate::OP_HINT;
-use anyhow::Result;
-use bitcoin::script::write_scriptint;
-use bitcoin_script_dsl::compiler::Compiler;
-use bitcoin_script_dsl::constraint_system::Element;
-use bitcoin_script_dsl::ldm::LDM;
+use anyhow::{anyhow, Result};
 use bitcoin_scriptexec::utils::scriptint_vec;
 use covenants_gadgets::utils::stack_hash::StackHash;
 use covenants_gadgets::CovenantProgram;
@@ -14,8 +12,10 @@ use sha2::digest::Update;
 use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::sync::OnceLock;
-
-pub type Witness = Vec<Vec<u8>>;
+use stwo_prover::core::pcs::PcsConfig;
+use stwo_prover::core::prover::{StarkProof, N_QUERIES};
+use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleHasher;
+use stwo_prover::examples::plonk::PlonkComponent;
 
 pub struct PlonkVerifierProgram {}
 
@@ -58,6 +58,17 @@ impl From<PlonkVerifierState> for Script {
     }
 }
 
+/// Build the state at program counter `pc` from the stack left by the verifier, whose hash is
+/// committed in the state.
+pub fn state_from_stack(pc: usize, stack: Vec<Vec<u8>>) -> PlonkVerifierState {
+    let stack_hash = StackHash::compute(&stack);
+    PlonkVerifierState {
+        pc,
+        stack_hash,
+        stack,
+    }
+}
+
 pub struct PlonkAllInformation {
     pub scripts: Vec<Script>,
     pub witnesses: Vec<Witness>,
@@ -77,60 +88,52 @@ impl PlonkAllInformation {
             hints: self.witnesses[idx].clone(),
         }
     }
+
+    /// The expected stack depth after the verifier part at `pc` finishes, i.e., the number of
+    /// its outputs, which is checked before the remaining stack is committed into the stack hash.
+    pub fn expected_depth(&self, pc: usize) -> usize {
+        self.outputs[pc].len()
+    }
 }
 
 pub fn compute_all_information() -> PlonkAllInformation {
-    let mut scripts = vec![];
-    let mut witnesses = vec![];
-
-    let hints = Hints::instance();
-    let mut ldm = LDM::new();
-
-    let num_to_str = |v: i32| {
-        let mut out = [0u8; 8];
-        let len = write_scriptint(&mut out, v as i64);
-        out[0..len].to_vec()
-    };
+    compute_all_information_from_hints(&Hints::instance())
+}
 
-    let mut outputs = vec![];
-
-    for f in [
-        super::part1_fiat_shamir1::generate_cs,
-        super::part2_fiat_shamir2_and_constraint_num::generate_cs,
-        super::part3_constraint_denom::generate_cs,
-        super::part4_pair_vanishing_and_alphas::generate_cs,
-        super::part5_column_line_coeffs1::generate_cs,
-        super::part6_column_line_coeffs2::generate_cs,
-        super::part7_column_line_coeffs3::generate_cs,
-    ] {
-        let cs = f(&hints, &mut ldm).unwrap();
-        let program = Compiler::compile(cs).unwrap();
-
-        scripts.push(program.script);
-
-        let mut witness = vec![];
-        for entry in program.hint.iter() {
-            match &entry {
-                Element::Num(v) => {
-                    witness.push(num_to_str(*v));
-                }
-                Element::Str(v) => {
-                    witness.push(v.clone());
-                }
-            }
-        }
+/// Generate the verifier scripts, their witnesses, and their outputs for a Plonk proof.
+pub fn verify_proof_scripts(
+    plonk_component: &PlonkComponent,
+    proof: StarkProof<Sha256MerkleHasher>,
+    config: PcsConfig,
+) -> Result<PlonkAllInformation> {
+    let hints = Hints::from_proof(plonk_component, proof, config)
+        .map_err(|e| anyhow!("the proof cannot be verified: {:?}", e))?;
+    Ok(compute_all_information_from_hints(&hints))
+}
 
-        witnesses.push(witness);
-        outputs.push(
-            convert_to_witness(script! {
-                { ldm.hash_var.as_ref().unwrap().value.clone() }
-            })
-            .unwrap(),
-        );
-    }
+/// The verifier scripts, computed once and cached.
+///
+/// The scripts only depend on the Plonk circuit, not on the proof, so the cached scripts can be
+/// used with the witnesses of any proof for the same circuit.
+pub fn cached_verifier_scripts() -> &'static [Script] {
+    &PLONK_ALL_INFORMATION
+        .get_or_init(compute_all_information)
+        .scripts
+}
 
-    for query_idx in 0..8 {
-        for f in [
+/// The split Plonk verifier, consisting of the global parts, the per-query parts, and the cleanup.
+pub fn plonk_verifier() -> SplitVerifier<Hints> {
+    SplitVerifier {
+        prologue: vec![
+            super::part1_fiat_shamir1::generate_cs,
+            super::part2_fiat_shamir2_and_constraint_num::generate_cs,
+            super::part3_constraint_denom::generate_cs,
+            super::part4_pair_vanishing_and_alphas::generate_cs,
+            super::part5_column_line_coeffs1::generate_cs,
+            super::part6_column_line_coeffs2::generate_cs,
+            super::part7_column_line_coeffs3::generate_cs,
+        ],
+        per_query: vec![
             super::per_query_part1_folding::generate_cs,
             super::per_query_part2_num_trace::generate_cs,
             super::per_query_part3_num_constant::generate_cs,
@@ -139,70 +142,19 @@ pub fn compute_all_information() -> PlonkAllInformation {
             super::per_query_part6_num_interaction1::generate_cs,
             super::per_query_part7_num_interaction2::generate_cs,
             super::per_query_part8_last_step::generate_cs,
-        ] {
-            let dsl = f(&hints, &mut ldm, query_idx).unwrap();
-            let program = Compiler::compile(dsl).unwrap();
-
-            scripts.push(program.script);
-
-            let mut witness = vec![];
-            for entry in program.hint.iter() {
-                match &entry {
-                    Element::Num(v) => {
-                        witness.push(num_to_str(*v));
-                    }
-                    Element::Str(v) => {
-                        witness.push(v.clone());
-                    }
-                }
-            }
-
-            witnesses.push(witness);
-
-            outputs.push(
-                convert_to_witness(script! {
-                    { ldm.hash_var.as_ref().unwrap().value.clone() }
-                })
-                .unwrap(),
-            );
-        }
-    }
-
-    for f in [super::part8_cleanup::generate_cs] {
-        let cs = f(&hints, &mut ldm).unwrap();
-        let program = Compiler::compile(cs).unwrap();
-
-        scripts.push(program.script);
-
-        let mut witness = vec![];
-        for entry in program.hint.iter() {
-            match &entry {
-                Element::Num(v) => {
-                    witness.push(num_to_str(*v));
-                }
-                Element::Str(v) => {
-                    witness.push(v.clone());
-                }
-            }
-        }
-
-        witnesses.push(witness);
-
-        outputs.push(
-            convert_to_witness(script! {
-                { ldm.hash_var.as_ref().unwrap().value.clone() }
-            })
-            .unwrap(),
-        );
+        ],
+        n_queries: N_QUERIES,
+        epilogue: vec![super::part8_cleanup::generate_cs],
     }
+}
 
-    assert_eq!(scripts.len(), witnesses.len());
-    assert_eq!(scripts.len(), outputs.len());
+pub fn compute_all_information_from_hints(hints: &Hints) -> PlonkAllInformation {
+    let compiled = plonk_verifier().compile(hints).unwrap();
 
     PlonkAllInformation {
-        scripts,
-        witnesses,
-        outputs,
+        scripts: compiled.scripts,
+        witnesses: compiled.witnesses,
+        outputs: compiled.outputs,
     }
 }
 
@@ -264,7 +216,7 @@ impl CovenantProgram for PlonkVerifierProgram {
                     { all_information.scripts[script_idx].clone() }
 
                     OP_DEPTH
-                    { 1 }
+                    { all_information.expected_depth(script_idx) }
                     OP_EQUALVERIFY
 
                     { StackHash::hash_drop(1) }
@@ -312,28 +264,35 @@ impl CovenantProgram for PlonkVerifierProgram {
         let all_information = PLONK_ALL_INFORMATION.get_or_init(compute_all_information);
 
         let final_stack = all_information.outputs[id].to_vec();
-        let stack_hash = StackHash::compute(&final_stack);
-        Ok(Self::State {
-            pc: id + 1,
-            stack_hash,
-            stack: final_stack,
-        })
+        Ok(state_from_stack(id + 1, final_stack))
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::dsl::plonk::covenant::{
-        compute_all_information, PlonkVerifierProgram, PlonkVerifierState, PLONK_ALL_INFORMATION,
+        cached_verifier_scripts, compute_all_information, compute_all_information_from_hints,
+        plonk_verifier, state_from_stack, verify_proof_scripts, PlonkVerifierProgram,
+        PlonkVerifierState, PLONK_ALL_INFORMATION,
     };
+    use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
+    use crate::treepp::*;
+    use bitcoin_scriptexec::utils::scriptint_vec;
     use covenants_gadgets::test::{simulation_test, SimulationInstruction};
+    use covenants_gadgets::utils::stack_hash::StackHash;
+    use covenants_gadgets::CovenantProgram;
+    use num_traits::One;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::pcs::PcsConfig;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
 
     #[test]
     fn test_integration() {
         // The integration assumes a fee rate of 7 sat/vByte.
         // Note that in many situations, the fee rate is only 2 sat/vByte.
 
-        let mut fees = vec![114555, 210434, 103439, 101759, 93233, 81704, 92834];
+        let mut fees = vec![119055, 212434, 103439, 101759, 93233, 81704, 92834];
 
         for _ in 0..8 {
             fees.extend_from_slice(&[100926, 97300, 97167, 86891, 77679, 86863, 88865, 40467]);
@@ -362,4 +321,159 @@ mod test {
 
         simulation_test::<PlonkVerifierProgram>(72, &mut test_generator);
     }
+
+    #[test]
+    fn test_verify_proof_scripts() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let all_information = verify_proof_scripts(&plonk_component, proof, config).unwrap();
+        assert_eq!(all_information.scripts.len(), 7 + 8 * 8 + 1);
+
+        for (idx, script) in all_information.scripts.iter().enumerate() {
+            assert_eq!(all_information.outputs[idx].len(), 1);
+
+            let witness = convert_to_witness(Script::from(all_information.get_input(idx))).unwrap();
+            let script = script! {
+                { script.clone() }
+                { all_information.outputs[idx][0].clone() }
+                OP_EQUAL
+            };
+
+            let exec_result = execute_script_with_witness_unlimited_stack(script, witness);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cached_verifier_scripts() {
+        let scripts = cached_verifier_scripts();
+        let scripts_again = cached_verifier_scripts();
+        assert!(std::ptr::eq(scripts, scripts_again));
+        assert_eq!(scripts.len(), plonk_verifier().num_programs());
+
+        // a proof from different inputs: with a stricter proof of work, the prover grinds a
+        // different nonce, which still meets the default requirement but changes the queries
+        let config = PcsConfig::default();
+        let cached_nonce = Hints::instance().fiat_shamir_hints.pow_hint.nonce;
+        let (plonk_component, proof) = (1..)
+            .map(|extra_bits| {
+                let stricter_config = PcsConfig {
+                    pow_bits: config.pow_bits + extra_bits,
+                    ..config
+                };
+                prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, stricter_config)
+            })
+            .find(|(_, proof)| proof.commitment_scheme_proof.proof_of_work != cached_nonce)
+            .unwrap();
+        let all_information = verify_proof_scripts(&plonk_component, proof, config).unwrap();
+
+        let cached_information = PLONK_ALL_INFORMATION.get_or_init(compute_all_information);
+        assert_ne!(all_information.witnesses, cached_information.witnesses);
+
+        for (idx, script) in scripts.iter().enumerate() {
+            assert_eq!(script.as_bytes(), all_information.scripts[idx].as_bytes());
+
+            let witness = convert_to_witness(Script::from(all_information.get_input(idx))).unwrap();
+            let script = script! {
+                { script.clone() }
+                { all_information.outputs[idx][0].clone() }
+                OP_EQUAL
+            };
+
+            let exec_result = execute_script_with_witness_unlimited_stack(script, witness);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_state_from_stack() {
+        let all_information = PLONK_ALL_INFORMATION.get_or_init(compute_all_information);
+
+        for (idx, output) in all_information.outputs.iter().enumerate().take(3) {
+            let state = state_from_stack(idx + 1, output.clone());
+            assert_eq!(state.pc, idx + 1);
+            assert_eq!(state.stack_hash, StackHash::compute(output));
+            assert_eq!(&state.stack, output);
+
+            let old_state = PlonkVerifierProgram::new();
+            let state_from_run =
+                PlonkVerifierProgram::run(idx, &old_state, &all_information.get_input(idx))
+                    .unwrap();
+            assert_eq!(state_from_run.stack_hash, state.stack_hash);
+        }
+    }
+
+    #[test]
+    fn test_expected_depth() {
+        let all_information = PLONK_ALL_INFORMATION.get_or_init(compute_all_information);
+
+        for pc in 0..all_information.scripts.len() {
+            let witness = convert_to_witness(Script::from(all_information.get_input(pc))).unwrap();
+            let exec_result = execute_verifier(all_information.scripts[pc].clone(), witness);
+            assert!(exec_result.success);
+            assert_eq!(
+                exec_result.final_stack.len(),
+                all_information.expected_depth(pc)
+            );
+        }
+    }
+
+    #[test]
+    fn test_execute_verifier() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let all_information = verify_proof_scripts(&plonk_component, proof, config).unwrap();
+
+        let witness = convert_to_witness(Script::from(all_information.get_input(0))).unwrap();
+        let exec_result = execute_verifier(all_information.scripts[0].clone(), witness.clone());
+        assert_eq!(exec_result.final_stack, all_information.outputs[0]);
+
+        let script = script! {
+            { all_information.scripts[0].clone() }
+            { all_information.outputs[0][0].clone() }
+            OP_EQUAL
+        };
+        let exec_result = execute_verifier(script, witness.clone());
+        assert!(exec_result.success);
+        assert!(exec_result.error.is_none());
+
+        // a script that is missing its last output fails
+        let script = script! {
+            { all_information.scripts[0].clone() }
+            OP_DROP
+            OP_VERIFY
+        };
+        let exec_result = execute_verifier(script, witness);
+        assert!(!exec_result.success);
+        assert!(exec_result.error.is_some());
+    }
+
+    #[test]
+    fn test_part2_rejects_tampered_claimed_sum_divided() {
+        let hints = Hints::instance();
+        let all_information = compute_all_information_from_hints(&hints);
+
+        let input = all_information.get_input(1);
+        let witness = convert_to_witness(Script::from(input.clone())).unwrap();
+        let exec_result = execute_verifier(all_information.scripts[1].clone(), witness);
+        assert_eq!(exec_result.final_stack, all_information.outputs[1]);
+
+        // replace one limb of the claimed sum divided in the hints of part2
+        let limb = hints.fiat_shamir_hints.claimed_sum_divided.0 .0;
+        let mut tampered_input = input;
+        let elem = tampered_input
+            .hints
+            .iter_mut()
+            .find(|elem| **elem == scriptint_vec(limb.0 as i64))
+            .unwrap();
+        *elem = scriptint_vec((limb + M31::one()).0 as i64);
+
+        let witness = convert_to_witness(Script::from(tampered_input)).unwrap();
+        let exec_result = execute_verifier(all_information.scripts[1].clone(), witness);
+        assert!(!exec_result.success);
+    }
 }
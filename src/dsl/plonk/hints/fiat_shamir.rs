@@ -2,6 +2,7 @@ use crate::dsl::plonk::hints::LOG_N_ROWS;
 use crate::fri::QueriesWithHint;
 use crate::merkle_tree::MerkleTreeTwinProof;
 use crate::pow::PoWHint;
+use crate::treepp::pushable::{Builder, Pushable};
 use itertools::{izip, Itertools};
 use stwo_prover::constraint_framework::logup::LookupElements;
 use stwo_prover::core::air::{Component, Components};
@@ -62,8 +63,8 @@ pub struct FiatShamirOutput {
     /// FRI folding alphas
     pub fri_layer_alphas: Vec<QM31>,
 
-    /// Last layer
-    pub last_layer: QM31,
+    /// Last layer coefficients
+    pub last_layer: Vec<QM31>,
 }
 
 pub struct FiatShamirHints {
@@ -88,8 +89,8 @@ pub struct FiatShamirHints {
     /// FRI folding alphas
     pub fri_layer_alphas: Vec<QM31>,
 
-    /// Last layer
-    pub last_layer: QM31,
+    /// Last layer coefficients
+    pub last_layer: Vec<QM31>,
 
     /// PoW hint
     pub pow_hint: PoWHint,
@@ -106,17 +107,128 @@ pub struct FiatShamirHints {
     /// Merkle proofs for the composition Merkle tree.
     pub merkle_proofs_compositions: Vec<MerkleTreeTwinProof>,
 
+    /// Claimed sum of the LogUp, which is sent along with the proof
+    pub claimed_sum: SecureField,
+
     /// Claimed sum divided by the range
     pub claimed_sum_divided: SecureField,
 }
 
+impl FiatShamirHints {
+    /// The number of witness elements when the hints are pushed through their `Pushable`
+    /// implementation, where a hash is one element and a qm31 element is four.
+    pub fn witness_element_count(&self) -> usize {
+        let num_qm31 = self.trace_oods_values.len()
+            + self.interaction_oods_values.len()
+            + self.constant_oods_values.len()
+            + self.composition_oods_values.len()
+            + self.fri_layer_alphas.len()
+            + self.last_layer.len()
+            + 2;
+
+        let num_hashes = self.commitments.len() + self.fri_layer_commitments.len();
+
+        let num_pow = 2 + self.pow_hint.msb.is_some() as usize;
+
+        let num_merkle = self
+            .merkle_proofs_traces
+            .iter()
+            .chain(self.merkle_proofs_interactions.iter())
+            .chain(self.merkle_proofs_constants.iter())
+            .chain(self.merkle_proofs_compositions.iter())
+            .map(|proof| proof.left.len() + proof.right.len() + proof.path.siblings.len())
+            .sum::<usize>();
+
+        // the last layer is prefixed by its number of coefficients
+        4 * num_qm31 + num_hashes + num_pow + num_merkle + 1
+    }
+}
+
+impl Pushable for FiatShamirHints {
+    fn bitcoin_script_push(&self, mut builder: Builder) -> Builder {
+        for commitment in self.commitments.iter() {
+            builder = commitment.bitcoin_script_push(builder);
+        }
+        for v in self
+            .trace_oods_values
+            .iter()
+            .chain(self.interaction_oods_values.iter())
+            .chain(self.constant_oods_values.iter())
+            .chain(self.composition_oods_values.iter())
+        {
+            builder = v.bitcoin_script_push(builder);
+        }
+        for commitment in self.fri_layer_commitments.iter() {
+            builder = commitment.bitcoin_script_push(builder);
+        }
+        for alpha in self.fri_layer_alphas.iter() {
+            builder = alpha.bitcoin_script_push(builder);
+        }
+        builder = (self.last_layer.len() as u32).bitcoin_script_push(builder);
+        for coeff in self.last_layer.iter() {
+            builder = coeff.bitcoin_script_push(builder);
+        }
+        builder = self.pow_hint.bitcoin_script_push(builder);
+        for proof in self
+            .merkle_proofs_traces
+            .iter()
+            .chain(self.merkle_proofs_interactions.iter())
+            .chain(self.merkle_proofs_constants.iter())
+            .chain(self.merkle_proofs_compositions.iter())
+        {
+            builder = proof.bitcoin_script_push(builder);
+        }
+        builder = self.claimed_sum.bitcoin_script_push(builder);
+        self.claimed_sum_divided.bitcoin_script_push(builder)
+    }
+}
+
+/// The channel digests recorded after each Fiat-Shamir step, in the order:
+/// - after the trace commitment (and the lookup elements)
+/// - after the interaction and constant commitments (and the composition random coefficient)
+/// - after the composition commitment (and the OODS point)
+/// - after the OODS values (and the FRI random coefficients)
+/// - after each FRI inner layer commitment (and its folding alpha)
+#[derive(Default, Clone)]
+pub struct FiatShamirTrace {
+    /// the recorded digests
+    pub digests: Vec<Sha256Hash>,
+}
+
+impl FiatShamirTrace {
+    /// The number of digests recorded before the FRI layers.
+    pub const NUM_DIGESTS_BEFORE_FRI: usize = 4;
+
+    /// The digests recorded after each FRI inner layer.
+    pub fn fri_layer_digests(&self) -> &[Sha256Hash] {
+        &self.digests[Self::NUM_DIGESTS_BEFORE_FRI.min(self.digests.len())..]
+    }
+}
+
 /// Generate Fiat Shamir hints along with fri inputs
+///
+/// If `trace` is provided, the channel digest after each step is recorded into it.
 pub fn compute_fiat_shamir_hints(
     proof: StarkProof<Sha256MerkleHasher>,
     channel: &mut Sha256Channel,
     component: &PlonkComponent,
     config: PcsConfig,
+    mut trace: Option<&mut FiatShamirTrace>,
 ) -> Result<(FiatShamirOutput, FiatShamirHints), VerificationError> {
+    let mut record = |channel: &Sha256Channel| {
+        if let Some(trace) = trace.as_mut() {
+            trace.digests.push(channel.digest());
+        }
+    };
+
+    // the trace, interaction, constant, and composition trees
+    if proof.commitments.len() != 4 {
+        return Err(VerificationError::InvalidStructure(format!(
+            "expected 4 commitments, found {}",
+            proof.commitments.len()
+        )));
+    }
+
     let components = Components([component as &dyn Component].to_vec());
     let mut commitment_scheme: CommitmentSchemeVerifier<Sha256MerkleChannel> =
         CommitmentSchemeVerifier::new(config);
@@ -132,11 +244,13 @@ pub fn compute_fiat_shamir_hints(
     commitment_scheme.commit(proof.commitments[0], &sizes[0], channel);
     let lookup_elements = LookupElements::<2>::draw(channel);
     assert_eq!(lookup_elements, component.lookup_elements);
+    record(channel);
 
     // step 2: absorb interaction commitment and constant commitment, squeeze random coefficient for composition folding
     commitment_scheme.commit(proof.commitments[1], &sizes[1], channel);
     commitment_scheme.commit(proof.commitments[2], &sizes[2], channel);
     let _ = channel.draw_felt();
+    record(channel);
 
     // step 3: absorb composition commitment, squeeze oods point
     commitment_scheme.commit(
@@ -145,6 +259,7 @@ pub fn compute_fiat_shamir_hints(
         channel,
     );
     let oods_point = CirclePoint::<SecureField>::get_random_point(channel);
+    record(channel);
 
     // step 4: draw fri folding coefficient with all oods values
     channel.mix_felts(
@@ -157,6 +272,7 @@ pub fn compute_fiat_shamir_hints(
 
     let line_batch_random_coeff = channel.draw_felt();
     let fri_fold_random_coeff = channel.draw_felt();
+    record(channel);
 
     // step 5: fri layer operator coefficients (intermediate)
     // Get mask sample points relative to oods point.
@@ -202,6 +318,7 @@ pub fn compute_fiat_shamir_hints(
         ));
 
         let folding_alpha = channel.draw_felt();
+        record(channel);
         fri_layer_alphas.push(folding_alpha);
         fri_layer_commitments.push(proof.commitment);
 
@@ -220,6 +337,8 @@ pub fn compute_fiat_shamir_hints(
     }
 
     // step 6: fri layer operator coefficient (last layer)
+    // the degree bound only reaches the last layer after the right number of folding steps, so
+    // this also checks the number of folding alphas
     if layer_bound.log_degree_bound != config.fri_config.log_last_layer_degree_bound {
         return Err(VerificationError::Fri(
             FriVerificationError::InvalidNumFriLayers,
@@ -227,7 +346,6 @@ pub fn compute_fiat_shamir_hints(
     }
     let last_layer_poly = proof.commitment_scheme_proof.fri_proof.last_layer_poly;
 
-    assert_eq!(last_layer_poly.len(), 1);
     if last_layer_poly.len() > (1 << config.fri_config.log_last_layer_degree_bound) {
         return Err(VerificationError::Fri(
             FriVerificationError::LastLayerDegreeInvalid,
@@ -280,25 +398,25 @@ pub fn compute_fiat_shamir_hints(
         &queries_parents,
         &proof.commitment_scheme_proof.queried_values[0],
         &proof.commitment_scheme_proof.decommitments[0],
-    );
+    )?;
     let merkle_proofs_interactions = MerkleTreeTwinProof::from_stwo_proof(
         (max_column_bound.log_degree_bound + config.fri_config.log_blowup_factor) as usize,
         &queries_parents,
         &proof.commitment_scheme_proof.queried_values[1],
         &proof.commitment_scheme_proof.decommitments[1],
-    );
+    )?;
     let merkle_proofs_constants = MerkleTreeTwinProof::from_stwo_proof(
         (max_column_bound.log_degree_bound + config.fri_config.log_blowup_factor) as usize,
         &queries_parents,
         &proof.commitment_scheme_proof.queried_values[2],
         &proof.commitment_scheme_proof.decommitments[2],
-    );
+    )?;
     let merkle_proofs_compositions = MerkleTreeTwinProof::from_stwo_proof(
         (max_column_bound.log_degree_bound + config.fri_config.log_blowup_factor) as usize,
         &queries_parents,
         &proof.commitment_scheme_proof.queried_values[3],
         &proof.commitment_scheme_proof.decommitments[3],
-    );
+    )?;
 
     for (&query, twin_proof) in queries_parents.iter().zip(merkle_proofs_traces.iter()) {
         assert!(twin_proof.verify(
@@ -385,7 +503,7 @@ pub fn compute_fiat_shamir_hints(
         circle_poly_alpha: fri_fold_random_coeff,
         fri_layer_commitments: fri_layer_commitments.clone(),
         fri_layer_alphas: fri_layer_alphas.clone(),
-        last_layer: last_layer_poly.to_vec()[0],
+        last_layer: last_layer_poly.to_vec(),
     };
 
     let claimed_sum_divided =
@@ -420,14 +538,281 @@ pub fn compute_fiat_shamir_hints(
             .collect_vec(),
         fri_layer_commitments,
         fri_layer_alphas,
-        last_layer: last_layer_poly.to_vec()[0],
+        last_layer: last_layer_poly.to_vec(),
         pow_hint,
         merkle_proofs_traces,
         merkle_proofs_interactions,
         merkle_proofs_constants,
         merkle_proofs_compositions,
+        claimed_sum: component.claimed_sum,
         claimed_sum_divided,
     };
 
     Ok((output, hints))
 }
+
+#[cfg(test)]
+mod test {
+    use crate::dsl::plonk::hints::fiat_shamir::compute_fiat_shamir_hints;
+    use crate::dsl::plonk::hints::{FiatShamirTrace, Hints, LOG_N_ROWS};
+    use crate::fri::{assert_query_values_aligned, num_fri_layers};
+    use crate::tests_utils::witness::dump_witness;
+    use crate::utils::get_rand_qm31;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::constraint_framework::logup::LookupElements;
+    use stwo_prover::core::air::{Component, Components};
+    use stwo_prover::core::channel::{Channel, Sha256Channel};
+    use stwo_prover::core::circle::CirclePoint;
+    use stwo_prover::core::fields::qm31::SecureField;
+    use stwo_prover::core::pcs::{CommitmentSchemeVerifier, PcsConfig};
+    use stwo_prover::core::prover::VerificationError;
+    use stwo_prover::core::vcs::sha256_hash::Sha256Hasher;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
+
+    #[test]
+    fn test_query_values_aligned() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        let (output, hints) =
+            compute_fiat_shamir_hints(proof, &mut channel, &plonk_component, config, None).unwrap();
+
+        let twin_proofs = [
+            hints.merkle_proofs_traces.as_slice(),
+            hints.merkle_proofs_interactions.as_slice(),
+            hints.merkle_proofs_constants.as_slice(),
+            hints.merkle_proofs_compositions.as_slice(),
+        ];
+        assert!(assert_query_values_aligned(
+            &output.queries_parents,
+            &twin_proofs,
+            &output.queried_values_left,
+            &output.queried_values_right,
+        )
+        .is_ok());
+
+        // swap the left values of two queries that are not duplicates of each other
+        let mut shuffled = output.queried_values_left.clone();
+        let j = (1..shuffled.len())
+            .find(|&j| shuffled[j] != shuffled[0])
+            .unwrap();
+        shuffled.swap(0, j);
+        assert!(assert_query_values_aligned(
+            &output.queries_parents,
+            &twin_proofs,
+            &shuffled,
+            &output.queried_values_right,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_num_fri_layers() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+        let n_inner_layers = proof.commitment_scheme_proof.fri_proof.inner_layers.len();
+
+        let mut channel = Sha256Channel::default();
+        let (output, _) =
+            compute_fiat_shamir_hints(proof, &mut channel, &plonk_component, config, None).unwrap();
+
+        let n_layers = num_fri_layers(
+            output.max_column_log_degree_bound,
+            config.fri_config.log_last_layer_degree_bound,
+        )
+        .unwrap();
+        assert_eq!(n_layers, n_inner_layers);
+        assert_eq!(n_layers, output.fri_layer_alphas.len());
+
+        assert_eq!(num_fri_layers(0, 0), None);
+        assert_eq!(num_fri_layers(3, 3), None);
+        assert_eq!(num_fri_layers(3, 2), Some(0));
+    }
+
+    #[test]
+    fn test_fiat_shamir_trace() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut trace = FiatShamirTrace::default();
+        let hints =
+            Hints::from_proof_with_trace(&plonk_component, proof.clone(), config, Some(&mut trace))
+                .unwrap();
+        let hints = hints.fiat_shamir_hints;
+
+        assert_eq!(
+            trace.digests.len(),
+            FiatShamirTrace::NUM_DIGESTS_BEFORE_FRI + hints.fri_layer_commitments.len()
+        );
+
+        // recompute the channel step by step
+        let mut channel = Sha256Channel::default();
+        let mut commitment_scheme: CommitmentSchemeVerifier<Sha256MerkleChannel> =
+            CommitmentSchemeVerifier::new(config);
+        let log_size = Components([&plonk_component as &dyn Component].to_vec())
+            .composition_log_degree_bound();
+
+        commitment_scheme.commit(proof.commitments[0], &[log_size; 4], &mut channel);
+        let _ = LookupElements::<2>::draw(&mut channel);
+        assert_eq!(trace.digests[0], channel.digest());
+
+        commitment_scheme.commit(proof.commitments[1], &[log_size; 8], &mut channel);
+        commitment_scheme.commit(proof.commitments[2], &[log_size; 4], &mut channel);
+        let _ = channel.draw_felt();
+        assert_eq!(trace.digests[1], channel.digest());
+
+        commitment_scheme.commit(proof.commitments[3], &[log_size; 4], &mut channel);
+        let _ = CirclePoint::<SecureField>::get_random_point(&mut channel);
+        assert_eq!(trace.digests[2], channel.digest());
+
+        channel.mix_felts(
+            &proof
+                .commitment_scheme_proof
+                .sampled_values
+                .clone()
+                .flatten_cols(),
+        );
+        let _ = channel.draw_felt();
+        let _ = channel.draw_felt();
+        assert_eq!(trace.digests[3], channel.digest());
+
+        for (commitment, digest) in hints
+            .fri_layer_commitments
+            .iter()
+            .zip(trace.fri_layer_digests().iter())
+        {
+            channel.update_digest(Sha256Hasher::concat_and_hash(commitment, &channel.digest()));
+            let _ = channel.draw_felt();
+            assert_eq!(*digest, channel.digest());
+        }
+    }
+
+    #[test]
+    fn test_truncated_fri_layers() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        assert!(compute_fiat_shamir_hints(
+            proof.clone(),
+            &mut channel,
+            &plonk_component,
+            config,
+            None
+        )
+        .is_ok());
+
+        let mut truncated_proof = proof;
+        truncated_proof
+            .commitment_scheme_proof
+            .fri_proof
+            .inner_layers
+            .pop();
+
+        let mut channel = Sha256Channel::default();
+        assert!(compute_fiat_shamir_hints(
+            truncated_proof,
+            &mut channel,
+            &plonk_component,
+            config,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_missing_commitment() {
+        let config = PcsConfig::default();
+        let (plonk_component, mut proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+        proof.commitments.0.pop();
+
+        let mut channel = Sha256Channel::default();
+        let res = compute_fiat_shamir_hints(proof, &mut channel, &plonk_component, config, None);
+        assert!(matches!(res, Err(VerificationError::InvalidStructure(_))));
+    }
+
+    #[test]
+    fn test_witness_element_count() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        // count the elements from the stwo proof itself, rather than from the hint fields
+        let commitment_scheme_proof = &proof.commitment_scheme_proof;
+        let n_hashes =
+            proof.commitments.len() + commitment_scheme_proof.fri_proof.inner_layers.len();
+        let n_qm31 = commitment_scheme_proof
+            .sampled_values
+            .iter()
+            .flatten()
+            .flatten()
+            .count()
+            + commitment_scheme_proof.fri_proof.inner_layers.len()
+            + commitment_scheme_proof.fri_proof.last_layer_poly.len()
+            // the claimed sum and the claimed sum divided by the domain size
+            + 2;
+        let n_columns = commitment_scheme_proof
+            .queried_values
+            .iter()
+            .map(|columns| columns.len())
+            .collect::<Vec<_>>();
+
+        let mut channel = Sha256Channel::default();
+        let (output, hints) =
+            compute_fiat_shamir_hints(proof, &mut channel, &plonk_component, config, None).unwrap();
+
+        // each query opens a twin leaf in each tree, whose path skips the leaf layer
+        let path_len =
+            (output.max_column_log_degree_bound + output.fri_log_blowup_factor - 1) as usize;
+        let n_merkle = output.queries_parents.len()
+            * n_columns.iter().map(|n| 2 * n + path_len).sum::<usize>();
+
+        // the last layer is prefixed by its number of coefficients
+        let count = n_hashes + 4 * n_qm31 + dump_witness(&hints.pow_hint).len() + n_merkle + 1;
+        assert_eq!(hints.witness_element_count(), count);
+        assert_eq!(dump_witness(&hints).len(), count);
+    }
+
+    #[test]
+    fn test_push_last_layer() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        let (_, mut hints) =
+            compute_fiat_shamir_hints(proof, &mut channel, &plonk_component, config, None).unwrap();
+        hints.last_layer = vec![get_rand_qm31(&mut prng), get_rand_qm31(&mut prng)];
+
+        // the count comes after the commitments, the OODS values, and the FRI layers
+        let start = hints.commitments.len()
+            + 4 * (hints.trace_oods_values.len()
+                + hints.interaction_oods_values.len()
+                + hints.constant_oods_values.len()
+                + hints.composition_oods_values.len())
+            + hints.fri_layer_commitments.len()
+            + 4 * hints.fri_layer_alphas.len();
+
+        let witness = dump_witness(&hints);
+        assert_eq!(witness.len(), hints.witness_element_count());
+        assert_eq!(witness[start], dump_witness(&2u32)[0]);
+        assert_eq!(
+            witness[start + 1..start + 9],
+            [
+                dump_witness(&hints.last_layer[0]),
+                dump_witness(&hints.last_layer[1])
+            ]
+            .concat()
+        );
+    }
+}
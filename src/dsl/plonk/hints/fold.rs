@@ -7,8 +7,10 @@ use std::collections::{BTreeMap, BTreeSet};
 use stwo_prover::core::fft::ibutterfly;
 use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::fields::qm31::SecureField;
-use stwo_prover::core::fri::FriProof;
+use stwo_prover::core::fri::{FriProof, FriVerificationError};
+use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
 use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleHasher;
+use stwo_prover::core::vcs::verifier::MerkleVerificationError;
 
 #[derive(Clone)]
 pub struct PerQueryFoldHints {
@@ -21,7 +23,7 @@ pub fn compute_fold_hints(
     fs_output: &FiatShamirOutput,
     prepare_output: &PrepareOutput,
     quotients_output: &QuotientsOutput,
-) -> Vec<PerQueryFoldHints> {
+) -> Result<Vec<PerQueryFoldHints>, FriVerificationError> {
     let mut layers = vec![];
 
     let num_fri_steps = fri_proof.inner_layers.len();
@@ -56,11 +58,13 @@ pub fn compute_fold_hints(
 
     let mut depth = prepare_output.precomputed_merkle_tree.layers.len() - 1;
 
-    for (((layer_twiddles, fri_layer_proof), &folding_alpha), twin_proofs_mut) in twiddles
-        .iter()
-        .zip_eq(fri_proof.inner_layers.iter())
-        .zip_eq(fs_output.fri_layer_alphas.iter())
-        .zip_eq(twin_proofs.iter_mut())
+    for (inner_layer, (((layer_twiddles, fri_layer_proof), &folding_alpha), twin_proofs_mut)) in
+        twiddles
+            .iter()
+            .zip_eq(fri_proof.inner_layers.iter())
+            .zip_eq(fs_output.fri_layer_alphas.iter())
+            .zip_eq(twin_proofs.iter_mut())
+            .enumerate()
     {
         let mut iter = fri_layer_proof.evals_subset.iter();
 
@@ -124,7 +128,11 @@ pub fn compute_fold_hints(
             &queries.iter().copied().collect::<Vec<usize>>(),
             &values,
             &fri_layer_proof.decommitment,
-        );
+        )
+        .map_err(|error| FriVerificationError::InnerLayerCommitmentInvalid {
+            inner_layer,
+            error,
+        })?;
 
         for (&queries_parent, proof) in queries_parent_sorted.iter().zip(proofs.iter()) {
             twin_proofs_mut.insert(queries_parent, proof.clone());
@@ -134,8 +142,14 @@ pub fn compute_fold_hints(
         depth -= 1;
     }
 
+    // the per-query folding only checks against a constant last layer
+    if fs_output.last_layer.len() != 1 {
+        return Err(FriVerificationError::LastLayerDegreeInvalid);
+    }
     for (_, &v) in queries_and_results.iter() {
-        assert_eq!(v, fs_output.last_layer);
+        if v != fs_output.last_layer[0] {
+            return Err(FriVerificationError::LastLayerEvaluationsInvalid);
+        }
     }
 
     let mut all_fold_hints = vec![];
@@ -149,20 +163,135 @@ pub fn compute_fold_hints(
             idx >>= 1;
         }
 
-        // test if the proof are correct
-        let mut depth = prepare_output.precomputed_merkle_tree.layers.len() - 1;
+        all_fold_hints.push(PerQueryFoldHints {
+            twin_proofs: proofs,
+        });
+    }
+
+    verify_fri_layer_commitments(
+        &all_fold_hints,
+        &fs_output.queries_parents,
+        &fs_output.fri_layer_commitments,
+        prepare_output.precomputed_merkle_tree.layers.len() - 1,
+    )?;
+
+    Ok(all_fold_hints)
+}
+
+/// Verify that the twin proofs of each FRI inner layer open against the layer's commitment, which
+/// is the one mixed into the channel, starting from a tree of the given depth.
+pub fn verify_fri_layer_commitments(
+    per_query_fold_hints: &[PerQueryFoldHints],
+    queries_parents: &[usize],
+    fri_layer_commitments: &[Sha256Hash],
+    first_depth: usize,
+) -> Result<(), FriVerificationError> {
+    for (fold_hints, &queries_parent) in per_query_fold_hints.iter().zip_eq(queries_parents) {
+        let mut depth = first_depth;
         let mut idx = queries_parent;
 
-        for (proof, commitment) in proofs.iter().zip(fs_output.fri_layer_commitments.iter()) {
-            assert!(proof.verify(commitment, depth, (idx >> 1) << 1));
+        for (inner_layer, (proof, commitment)) in fold_hints
+            .twin_proofs
+            .iter()
+            .zip_eq(fri_layer_commitments.iter())
+            .enumerate()
+        {
+            if !proof.verify(commitment, depth, (idx >> 1) << 1) {
+                return Err(FriVerificationError::InnerLayerCommitmentInvalid {
+                    inner_layer,
+                    error: MerkleVerificationError::RootMismatch,
+                });
+            }
             depth -= 1;
             idx >>= 1;
         }
+    }
 
-        all_fold_hints.push(PerQueryFoldHints {
-            twin_proofs: proofs,
-        });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dsl::plonk::hints::fiat_shamir::compute_fiat_shamir_hints;
+    use crate::dsl::plonk::hints::fold::{compute_fold_hints, verify_fri_layer_commitments};
+    use crate::dsl::plonk::hints::prepare::compute_prepare_hints;
+    use crate::dsl::plonk::hints::quotients::compute_quotients_hints;
+    use crate::dsl::plonk::hints::LOG_N_ROWS;
+    use num_traits::One;
+    use stwo_prover::core::channel::Sha256Channel;
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::fri::FriVerificationError;
+    use stwo_prover::core::pcs::PcsConfig;
+    use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
+
+    #[test]
+    fn test_corrupted_fri_layer_commitment() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        let (fs_output, _) =
+            compute_fiat_shamir_hints(proof.clone(), &mut channel, &plonk_component, config, None)
+                .unwrap();
+        let prepare_output = compute_prepare_hints(&fs_output, &proof).unwrap();
+        let (quotients_output, _) = compute_quotients_hints(&fs_output, &prepare_output);
+
+        let fold_hints = compute_fold_hints(
+            &proof.commitment_scheme_proof.fri_proof,
+            &fs_output,
+            &prepare_output,
+            &quotients_output,
+        )
+        .unwrap();
+
+        let depth = prepare_output.precomputed_merkle_tree.layers.len() - 1;
+
+        let mut corrupted_commitments = fs_output.fri_layer_commitments.clone();
+        corrupted_commitments[1] = Sha256Hash::from(vec![0u8; 32]);
+
+        let res = verify_fri_layer_commitments(
+            &fold_hints,
+            &fs_output.queries_parents,
+            &corrupted_commitments,
+            depth,
+        );
+        assert!(matches!(
+            res,
+            Err(FriVerificationError::InnerLayerCommitmentInvalid { inner_layer: 1, .. })
+        ));
     }
 
-    all_fold_hints
+    #[test]
+    fn test_non_constant_last_layer() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        let (mut fs_output, _) =
+            compute_fiat_shamir_hints(proof.clone(), &mut channel, &plonk_component, config, None)
+                .unwrap();
+        let prepare_output = compute_prepare_hints(&fs_output, &proof).unwrap();
+        let (quotients_output, _) = compute_quotients_hints(&fs_output, &prepare_output);
+        let fri_proof = &proof.commitment_scheme_proof.fri_proof;
+
+        // a last layer that the folded values do not match
+        fs_output.last_layer[0] += QM31::one();
+        let res = compute_fold_hints(fri_proof, &fs_output, &prepare_output, &quotients_output);
+        assert!(matches!(
+            res,
+            Err(FriVerificationError::LastLayerEvaluationsInvalid)
+        ));
+
+        // a last layer with two coefficients, which the per-query folding does not support
+        fs_output.last_layer.push(QM31::one());
+        let res = compute_fold_hints(fri_proof, &fs_output, &prepare_output, &quotients_output);
+        assert!(matches!(
+            res,
+            Err(FriVerificationError::LastLayerDegreeInvalid)
+        ));
+    }
 }
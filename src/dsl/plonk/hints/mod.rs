@@ -1,10 +1,12 @@
 use crate::dsl::plonk::hints::fiat_shamir::FiatShamirHints;
 use crate::dsl::plonk::hints::fold::PerQueryFoldHints;
 use crate::dsl::plonk::hints::quotients::PerQueryQuotientHint;
+use crate::fri::assert_query_values_aligned;
 use stwo_prover::core::channel::Sha256Channel;
 use stwo_prover::core::pcs::PcsConfig;
-use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
-use stwo_prover::examples::plonk::prove_fibonacci_plonk;
+use stwo_prover::core::prover::{StarkProof, VerificationError};
+use stwo_prover::core::vcs::sha256_merkle::{Sha256MerkleChannel, Sha256MerkleHasher};
+use stwo_prover::examples::plonk::{prove_fibonacci_plonk, PlonkComponent};
 
 pub const LOG_N_ROWS: u32 = 5;
 
@@ -13,6 +15,8 @@ mod fold;
 mod prepare;
 mod quotients;
 
+pub use fiat_shamir::FiatShamirTrace;
+
 pub struct Hints {
     pub fiat_shamir_hints: FiatShamirHints,
     pub per_query_quotients_hints: Vec<PerQueryQuotientHint>,
@@ -26,17 +30,52 @@ impl Hints {
         let (plonk_component, proof) =
             prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
 
+        Self::from_proof(&plonk_component, proof, config).unwrap()
+    }
+
+    /// Compute all the hints for verifying a Plonk proof.
+    pub fn from_proof(
+        plonk_component: &PlonkComponent,
+        proof: StarkProof<Sha256MerkleHasher>,
+        config: PcsConfig,
+    ) -> Result<Self, VerificationError> {
+        Self::from_proof_with_trace(plonk_component, proof, config, None)
+    }
+
+    /// Compute all the hints for verifying a Plonk proof, recording the channel digest after each
+    /// Fiat-Shamir step into `trace` if it is provided.
+    pub fn from_proof_with_trace(
+        plonk_component: &PlonkComponent,
+        proof: StarkProof<Sha256MerkleHasher>,
+        config: PcsConfig,
+        trace: Option<&mut FiatShamirTrace>,
+    ) -> Result<Self, VerificationError> {
         let mut channel = Sha256Channel::default();
 
         let (fiat_shamir_output, fiat_shamir_hints) = fiat_shamir::compute_fiat_shamir_hints(
             proof.clone(),
             &mut channel,
-            &plonk_component,
+            plonk_component,
             config,
+            trace,
+        )?;
+
+        // the quotient hints take the i-th queried values as those of the i-th query, so check
+        // them against the twin proofs that the per-query parts verify for that query
+        assert_query_values_aligned(
+            &fiat_shamir_output.queries_parents,
+            &[
+                fiat_shamir_hints.merkle_proofs_traces.as_slice(),
+                fiat_shamir_hints.merkle_proofs_interactions.as_slice(),
+                fiat_shamir_hints.merkle_proofs_constants.as_slice(),
+                fiat_shamir_hints.merkle_proofs_compositions.as_slice(),
+            ],
+            &fiat_shamir_output.queried_values_left,
+            &fiat_shamir_output.queried_values_right,
         )
-        .unwrap();
+        .map_err(|err| VerificationError::InvalidStructure(err.to_string()))?;
 
-        let prepare_output = prepare::compute_prepare_hints(&fiat_shamir_output, &proof).unwrap();
+        let prepare_output = prepare::compute_prepare_hints(&fiat_shamir_output, &proof)?;
 
         let (quotients_output, per_query_quotients_hints) =
             quotients::compute_quotients_hints(&fiat_shamir_output, &prepare_output);
@@ -46,12 +85,12 @@ impl Hints {
             &fiat_shamir_output,
             &prepare_output,
             &quotients_output,
-        );
+        )?;
 
-        Hints {
+        Ok(Hints {
             fiat_shamir_hints,
             per_query_quotients_hints,
             per_query_fold_hints,
-        }
+        })
     }
 }
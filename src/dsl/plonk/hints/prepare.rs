@@ -4,8 +4,10 @@ use crate::precomputed_merkle_tree::PrecomputedMerkleTree;
 use itertools::Itertools;
 use std::iter::zip;
 use stwo_prover::core::backend::cpu::quotients::denominator_inverses;
+use stwo_prover::core::circle::CirclePoint;
 use stwo_prover::core::constraints::complex_conjugate_line_coeffs_normalized;
 use stwo_prover::core::fields::cm31::CM31;
+use stwo_prover::core::fields::qm31::QM31;
 use stwo_prover::core::pcs::quotients::{ColumnSampleBatch, PointSample};
 use stwo_prover::core::poly::circle::CanonicCoset;
 use stwo_prover::core::prover::{StarkProof, VerificationError, N_QUERIES};
@@ -23,10 +25,87 @@ pub struct PrepareOutput {
     pub column_line_coeffs: Vec<ColumnLineCoeffs>,
 }
 
+/// The mask layout of the committed trees, which, for each tree and each column in the tree,
+/// gives the number of points at which the column is sampled. The sample points themselves come
+/// from the component's mask offsets, through `FiatShamirOutput::sampled_points`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaskLayout(pub Vec<Vec<usize>>);
+
+impl MaskLayout {
+    /// The mask layout of the Plonk component, consisting of four trace columns, eight
+    /// interaction columns (the last four of which are also sampled at the previous row), four
+    /// constant columns, and four composition columns.
+    pub fn plonk() -> Self {
+        Self(vec![
+            vec![1; 4],
+            vec![1, 1, 1, 1, 2, 2, 2, 2],
+            vec![1; 4],
+            vec![1; 4],
+        ])
+    }
+}
+
+/// The error when the sampled values do not match the mask layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidOodsSampleStructure(pub String);
+
+/// Check that the sampled values have, for each tree and each column in the tree, as many values
+/// as the mask layout expects.
+pub fn validate_sampled_values(
+    mask_layout: &MaskLayout,
+    sampled_values: &[Vec<Vec<QM31>>],
+) -> Result<(), InvalidOodsSampleStructure> {
+    if sampled_values.len() != mask_layout.0.len() {
+        return Err(InvalidOodsSampleStructure(format!(
+            "expected {} trees, found {}",
+            mask_layout.0.len(),
+            sampled_values.len()
+        )));
+    }
+    for (tree, (tree_values, tree_layout)) in
+        sampled_values.iter().zip(mask_layout.0.iter()).enumerate()
+    {
+        if tree_values.len() != tree_layout.len() {
+            return Err(InvalidOodsSampleStructure(format!(
+                "expected {} columns in tree {}, found {}",
+                tree_layout.len(),
+                tree,
+                tree_values.len()
+            )));
+        }
+        for (column, (column_values, &n_masks)) in
+            tree_values.iter().zip(tree_layout.iter()).enumerate()
+        {
+            if column_values.len() != n_masks {
+                return Err(InvalidOodsSampleStructure(format!(
+                    "expected {} values in column {} of tree {}, found {}",
+                    n_masks,
+                    column,
+                    tree,
+                    column_values.len()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
 /// prepare output for quotients and verifier hints
 pub fn compute_prepare_hints(
     fs_output: &FiatShamirOutput,
     proof: &StarkProof<Sha256MerkleHasher>,
+) -> Result<PrepareOutput, VerificationError> {
+    compute_prepare_hints_for_masks(fs_output, proof, &MaskLayout::plonk())
+}
+
+/// prepare output for quotients and verifier hints, for a given mask layout
+///
+/// The column line coefficients are grouped by tree and then by the sampled point, where each group
+/// consists of the columns of the tree that are sampled at that point.
+pub fn compute_prepare_hints_for_masks(
+    fs_output: &FiatShamirOutput,
+    proof: &StarkProof<Sha256MerkleHasher>,
+    mask_layout: &MaskLayout,
 ) -> Result<PrepareOutput, VerificationError> {
     let column_size: Vec<u32> = fs_output
         .commitment_scheme_column_log_sizes
@@ -41,49 +120,8 @@ pub fn compute_prepare_hints(
         fs_output.max_column_log_degree_bound + fs_output.fri_log_blowup_factor
     );
 
-    assert_eq!(proof.commitment_scheme_proof.sampled_values.0.len(), 4);
-
-    // trace polynomials, four polynomials
-    assert_eq!(proof.commitment_scheme_proof.sampled_values.0[0].len(), 4);
-    for i in 0..4 {
-        assert_eq!(
-            proof.commitment_scheme_proof.sampled_values.0[0][i].len(),
-            1
-        );
-    }
-
-    // interaction polynomials, eight polynomials
-    assert_eq!(proof.commitment_scheme_proof.sampled_values.0[1].len(), 8);
-    for i in 0..4 {
-        assert_eq!(
-            proof.commitment_scheme_proof.sampled_values.0[1][i].len(),
-            1
-        );
-    }
-    for i in 4..8 {
-        assert_eq!(
-            proof.commitment_scheme_proof.sampled_values.0[1][i].len(),
-            2
-        );
-    }
-
-    // constant polynomials, four polynomials
-    assert_eq!(proof.commitment_scheme_proof.sampled_values.0[2].len(), 4);
-    for i in 0..4 {
-        assert_eq!(
-            proof.commitment_scheme_proof.sampled_values.0[2][i].len(),
-            1
-        );
-    }
-
-    // composition polynomials, four polynomials
-    assert_eq!(proof.commitment_scheme_proof.sampled_values.0[3].len(), 4);
-    for i in 0..4 {
-        assert_eq!(
-            proof.commitment_scheme_proof.sampled_values.0[3][i].len(),
-            1
-        );
-    }
+    validate_sampled_values(mask_layout, &proof.commitment_scheme_proof.sampled_values.0)
+        .map_err(|err| VerificationError::InvalidStructure(err.0))?;
 
     let precomputed_merkle_tree = PrecomputedMerkleTree::new(
         (fs_output.max_column_log_degree_bound + fs_output.fri_log_blowup_factor - 1) as usize,
@@ -125,113 +163,59 @@ pub fn compute_prepare_hints(
             .collect()
     };
 
-    let column_line_coeffs_trace = ColumnLineCoeffs::from_values_and_point(
-        &[
-            samples[0][0].value,
-            samples[1][0].value,
-            samples[2][0].value,
-            samples[3][0].value,
-        ],
-        samples[0][0].point,
-    );
+    let mut column_line_coeffs = vec![];
+    let mut column_offset = 0;
 
-    for i in 0..4 {
-        assert_eq!(
-            expected_line_coeffs[0][i].0,
-            column_line_coeffs_trace.fp_imag_div_y_imag[i],
-        );
-        assert_eq!(
-            expected_line_coeffs[0][i].1,
-            column_line_coeffs_trace.cross_term[i],
-        );
-    }
+    for tree_layout in mask_layout.0.iter() {
+        // group the columns of the tree by the sampled point, in the order in which the points
+        // first appear, since columns may be sampled at the same point under different mask
+        // indices
+        let mut groups: Vec<(CirclePoint<QM31>, Vec<(usize, usize)>)> = vec![];
+        for (i, &n_masks) in tree_layout.iter().enumerate() {
+            let column = column_offset + i;
+            for mask in 0..n_masks {
+                let point = samples[column][mask].point;
+                match groups.iter_mut().find(|(p, _)| *p == point) {
+                    Some((_, entries)) => entries.push((column, mask)),
+                    None => groups.push((point, vec![(column, mask)])),
+                }
+            }
+        }
 
-    let column_line_coeffs_interaction = ColumnLineCoeffs::from_values_and_point(
-        &[
-            samples[4][0].value,
-            samples[5][0].value,
-            samples[6][0].value,
-            samples[7][0].value,
-            samples[8][0].value,
-            samples[9][0].value,
-            samples[10][0].value,
-            samples[11][0].value,
-        ],
-        samples[4][0].point,
-    );
+        for (point, entries) in groups {
+            let columns = entries.iter().map(|&(column, _)| column).collect_vec();
+            let values = entries
+                .iter()
+                .map(|&(column, mask)| samples[column][mask].value)
+                .collect_vec();
 
-    for i in 0..8 {
-        assert_eq!(
-            expected_line_coeffs[0][4 + i].0,
-            column_line_coeffs_interaction.fp_imag_div_y_imag[i],
-        );
-        assert_eq!(
-            expected_line_coeffs[0][4 + i].1,
-            column_line_coeffs_interaction.cross_term[i],
-        );
-    }
-
-    let column_line_coeffs_interaction_shifted = ColumnLineCoeffs::from_values_and_point(
-        &[
-            samples[8][1].value,
-            samples[9][1].value,
-            samples[10][1].value,
-            samples[11][1].value,
-        ],
-        samples[8][1].point,
-    );
-
-    for i in 0..4 {
-        assert_eq!(
-            expected_line_coeffs[1][i].0,
-            column_line_coeffs_interaction_shifted.fp_imag_div_y_imag[i],
-        );
-        assert_eq!(
-            expected_line_coeffs[1][i].1,
-            column_line_coeffs_interaction_shifted.cross_term[i],
-        );
-    }
-
-    let column_line_coeffs_constant = ColumnLineCoeffs::from_values_and_point(
-        &[
-            samples[12][0].value,
-            samples[13][0].value,
-            samples[14][0].value,
-            samples[15][0].value,
-        ],
-        samples[12][0].point,
-    );
+            let line_coeffs = ColumnLineCoeffs::from_values_and_point(&values, point);
 
-    for i in 0..4 {
-        assert_eq!(
-            expected_line_coeffs[0][12 + i].0,
-            column_line_coeffs_constant.fp_imag_div_y_imag[i],
-        );
-        assert_eq!(
-            expected_line_coeffs[0][12 + i].1,
-            column_line_coeffs_constant.cross_term[i],
-        );
-    }
+            // compare with the line coefficients computed by stwo for the same sample batch
+            let batch_idx = column_sample_batches
+                .iter()
+                .position(|sample_batch| sample_batch.point == point)
+                .unwrap();
+            for (i, &column) in columns.iter().enumerate() {
+                let j = column_sample_batches[batch_idx]
+                    .columns_and_values
+                    .iter()
+                    .position(|(idx, _)| *idx == column)
+                    .unwrap();
+                assert_eq!(
+                    expected_line_coeffs[batch_idx][j].0,
+                    line_coeffs.fp_imag_div_y_imag[i],
+                );
+                assert_eq!(
+                    expected_line_coeffs[batch_idx][j].1,
+                    line_coeffs.cross_term[i],
+                );
+            }
 
-    let column_line_coeffs_composition = ColumnLineCoeffs::from_values_and_point(
-        &[
-            samples[16][0].value,
-            samples[17][0].value,
-            samples[18][0].value,
-            samples[19][0].value,
-        ],
-        samples[16][0].point,
-    );
+            column_line_coeffs.push(line_coeffs);
+        }
 
-    for i in 0..4 {
-        assert_eq!(
-            expected_line_coeffs[0][16 + i].0,
-            column_line_coeffs_composition.fp_imag_div_y_imag[i],
-        );
-        assert_eq!(
-            expected_line_coeffs[0][16 + i].1,
-            column_line_coeffs_composition.cross_term[i],
-        );
+        column_offset += tree_layout.len();
     }
 
     let commitment_domain =
@@ -251,12 +235,176 @@ pub fn compute_prepare_hints(
     Ok(PrepareOutput {
         precomputed_merkle_tree,
         denominator_inverses_expected,
-        column_line_coeffs: vec![
-            column_line_coeffs_trace,
-            column_line_coeffs_interaction,
-            column_line_coeffs_interaction_shifted,
-            column_line_coeffs_constant,
-            column_line_coeffs_composition,
-        ],
+        column_line_coeffs,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use crate::constraints::ColumnLineCoeffs;
+    use crate::dsl::plonk::hints::fiat_shamir::compute_fiat_shamir_hints;
+    use crate::dsl::plonk::hints::prepare::{
+        compute_prepare_hints_for_masks, validate_sampled_values, MaskLayout,
+    };
+    use crate::dsl::plonk::hints::LOG_N_ROWS;
+    use stwo_prover::core::channel::Sha256Channel;
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::pcs::PcsConfig;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
+
+    #[test]
+    fn test_compute_prepare_hints_for_masks() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        let (fs_output, _) =
+            compute_fiat_shamir_hints(proof.clone(), &mut channel, &plonk_component, config, None)
+                .unwrap();
+
+        let prepare_output =
+            compute_prepare_hints_for_masks(&fs_output, &proof, &MaskLayout::plonk()).unwrap();
+
+        // the Plonk line coefficients, grouped as trace, interaction, shifted interaction,
+        // constant, and composition
+        let sampled_values = &proof.commitment_scheme_proof.sampled_values.0;
+        let sampled_points = &fs_output.sampled_points.0;
+        let groups = [
+            (0, 0..4, 0),
+            (1, 0..8, 0),
+            (1, 4..8, 1),
+            (2, 0..4, 0),
+            (3, 0..4, 0),
+        ];
+
+        assert_eq!(prepare_output.column_line_coeffs.len(), groups.len());
+        for ((tree, columns, mask), line_coeffs) in groups
+            .into_iter()
+            .zip(prepare_output.column_line_coeffs.iter())
+        {
+            let values = columns
+                .clone()
+                .map(|i| sampled_values[tree][i][mask])
+                .collect::<Vec<_>>();
+            let point = sampled_points[tree][columns.start][mask];
+
+            let expected = ColumnLineCoeffs::from_values_and_point(&values, point);
+            assert_eq!(line_coeffs.fp_imag_div_y_imag, expected.fp_imag_div_y_imag);
+            assert_eq!(line_coeffs.cross_term, expected.cross_term);
+        }
+    }
+
+    #[test]
+    fn test_compute_prepare_hints_for_mixed_offsets() {
+        let config = PcsConfig::default();
+        let (plonk_component, mut proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut channel = Sha256Channel::default();
+        let (mut fs_output, _) =
+            compute_fiat_shamir_hints(proof.clone(), &mut channel, &plonk_component, config, None)
+                .unwrap();
+
+        // sample the shifted interaction columns at the previous row first, so that the current
+        // row is at mask index 0 for the first four columns but at mask index 1 for the others
+        for i in 4..8 {
+            fs_output.sampled_points.0[1][i].reverse();
+            proof.commitment_scheme_proof.sampled_values.0[1][i].reverse();
+        }
+
+        let prepare_output =
+            compute_prepare_hints_for_masks(&fs_output, &proof, &MaskLayout::plonk()).unwrap();
+
+        // the interaction groups are still formed by the sampled point
+        let sampled_values = &proof.commitment_scheme_proof.sampled_values.0;
+        let sampled_points = &fs_output.sampled_points.0;
+        let groups: [(usize, Vec<(usize, usize)>); 5] = [
+            (0, (0..4).map(|i| (i, 0)).collect()),
+            (
+                1,
+                (0..4)
+                    .map(|i| (i, 0))
+                    .chain((4..8).map(|i| (i, 1)))
+                    .collect(),
+            ),
+            (1, (4..8).map(|i| (i, 0)).collect()),
+            (2, (0..4).map(|i| (i, 0)).collect()),
+            (3, (0..4).map(|i| (i, 0)).collect()),
+        ];
+
+        assert_eq!(prepare_output.column_line_coeffs.len(), groups.len());
+        for ((tree, entries), line_coeffs) in groups
+            .into_iter()
+            .zip(prepare_output.column_line_coeffs.iter())
+        {
+            let values = entries
+                .iter()
+                .map(|&(i, mask)| sampled_values[tree][i][mask])
+                .collect::<Vec<_>>();
+            let (i, mask) = entries[0];
+            let point = sampled_points[tree][i][mask];
+
+            let expected = ColumnLineCoeffs::from_values_and_point(&values, point);
+            assert_eq!(line_coeffs.fp_imag_div_y_imag, expected.fp_imag_div_y_imag);
+            assert_eq!(line_coeffs.cross_term, expected.cross_term);
+        }
+    }
+
+    #[test]
+    fn test_validate_sampled_values() {
+        let config = PcsConfig::default();
+        let (_, proof) = prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let sampled_values = proof.commitment_scheme_proof.sampled_values.0.clone();
+        assert!(validate_sampled_values(&MaskLayout::plonk(), &sampled_values).is_ok());
+
+        // a shifted interaction column missing its value at the previous row
+        let mut malformed = sampled_values.clone();
+        malformed[1][4].pop();
+        assert!(validate_sampled_values(&MaskLayout::plonk(), &malformed).is_err());
+
+        // a missing constant column
+        let mut malformed = sampled_values.clone();
+        malformed[2].pop();
+        assert!(validate_sampled_values(&MaskLayout::plonk(), &malformed).is_err());
+
+        // a missing composition tree
+        let mut malformed = sampled_values;
+        malformed.pop();
+        assert!(validate_sampled_values(&MaskLayout::plonk(), &malformed).is_err());
+    }
+
+    #[test]
+    fn test_validate_sampled_values_two_interactions() {
+        // trace, two interaction trees (the second with two shifted columns), constant, and
+        // composition
+        let mask_layout = MaskLayout(vec![
+            vec![1; 4],
+            vec![1; 8],
+            vec![1, 1, 2, 2],
+            vec![1; 4],
+            vec![1; 4],
+        ]);
+
+        let sampled_values = mask_layout
+            .0
+            .iter()
+            .map(|tree| {
+                tree.iter()
+                    .map(|&n_masks| vec![QM31::default(); n_masks])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert!(validate_sampled_values(&mask_layout, &sampled_values).is_ok());
+
+        // the Plonk layout only has one interaction tree
+        assert!(validate_sampled_values(&MaskLayout::plonk(), &sampled_values).is_err());
+
+        // the second interaction tree misses the shifted value of its last column
+        let mut malformed = sampled_values;
+        malformed[2][3].pop();
+        assert!(validate_sampled_values(&mask_layout, &malformed).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+use crate::dsl::plonk::hints::LOG_N_ROWS;
+use crate::dsl::primitives::qm31::QM31Var;
+use crate::dsl::primitives::table::TableVar;
+use anyhow::{bail, Result};
+use bitcoin_script_dsl::builtins::hash::HashVar;
+use bitcoin_script_dsl::builtins::str::StrVar;
+use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+use itertools::Itertools;
+use num_traits::One;
+use std::sync::OnceLock;
+use stwo_prover::core::backend::CpuBackend;
+use stwo_prover::core::fields::m31::{BaseField, M31};
+use stwo_prover::core::poly::circle::{CanonicCoset, CircleEvaluation};
+use stwo_prover::core::poly::BitReversedOrder;
+use stwo_prover::core::prover::LOG_BLOWUP_FACTOR;
+use stwo_prover::core::vcs::prover::MerkleProver;
+use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleHasher;
+
+/// The drawn LogUp lookup elements `LookupElements<2>`, consisting of `z` and `alpha`.
+pub struct LookupElementsVar {
+    pub z: QM31Var,
+    pub alpha: QM31Var,
+}
+
+impl LookupElementsVar {
+    /// Combine a (wire, value) pair into the LogUp denominator `wire + alpha * value - z`.
+    pub fn combine(&self, table: &TableVar, values: [&QM31Var; 2]) -> QM31Var {
+        &(values[0] + &(&self.alpha * (table, values[1]))) - &self.z
+    }
+}
+
+/// Evaluate the two LogUp constraints of the Plonk component, before being folded by the
+/// composition random coefficient.
+///
+/// `wires` and `vals` are the (a, b, c) wire and value columns, and `interaction_values` are the
+/// twelve interaction columns in the order they are sampled: the four partial evaluations of the
+/// a/b fraction sum, followed by the interleaved partial evaluations of the c fraction sum at the
+/// current row and the previous row.
+pub fn evaluate_logup_constraints(
+    table: &TableVar,
+    lookup_elements: &LookupElementsVar,
+    wires: [&QM31Var; 3],
+    vals: [&QM31Var; 3],
+    mult: &QM31Var,
+    interaction_values: &[QM31Var; 12],
+    claimed_sum_divided: &QM31Var,
+) -> (QM31Var, QM31Var) {
+    let denominator_1 = lookup_elements.combine(table, [wires[0], vals[0]]);
+    let denominator_2 = lookup_elements.combine(table, [wires[1], vals[1]]);
+
+    let num_aggregated = &denominator_1 + &denominator_2;
+    let denom_aggregated = &denominator_1 * (table, &denominator_2);
+
+    let a_b_logup = QM31Var::from_partial_evals([
+        &interaction_values[0],
+        &interaction_values[1],
+        &interaction_values[2],
+        &interaction_values[3],
+    ]);
+
+    let res1 = &(&a_b_logup * (table, &denom_aggregated)) - &num_aggregated;
+
+    let denominator_3 = lookup_elements.combine(table, [wires[2], vals[2]]);
+
+    let c_logup = QM31Var::from_partial_evals([
+        &interaction_values[4],
+        &interaction_values[6],
+        &interaction_values[8],
+        &interaction_values[10],
+    ]);
+    let c_logup_next = QM31Var::from_partial_evals([
+        &interaction_values[5],
+        &interaction_values[7],
+        &interaction_values[9],
+        &interaction_values[11],
+    ]);
+
+    let mut res2 = &(&(&c_logup - &c_logup_next) - &a_b_logup) + claimed_sum_divided;
+    res2 = &res2 * (table, &denominator_3);
+    res2 = &res2 + mult;
+
+    (res1, res2)
+}
+
+/// Verify that `claimed_sum_divided` is `claimed_sum` divided by the domain size `2^log_n_rows`,
+/// by doubling `claimed_sum_divided` for `log_n_rows` times.
+pub fn verify_claimed_sum_divided(
+    claimed_sum: &QM31Var,
+    claimed_sum_divided: &QM31Var,
+    log_n_rows: u32,
+) -> Result<()> {
+    let mut res = claimed_sum_divided.clone();
+    for _ in 0..log_n_rows {
+        res = &res + &res;
+    }
+
+    if res.value()? != claimed_sum.value()? {
+        bail!("the claimed sum divided does not match the claimed sum");
+    }
+    res.equalverify(claimed_sum)
+}
+
+/// Compute the commitment of the constant columns of the Fibonacci Plonk circuit with
+/// `2^log_n_rows` rows, by committing to its a/b/c wire selectors and its op column over the
+/// blown-up domain.
+///
+/// Row `i` of the circuit adds the wires `i` and `i + 1` into the wire `i + 2`, so the selectors
+/// are `i`, `i + 1`, and `i + 2`, and the op column is always 1.
+pub fn compute_constant_commitment(log_n_rows: u32) -> Sha256Hash {
+    let trace_domain = CanonicCoset::new(log_n_rows).circle_domain();
+    let commitment_domain = CanonicCoset::new(log_n_rows + LOG_BLOWUP_FACTOR).circle_domain();
+
+    let columns = (0..3)
+        .map(|offset| {
+            (0..1u32 << log_n_rows)
+                .map(|i| M31::from(i + offset))
+                .collect_vec()
+        })
+        .chain([vec![M31::one(); 1 << log_n_rows]])
+        .map(|values| {
+            CircleEvaluation::<CpuBackend, BaseField, BitReversedOrder>::new(trace_domain, values)
+                .interpolate()
+                .evaluate(commitment_domain)
+                .values
+        })
+        .collect_vec();
+
+    MerkleProver::<CpuBackend, Sha256MerkleHasher>::commit(columns.iter().collect()).root()
+}
+
+/// The commitment of the constant columns of the Plonk circuit with `2^LOG_N_ROWS` rows, which is
+/// only computed once.
+pub fn constant_commitment() -> Sha256Hash {
+    static CONSTANT_COMMITMENT: OnceLock<Sha256Hash> = OnceLock::new();
+    *CONSTANT_COMMITMENT.get_or_init(|| compute_constant_commitment(LOG_N_ROWS))
+}
+
+/// Verify that the constant columns (the a/b/c wire selectors and the op column) are the public
+/// columns of the circuit, by pinning `constant_commitment` to `expected_root`.
+///
+/// The constant columns are fixed by the circuit, so their commitment is a public value. Once it
+/// is pinned, a Merkle twin proof against it ensures the queried values are exactly the public
+/// constant values at the query position, so this only needs to be done once for all the queries.
+pub fn assert_constant_columns(
+    constant_commitment: &HashVar,
+    expected_root: &Sha256Hash,
+) -> Result<()> {
+    if constant_commitment.value.as_slice() != expected_root.as_ref() {
+        bail!("the constant commitment does not match the public constant columns");
+    }
+
+    let cs = constant_commitment.cs();
+    let expected_root_var = HashVar::new_constant(&cs, expected_root.as_ref().to_vec())?;
+    StrVar::from(constant_commitment).equalverify(&StrVar::from(&expected_root_var))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::twin_tree::query_and_verify_merkle_twin_tree;
+    use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
+    use crate::dsl::plonk::logup_gadget::{
+        assert_constant_columns, compute_constant_commitment, constant_commitment,
+        evaluate_logup_constraints, verify_claimed_sum_divided, LookupElementsVar,
+    };
+    use crate::dsl::primitives::m31::M31Var;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::TableVar;
+    use crate::merkle_tree::MerkleTreeTwinProof;
+    use crate::tests_utils::gadget::{
+        assert_rejects_tampered_hints, hint_index, run_program_with_tampered_hints,
+    };
+    use crate::treepp::*;
+    use bitcoin_script_dsl::builtins::hash::HashVar;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::compiler::Compiler;
+    use bitcoin_script_dsl::constraint_system::{ConstraintSystem, Element};
+    use bitcoin_script_dsl::test_program;
+    use num_traits::One;
+    use stwo_prover::core::fields::m31::BaseField;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::qm31::QM31;
+    use stwo_prover::core::pcs::PcsConfig;
+    use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
+
+    #[test]
+    fn test_verify_claimed_sum_divided() {
+        let config = PcsConfig::default();
+        let (plonk_component, _) = prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let claimed_sum = plonk_component.claimed_sum;
+        let claimed_sum_divided = claimed_sum / BaseField::from_u32_unchecked(1 << LOG_N_ROWS);
+
+        let cs = ConstraintSystem::new_ref();
+        let claimed_sum_var = QM31Var::new_constant(&cs, claimed_sum).unwrap();
+        let claimed_sum_divided_var = QM31Var::new_hint(&cs, claimed_sum_divided).unwrap();
+
+        verify_claimed_sum_divided(&claimed_sum_var, &claimed_sum_divided_var, LOG_N_ROWS).unwrap();
+        test_program(cs, script! {}).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let claimed_sum_var = QM31Var::new_constant(&cs, claimed_sum).unwrap();
+        let tampered_var = QM31Var::new_hint(&cs, claimed_sum_divided + QM31::one()).unwrap();
+
+        assert!(verify_claimed_sum_divided(&claimed_sum_var, &tampered_var, LOG_N_ROWS).is_err());
+
+        let build = || {
+            let cs = ConstraintSystem::new_ref();
+            let claimed_sum_var = QM31Var::new_constant(&cs, claimed_sum).unwrap();
+            let claimed_sum_divided_var = QM31Var::new_hint(&cs, claimed_sum_divided).unwrap();
+            verify_claimed_sum_divided(&claimed_sum_var, &claimed_sum_divided_var, LOG_N_ROWS)
+                .unwrap();
+            cs
+        };
+        assert_rejects_tampered_hints(
+            build,
+            &claimed_sum_divided.to_m31_array(),
+            &(claimed_sum_divided + QM31::one()).to_m31_array(),
+        );
+    }
+
+    #[test]
+    fn test_evaluate_logup_constraints() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+        let hints = Hints::from_proof(&plonk_component, proof, config).unwrap();
+        let fiat_shamir_hints = &hints.fiat_shamir_hints;
+
+        let lookup_elements = &plonk_component.lookup_elements;
+        let trace = &fiat_shamir_hints.trace_oods_values;
+        let interaction = &fiat_shamir_hints.interaction_oods_values;
+        let constant = &fiat_shamir_hints.constant_oods_values;
+        let claimed_sum_divided = fiat_shamir_hints.claimed_sum_divided;
+
+        let denominator_1: QM31 = lookup_elements.combine(&[constant[0], trace[1]]);
+        let denominator_2: QM31 = lookup_elements.combine(&[constant[1], trace[2]]);
+        let denominator_3: QM31 = lookup_elements.combine(&[constant[2], trace[3]]);
+
+        let a_b_logup = QM31::from_partial_evals([
+            interaction[0],
+            interaction[1],
+            interaction[2],
+            interaction[3],
+        ]);
+        let c_logup = QM31::from_partial_evals([
+            interaction[4],
+            interaction[6],
+            interaction[8],
+            interaction[10],
+        ]);
+        let c_logup_next = QM31::from_partial_evals([
+            interaction[5],
+            interaction[7],
+            interaction[9],
+            interaction[11],
+        ]);
+
+        let expected_1 =
+            a_b_logup * denominator_1 * denominator_2 - (denominator_1 + denominator_2);
+        let expected_2 =
+            (c_logup - c_logup_next - a_b_logup + claimed_sum_divided) * denominator_3 + trace[0];
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+
+        let lookup_elements_var = LookupElementsVar {
+            z: QM31Var::new_hint(&cs, lookup_elements.z).unwrap(),
+            alpha: QM31Var::new_hint(&cs, lookup_elements.alpha).unwrap(),
+        };
+
+        let mut trace_vars = vec![];
+        for &v in trace.iter() {
+            trace_vars.push(QM31Var::new_hint(&cs, v).unwrap());
+        }
+        let mut constant_vars = vec![];
+        for &v in constant.iter() {
+            constant_vars.push(QM31Var::new_hint(&cs, v).unwrap());
+        }
+        let mut interaction_vars = vec![];
+        for &v in interaction.iter() {
+            interaction_vars.push(QM31Var::new_hint(&cs, v).unwrap());
+        }
+        let interaction_vars: [QM31Var; 12] = interaction_vars.try_into().unwrap();
+        let claimed_sum_divided_var = QM31Var::new_hint(&cs, claimed_sum_divided).unwrap();
+
+        let (res1, res2) = evaluate_logup_constraints(
+            &table,
+            &lookup_elements_var,
+            [&constant_vars[0], &constant_vars[1], &constant_vars[2]],
+            [&trace_vars[1], &trace_vars[2], &trace_vars[3]],
+            &trace_vars[0],
+            &interaction_vars,
+            &claimed_sum_divided_var,
+        );
+        assert_eq!(res1.value().unwrap(), expected_1);
+        assert_eq!(res2.value().unwrap(), expected_2);
+
+        let expected_1_var = QM31Var::new_constant(&cs, expected_1).unwrap();
+        let expected_2_var = QM31Var::new_constant(&cs, expected_2).unwrap();
+        res1.equalverify(&expected_1_var).unwrap();
+        res2.equalverify(&expected_2_var).unwrap();
+
+        test_program(cs, script! {}).unwrap();
+    }
+
+    #[test]
+    fn test_compute_constant_commitment() {
+        let config = PcsConfig::default();
+        let (_, proof) = prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        assert_eq!(
+            compute_constant_commitment(LOG_N_ROWS),
+            proof.commitments[2]
+        );
+        assert_eq!(constant_commitment(), proof.commitments[2]);
+    }
+
+    #[test]
+    fn test_assert_constant_columns() {
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+        let hints = Hints::from_proof(&plonk_component, proof, config).unwrap();
+        let fiat_shamir_hints = &hints.fiat_shamir_hints;
+
+        let constant_root = constant_commitment();
+        let query = (fiat_shamir_hints.queries_parents[0] << 1) as u32;
+        let proof = &fiat_shamir_hints.merkle_proofs_constants[0];
+
+        let generate_cs = |root: Sha256Hash, proof: &MerkleTreeTwinProof, pinned: bool| {
+            let cs = ConstraintSystem::new_ref();
+            let commitment_var = HashVar::new_hint(&cs, root.as_ref().to_vec()).unwrap();
+            let query_var = M31Var::new_constant(&cs, M31::from(query)).unwrap();
+            if pinned {
+                assert_constant_columns(&commitment_var, &constant_root).unwrap();
+            }
+            query_and_verify_merkle_twin_tree(&commitment_var, &query_var, proof).unwrap();
+            cs
+        };
+
+        let cs = generate_cs(constant_root, proof, true);
+        test_program(cs, script! {}).unwrap();
+
+        // a tampered constant value
+        let cs = generate_cs(constant_root, proof, true);
+        let result = run_program_with_tampered_hints(cs, |hints| {
+            let index = hint_index(hints, &[proof.left.clone(), proof.right.clone()].concat());
+            hints[index] = Element::Num((proof.left[0] + M31::one()).0 as i32);
+        });
+        assert!(!result.success);
+
+        // a commitment to other columns, here the trace, with a valid Merkle proof against it
+        let trace_root = fiat_shamir_hints.commitments[0];
+        let trace_proof = &fiat_shamir_hints.merkle_proofs_traces[0];
+        let forged_hints = Compiler::compile(generate_cs(trace_root, trace_proof, false))
+            .unwrap()
+            .hint;
+
+        let cs = generate_cs(constant_root, proof, true);
+        let result = run_program_with_tampered_hints(cs, |hints| {
+            hints.clone_from_slice(&forged_hints);
+        });
+        assert!(!result.success);
+
+        // the gadget also refuses to generate a constraint system for a mismatched commitment
+        let cs = ConstraintSystem::new_ref();
+        let commitment_var = HashVar::new_hint(&cs, trace_root.as_ref().to_vec()).unwrap();
+        assert!(assert_constant_columns(&commitment_var, &constant_root).is_err());
+    }
+}
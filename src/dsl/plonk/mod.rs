@@ -1,7 +1,11 @@
 pub mod hints;
 
+pub mod air;
+
 pub mod covenant;
 
+pub mod logup_gadget;
+
 pub mod part1_fiat_shamir1;
 pub mod part2_fiat_shamir2_and_constraint_num;
 pub mod part3_constraint_denom;
@@ -21,13 +25,29 @@ pub mod per_query_part8_last_step;
 
 pub mod part8_cleanup;
 
+use bitcoin_script_dsl::ldm::LDM;
+
+/// The hash of the LDM state left behind by a part, which is also the output of the part.
+///
+/// The LDM does not expose its entries, only this hash, which commits to all of them.
+pub fn ldm_hash(ldm: &LDM) -> Option<Vec<u8>> {
+    ldm.hash_var.as_ref().map(|hash_var| hash_var.value.clone())
+}
+
 #[cfg(test)]
 mod test {
-    use crate::dsl::plonk::hints::Hints;
+    use crate::dsl::plonk::covenant::{compute_all_information, PLONK_ALL_INFORMATION};
+    use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
+    use crate::dsl::plonk::ldm_hash;
+    use crate::tests_utils::report::report_bitcoin_script_size;
     use crate::treepp::*;
+    use bitcoin_script_dsl::compiler::Compiler;
     use bitcoin_script_dsl::ldm::LDM;
     use bitcoin_script_dsl::test_program;
-    use stwo_prover::core::prover::N_QUERIES;
+    use stwo_prover::core::pcs::PcsConfig;
+    use stwo_prover::core::prover::{N_QUERIES, PROOF_OF_WORK_BITS};
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
 
     #[test]
     fn test_generate_dsl() {
@@ -186,4 +206,140 @@ mod test {
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_part2_self_contained() {
+        let hints = Hints::instance();
+
+        let generate = |self_contained: bool| {
+            let mut ldm = LDM::new();
+            super::part1_fiat_shamir1::generate_cs(&hints, &mut ldm).unwrap();
+            let cs = if self_contained {
+                super::part2_fiat_shamir2_and_constraint_num::generate_cs_self_contained(
+                    &hints, &mut ldm,
+                )
+            } else {
+                super::part2_fiat_shamir2_and_constraint_num::generate_cs(&hints, &mut ldm)
+            }
+            .unwrap();
+            (cs, ldm)
+        };
+
+        let (cs, ldm) = generate(true);
+        let expected_hash = generate(false).1.hash_var.unwrap().value;
+
+        // the self-contained part writes exactly the same values into the LDM
+        assert_eq!(ldm.hash_var.as_ref().unwrap().value, expected_hash);
+        test_program(
+            cs,
+            script! {
+                { ldm.hash_var.as_ref().unwrap().value.clone() }
+            },
+        )
+        .unwrap();
+
+        let cached_root = Compiler::compile(generate(false).0).unwrap();
+        let self_contained = Compiler::compile(generate(true).0).unwrap();
+        report_bitcoin_script_size("Plonk", "part2(cached root)", cached_root.script.len());
+        report_bitcoin_script_size(
+            "Plonk",
+            "part2(self-contained)",
+            self_contained.script.len(),
+        );
+    }
+
+    #[test]
+    fn test_per_query_part6_lazy() {
+        let hints = Hints::instance();
+
+        let generate = |lazy: bool| {
+            let mut ldm = LDM::new();
+            super::part1_fiat_shamir1::generate_cs(&hints, &mut ldm).unwrap();
+            super::part2_fiat_shamir2_and_constraint_num::generate_cs(&hints, &mut ldm).unwrap();
+            super::part3_constraint_denom::generate_cs(&hints, &mut ldm).unwrap();
+            super::part4_pair_vanishing_and_alphas::generate_cs(&hints, &mut ldm).unwrap();
+            super::part5_column_line_coeffs1::generate_cs(&hints, &mut ldm).unwrap();
+            super::part6_column_line_coeffs2::generate_cs(&hints, &mut ldm).unwrap();
+            super::part7_column_line_coeffs3::generate_cs(&hints, &mut ldm).unwrap();
+            super::per_query_part1_folding::generate_cs(&hints, &mut ldm, 0).unwrap();
+            super::per_query_part2_num_trace::generate_cs(&hints, &mut ldm, 0).unwrap();
+            super::per_query_part3_num_constant::generate_cs(&hints, &mut ldm, 0).unwrap();
+            super::per_query_part4_num_composition::generate_cs(&hints, &mut ldm, 0).unwrap();
+            super::per_query_part5_num_interaction_shifted::generate_cs(&hints, &mut ldm, 0)
+                .unwrap();
+            let cs = if lazy {
+                super::per_query_part6_num_interaction1::generate_cs_lazy(&hints, &mut ldm, 0)
+            } else {
+                super::per_query_part6_num_interaction1::generate_cs(&hints, &mut ldm, 0)
+            }
+            .unwrap();
+            (cs, ldm)
+        };
+
+        let (cs, ldm) = generate(true);
+        let expected_hash = generate(false).1.hash_var.unwrap().value;
+
+        // recomputing the powers leads to the same state
+        assert_eq!(ldm.hash_var.as_ref().unwrap().value, expected_hash);
+        test_program(
+            cs,
+            script! {
+                { ldm.hash_var.as_ref().unwrap().value.clone() }
+            },
+        )
+        .unwrap();
+
+        let stored = Compiler::compile(generate(false).0).unwrap();
+        let lazy = Compiler::compile(generate(true).0).unwrap();
+        report_bitcoin_script_size("Plonk", "per_query_part6", stored.script.len());
+        report_bitcoin_script_size("Plonk", "per_query_part6(lazy)", lazy.script.len());
+    }
+
+    #[test]
+    fn test_ldm_hash() {
+        let hints = Hints::instance();
+        let mut ldm = LDM::new();
+        assert!(ldm_hash(&ldm).is_none());
+
+        super::part1_fiat_shamir1::generate_cs(&hints, &mut ldm).unwrap();
+
+        // the hash is what the compiled part1 leaves on the stack
+        let all_information = PLONK_ALL_INFORMATION.get_or_init(compute_all_information);
+        assert_eq!(
+            ldm_hash(&ldm).unwrap(),
+            all_information.outputs[0][0].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_part1_with_pow_bits() {
+        let config = PcsConfig {
+            pow_bits: PROOF_OF_WORK_BITS + 2,
+            ..Default::default()
+        };
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+        let hints = Hints::from_proof(&plonk_component, proof, config).unwrap();
+
+        let mut ldm = LDM::new();
+        let cs =
+            super::part1_fiat_shamir1::generate_cs_with_pow_bits(&hints, &mut ldm, config.pow_bits)
+                .unwrap();
+        test_program(
+            cs,
+            script! {
+                { ldm.hash_var.as_ref().unwrap().value.clone() }
+            },
+        )
+        .unwrap();
+
+        // the proof of work does not meet a stricter requirement
+        let mut ldm = LDM::new();
+        let res = super::part1_fiat_shamir1::generate_cs_with_pow_bits(
+            &hints,
+            &mut ldm,
+            config.pow_bits + 16,
+        );
+        assert!(res.is_err());
+    }
 }
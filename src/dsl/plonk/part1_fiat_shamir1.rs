@@ -2,19 +2,30 @@ use crate::algorithms::point::get_oods_point;
 use crate::algorithms::pow::verify_pow;
 use crate::algorithms::twin_tree::query_and_verify_merkle_twin_tree;
 use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
+use crate::dsl::plonk::logup_gadget::{assert_constant_columns, constant_commitment};
 use crate::dsl::primitives::channel::HashVarWithChannel;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bitcoin_script_dsl::builtins::hash::HashVar;
 use bitcoin_script_dsl::bvar::AllocVar;
 use bitcoin_script_dsl::constraint_system::{ConstraintSystem, ConstraintSystemRef};
 use bitcoin_script_dsl::ldm::LDM;
 use stwo_prover::core::channel::Sha256Channel;
-use stwo_prover::core::prover::{LOG_BLOWUP_FACTOR, PROOF_OF_WORK_BITS};
+use stwo_prover::core::prover::{LOG_BLOWUP_FACTOR, N_QUERIES, PROOF_OF_WORK_BITS};
 
 pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
+    generate_cs_with_pow_bits(hints, ldm, PROOF_OF_WORK_BITS)
+}
+
+/// Generate the first part of the verifier for a proof produced with `pow_bits` bits of proof of
+/// work, rather than the default of stwo.
+pub fn generate_cs_with_pow_bits(
+    hints: &Hints,
+    ldm: &mut LDM,
+    pow_bits: u32,
+) -> Result<ConstraintSystemRef> {
     let cs = ConstraintSystem::new_ref();
     ldm.init(&cs)?;
 
@@ -48,6 +59,10 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef>
 
     channel_var = &channel_var + &interaction_commitment_var;
     channel_var = &channel_var + &constant_commitment_var;
+    // the constant commitment is pinned to the public constant columns once, and then only
+    // queried, per query
+    assert_constant_columns(&constant_commitment_var, &constant_commitment())?;
+    ldm.write("constant_commitment", &constant_commitment_var)?;
 
     let composition_fold_random_coeff_var = channel_var.draw_felt();
     ldm.write(
@@ -148,20 +163,25 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef>
         folding_alphas_vars.push(folding_alpha_var);
     }
 
-    // Step 9: get the last layer and mix it with the channel
-    let last_layer_var = QM31Var::new_hint(&cs, hints.fiat_shamir_hints.last_layer)?;
+    // Step 9: get the last layer and mix it with the channel, which must be a constant since the
+    // per-query folding checks against it
+    if hints.fiat_shamir_hints.last_layer.len() != 1 {
+        bail!("the FRI last layer must be a constant");
+    }
+    let last_layer_var = QM31Var::new_hint(&cs, hints.fiat_shamir_hints.last_layer[0])?;
     ldm.write("last_layer", &last_layer_var)?;
     channel_var = &channel_var + &last_layer_var;
 
     // Step 10: check proof of work
     verify_pow(
         &mut channel_var,
-        PROOF_OF_WORK_BITS,
+        pow_bits,
         hints.fiat_shamir_hints.pow_hint.nonce,
     )?;
 
     // Step 11: draw all the queries
-    let queries = channel_var.draw_numbers(8, (LOG_N_ROWS + LOG_BLOWUP_FACTOR + 1) as usize);
+    let queries =
+        channel_var.draw_numbers(N_QUERIES, (LOG_N_ROWS + LOG_BLOWUP_FACTOR + 1) as usize);
     for (i, query) in queries.iter().enumerate() {
         ldm.write(format!("query_{}", i), query)?;
     }
@@ -239,41 +259,6 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef>
         ldm.write(format!("interaction_cum_queried_results_{}_r", i), &right)?;
     }
 
-    // Step 14: query the constant commitment on the queries
-    for (i, (query, proof)) in queries
-        .iter()
-        .zip(hints.fiat_shamir_hints.merkle_proofs_constants.iter())
-        .enumerate()
-    {
-        let res = query_and_verify_merkle_twin_tree(&constant_commitment_var, query, proof)?;
-        ldm.write(
-            format!("constant_a_wire_queried_results_{}_l", i),
-            &res.0[0],
-        )?;
-        ldm.write(
-            format!("constant_a_wire_queried_results_{}_r", i),
-            &res.1[0],
-        )?;
-        ldm.write(
-            format!("constant_b_wire_queried_results_{}_l", i),
-            &res.0[1],
-        )?;
-        ldm.write(
-            format!("constant_b_wire_queried_results_{}_r", i),
-            &res.1[1],
-        )?;
-        ldm.write(
-            format!("constant_c_wire_queried_results_{}_l", i),
-            &res.0[2],
-        )?;
-        ldm.write(
-            format!("constant_c_wire_queried_results_{}_r", i),
-            &res.1[2],
-        )?;
-        ldm.write(format!("constant_op_queried_results_{}_l", i), &res.0[3])?;
-        ldm.write(format!("constant_op_queried_results_{}_r", i), &res.1[3])?;
-    }
-
     // compute the OODS point
     let table = TableVar::new_constant(&cs, ())?;
     let point = get_oods_point(&mut channel_var_before_oods, &table);
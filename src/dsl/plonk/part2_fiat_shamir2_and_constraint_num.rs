@@ -1,13 +1,16 @@
-use crate::algorithms::precomputed_tree::query_and_verify_precomputed_merkle_tree;
+use crate::algorithms::precomputed_tree::{
+    compute_query_point_and_twiddles, query_and_verify_precomputed_merkle_tree,
+};
 use crate::algorithms::twin_tree::query_and_verify_merkle_twin_tree;
-use crate::dsl::plonk::hints::Hints;
+use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
+use crate::dsl::plonk::logup_gadget::{
+    evaluate_logup_constraints, verify_claimed_sum_divided, LookupElementsVar,
+};
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
-use crate::precomputed_merkle_tree::{
-    get_precomputed_merkle_tree_roots, PRECOMPUTED_MERKLE_TREE_ROOTS,
-};
+use crate::precomputed_merkle_tree::{ensure_roots, require_root};
 use anyhow::Result;
 use bitcoin_script_dsl::builtins::hash::HashVar;
 use bitcoin_script_dsl::bvar::AllocVar;
@@ -16,6 +19,21 @@ use bitcoin_script_dsl::ldm::LDM;
 use stwo_prover::core::prover::N_QUERIES;
 
 pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
+    generate_cs_internal(hints, ldm, false)
+}
+
+/// Generate the same program as `generate_cs`, but compute the circle points and the twiddle
+/// factors of the queries in the script instead of verifying them against the root of the
+/// precomputed Merkle tree, which needs no precomputation at the cost of a larger script.
+pub fn generate_cs_self_contained(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
+    generate_cs_internal(hints, ldm, true)
+}
+
+fn generate_cs_internal(
+    hints: &Hints,
+    ldm: &mut LDM,
+    self_contained: bool,
+) -> Result<ConstraintSystemRef> {
     let cs = ConstraintSystem::new_ref();
     ldm.init(&cs)?;
 
@@ -58,20 +76,25 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef>
         ldm.write(format!("composition_queried_results_{}_r", i), &right)?;
     }
 
-    let precomputed_merkle_tree_roots =
-        PRECOMPUTED_MERKLE_TREE_ROOTS.get_or_init(get_precomputed_merkle_tree_roots);
+    let precomputed_merkle_tree_root = if self_contained {
+        None
+    } else {
+        ensure_roots(&[15]);
+        Some(require_root(15)?)
+    };
 
     for (i, (query, pre_query_quotients_hint)) in queries
         .iter()
         .zip(hints.per_query_quotients_hints.iter())
         .enumerate()
     {
-        let proof = &pre_query_quotients_hint.precomputed_merkle_proofs[0];
-        let res = query_and_verify_precomputed_merkle_tree(
-            precomputed_merkle_tree_roots.get(&15).unwrap().as_ref(),
-            query,
-            proof,
-        )?;
+        let res = match precomputed_merkle_tree_root {
+            Some(root) => {
+                let proof = &pre_query_quotients_hint.precomputed_merkle_proofs[0];
+                query_and_verify_precomputed_merkle_tree(root.as_ref(), query, proof)?
+            }
+            None => compute_query_point_and_twiddles(query, 15),
+        };
         ldm.write(format!("circle_point_x_{}", i), &res.circle_point_x_var)?;
         ldm.write(format!("circle_point_y_{}", i), &res.circle_point_y_var)?;
         ldm.write(format!("twiddle_factor_1_{}", i), &res.twiddles_var[13])?;
@@ -100,62 +123,42 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef>
 
     res1 = &res1 * &composition_fold_random_coeff_squared_var;
 
+    let lookup_elements = LookupElementsVar {
+        z: ldm.read("z")?,
+        alpha: ldm.read("alpha")?,
+    };
+
     let a_wire_var: QM31Var = ldm.read("constant_oods_value_0")?;
     let b_wire_var: QM31Var = ldm.read("constant_oods_value_1")?;
     let c_wire_var: QM31Var = ldm.read("constant_oods_value_2")?;
-    let alpha_var: QM31Var = ldm.read("alpha")?;
-    let z_var: QM31Var = ldm.read("z")?;
-
-    let denominator_1_var = &(&a_wire_var + &(&alpha_var * (&table, &a_val_var))) - &z_var;
-    let denominator_2_var = &(&b_wire_var + &(&alpha_var * (&table, &b_val_var))) - &z_var;
-
-    let num_aggregated_var = &denominator_1_var + &denominator_2_var;
-    let denom_aggregated_var = &denominator_1_var * (&table, &denominator_2_var);
-
-    let a_b_logup_0_var: QM31Var = ldm.read("interaction_oods_value_0")?;
-    let a_b_logup_1_var: QM31Var = ldm.read("interaction_oods_value_1")?;
-    let a_b_logup_2_var: QM31Var = ldm.read("interaction_oods_value_2")?;
-    let a_b_logup_3_var: QM31Var = ldm.read("interaction_oods_value_3")?;
-
-    let mut a_b_logup_var = &a_b_logup_0_var + &a_b_logup_1_var.shift_by_i();
-    a_b_logup_var = &a_b_logup_var + &a_b_logup_2_var.shift_by_j();
-    a_b_logup_var = &a_b_logup_var + &a_b_logup_3_var.shift_by_ij();
-
-    let mut res2 = &(&a_b_logup_var * (&table, &denom_aggregated_var)) - &num_aggregated_var;
-    res2 = &res2 * &composition_fold_random_coeff_var;
-
-    let res12 = &res1 + &res2;
-
-    let denominator_3_var = &(&c_wire_var + &(&alpha_var * (&table, &c_val_var))) - &z_var;
-
-    let c_logup_0_var: QM31Var = ldm.read("interaction_oods_value_4")?;
-    let c_logup_1_var: QM31Var = ldm.read("interaction_oods_value_6")?;
-    let c_logup_2_var: QM31Var = ldm.read("interaction_oods_value_8")?;
-    let c_logup_3_var: QM31Var = ldm.read("interaction_oods_value_10")?;
-
-    let mut c_logup_var = &c_logup_0_var + &c_logup_1_var.shift_by_i();
-    c_logup_var = &c_logup_var + &c_logup_2_var.shift_by_j();
-    c_logup_var = &c_logup_var + &c_logup_3_var.shift_by_ij();
-
-    let c_logup_next_0_var: QM31Var = ldm.read("interaction_oods_value_5")?;
-    let c_logup_next_1_var: QM31Var = ldm.read("interaction_oods_value_7")?;
-    let c_logup_next_2_var: QM31Var = ldm.read("interaction_oods_value_9")?;
-    let c_logup_next_3_var: QM31Var = ldm.read("interaction_oods_value_11")?;
+    let mult_var: QM31Var = ldm.read("trace_oods_value_0")?;
 
-    let mut c_logup_next_var = &c_logup_next_0_var + &c_logup_next_1_var.shift_by_i();
-    c_logup_next_var = &c_logup_next_var + &c_logup_next_2_var.shift_by_j();
-    c_logup_next_var = &c_logup_next_var + &c_logup_next_3_var.shift_by_ij();
+    let mut interaction_values = Vec::<QM31Var>::new();
+    for i in 0..12 {
+        interaction_values.push(ldm.read(format!("interaction_oods_value_{}", i))?);
+    }
+    let interaction_values: [QM31Var; 12] = interaction_values.try_into().unwrap();
 
-    // for testing purposes, claimed sum divided is given as an unrestrained hint
+    // the claimed sum is sent along with the proof, and the claimed sum divided by the domain size
+    // is verified against it
+    let claimed_sum = QM31Var::new_hint(&cs, hints.fiat_shamir_hints.claimed_sum)?;
     let claimed_sum_divided = QM31Var::new_hint(&cs, hints.fiat_shamir_hints.claimed_sum_divided)?;
+    verify_claimed_sum_divided(&claimed_sum, &claimed_sum_divided, LOG_N_ROWS)?;
+
+    let (logup_res1, logup_res2) = evaluate_logup_constraints(
+        &table,
+        &lookup_elements,
+        [&a_wire_var, &b_wire_var, &c_wire_var],
+        [&a_val_var, &b_val_var, &c_val_var],
+        &mult_var,
+        &interaction_values,
+        &claimed_sum_divided,
+    );
+
+    let res2 = &logup_res1 * &composition_fold_random_coeff_var;
+    let res12 = &res1 + &res2;
 
-    let mut res3 = &(&(&c_logup_var - &c_logup_next_var) - &a_b_logup_var) + &claimed_sum_divided;
-    res3 = &res3 * (&table, &denominator_3_var);
-
-    let mult_var: QM31Var = ldm.read("trace_oods_value_0")?;
-    res3 = &res3 + &mult_var;
-
-    let constraint_num = &res12 + &res3;
+    let constraint_num = &res12 + &logup_res2;
     ldm.write("constraint_num", &constraint_num)?;
 
     ldm.save()?;
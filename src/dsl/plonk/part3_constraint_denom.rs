@@ -1,6 +1,5 @@
-use crate::algorithms::point::{
-    add_constant_m31_point, add_constant_m31_point_x_only, SecureCirclePointVar,
-};
+use crate::algorithms::composition::assert_composition_matches;
+use crate::algorithms::point::{add_constant_m31_point, coset_vanishing, SecureCirclePointVar};
 use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
@@ -24,16 +23,8 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
 
     let table = TableVar::new_constant(&cs, ())?;
 
-    let coset = CanonicCoset::new(LOG_N_ROWS).coset;
-    let shift = -coset.initial + coset.step_size.half().to_point();
-    let mut cur_x = add_constant_m31_point_x_only(&oods_point, &table, shift);
-    for _ in 1..coset.log_size {
-        cur_x = &cur_x * (&table, &cur_x);
-        cur_x = &cur_x + &cur_x;
-        cur_x = cur_x.sub1();
-    }
-
-    let constraint_denom = cur_x.inverse(&table);
+    // the inverse also asserts that the oods point is not in the trace domain
+    let constraint_denom = coset_vanishing(&oods_point, &table, LOG_N_ROWS).inverse(&table);
 
     let constraint_num: QM31Var = ldm.read("constraint_num")?;
     let computed_composition = &constraint_denom * (&table, &constraint_num);
@@ -43,11 +34,15 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
     let composition_2_var: QM31Var = ldm.read("composition_oods_value_2")?;
     let composition_3_var: QM31Var = ldm.read("composition_oods_value_3")?;
 
-    let mut composition_var = &composition_0_var + &composition_1_var.shift_by_i();
-    composition_var = &composition_var + &composition_2_var.shift_by_j();
-    composition_var = &composition_var + &composition_3_var.shift_by_ij();
-
-    computed_composition.equalverify(&composition_var)?;
+    assert_composition_matches(
+        &computed_composition,
+        [
+            &composition_0_var,
+            &composition_1_var,
+            &composition_2_var,
+            &composition_3_var,
+        ],
+    )?;
 
     // shift the oods point
     let trace_step = CanonicCoset::new(LOG_N_ROWS).step();
@@ -1,5 +1,6 @@
 use crate::algorithms::pair_vanishing::prepare_pair_vanishing;
 use crate::algorithms::point::SecureCirclePointVar;
+use crate::algorithms::quotient::coeff_powers;
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
@@ -48,15 +49,15 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM) -> anyhow::Result<ConstraintSystemR
     // The needed alphas are:
     // - alpha, alpha^2, alpha^3, alpha^4, alpha^8, alpha^12, alpha^20
 
-    let alpha2 = &alpha * (&table, &alpha);
-    let alpha3 = &alpha2 * (&table, &alpha);
-    let alpha4 = &alpha2 * (&table, &alpha2);
+    let powers = coeff_powers(&table, &alpha, 3);
+    let (alpha2, alpha3) = (&powers[1], &powers[2]);
+    let alpha4 = alpha2 * (&table, alpha2);
     let alpha8 = &alpha4 * (&table, &alpha4);
     let alpha12 = &alpha8 * (&table, &alpha4);
     let alpha20 = &alpha8 * (&table, &alpha12);
 
-    ldm.write("line_batch_random_coeff_2", &alpha2)?;
-    ldm.write("line_batch_random_coeff_3", &alpha3)?;
+    ldm.write("line_batch_random_coeff_2", alpha2)?;
+    ldm.write("line_batch_random_coeff_3", alpha3)?;
     ldm.write("line_batch_random_coeff_4", &alpha4)?;
     ldm.write("line_batch_random_coeff_8", &alpha8)?;
     ldm.write("line_batch_random_coeff_12", &alpha12)?;
@@ -1,4 +1,6 @@
-use crate::algorithms::folding::{decompose_positions, ibutterfly, skip_one_and_extract_bits};
+use crate::algorithms::folding::{
+    decompose_positions, fold_step, skip_one_and_extract_bits, FoldConfig,
+};
 use crate::algorithms::twin_tree::query_and_verify_merkle_twin_tree;
 use crate::dsl::plonk::hints::{Hints, LOG_N_ROWS};
 use crate::dsl::primitives::cm31::CM31Var;
@@ -70,6 +72,7 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<Con
     }
 
     let table = TableVar::new_constant(&cs, ())?;
+    let fold_config = FoldConfig::default();
 
     let mut folded_results_vars = vec![];
     for ((folding_intermediate_result, twiddle_var), folding_alpha_var) in folding_intermediate_vars
@@ -77,15 +80,16 @@ pub fn generate_cs(hints: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<Con
         .zip(twiddles_vars.iter())
         .zip(folding_alphas_vars.iter())
     {
-        let ifft_results_vars = ibutterfly(
+        folded_results_vars.push(fold_step(
             &table,
-            &folding_intermediate_result.0,
-            &folding_intermediate_result.1,
-            twiddle_var,
-        );
-
-        folded_results_vars
-            .push(&ifft_results_vars.0 + &(&ifft_results_vars.1 * (&table, folding_alpha_var)));
+            &fold_config,
+            &[
+                folding_intermediate_result.0.clone(),
+                folding_intermediate_result.1.clone(),
+            ],
+            &[vec![twiddle_var.clone()]],
+            folding_alpha_var,
+        ));
     }
 
     for i in 0..4 {
@@ -1,4 +1,6 @@
-use crate::algorithms::quotient::{apply_twin, denominator_inverse_from_prepared};
+use crate::algorithms::quotient::{
+    aggregate_numerators, apply_twin, denominator_inverse_from_prepared,
+};
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
@@ -83,15 +85,29 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<Constra
     let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
     let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
 
-    let mut sum_num_trace_l = &alpha3 * (&table, &numerator_trace_mult.0);
-    sum_num_trace_l = &sum_num_trace_l + &(&alpha2 * (&table, &numerator_trace_a_val.0));
-    sum_num_trace_l = &sum_num_trace_l + &(&alpha * (&table, &numerator_trace_b_val.0));
-    sum_num_trace_l = &sum_num_trace_l + &numerator_trace_c_val.0;
+    let coeff_powers = [alpha, alpha2, alpha3];
 
-    let mut sum_num_trace_r = &alpha3 * (&table, &numerator_trace_mult.1);
-    sum_num_trace_r = &sum_num_trace_r + &(&alpha2 * (&table, &numerator_trace_a_val.1));
-    sum_num_trace_r = &sum_num_trace_r + &(&alpha * (&table, &numerator_trace_b_val.1));
-    sum_num_trace_r = &sum_num_trace_r + &numerator_trace_c_val.1;
+    let sum_num_trace_l = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_trace_mult.0,
+            &numerator_trace_a_val.0,
+            &numerator_trace_b_val.0,
+            &numerator_trace_c_val.0,
+        ],
+    );
+
+    let sum_num_trace_r = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_trace_mult.1,
+            &numerator_trace_a_val.1,
+            &numerator_trace_b_val.1,
+            &numerator_trace_c_val.1,
+        ],
+    );
 
     let alpha20: QM31Var = ldm.read("line_batch_random_coeff_20")?;
 
@@ -1,15 +1,19 @@
-use crate::algorithms::quotient::{apply_twin, denominator_inverse_from_prepared};
+use crate::algorithms::quotient::{
+    aggregate_numerators, apply_twin, denominator_inverse_from_prepared,
+};
+use crate::algorithms::twin_tree::query_and_verify_merkle_twin_tree;
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
 use crate::dsl::primitives::qm31::QM31Var;
 use crate::dsl::primitives::table::TableVar;
+use bitcoin_script_dsl::builtins::hash::HashVar;
 use bitcoin_script_dsl::bvar::AllocVar;
 use bitcoin_script_dsl::constraint_system::{ConstraintSystem, ConstraintSystemRef};
 use bitcoin_script_dsl::ldm::LDM;
 
 pub fn generate_cs(
-    _: &Hints,
+    hints: &Hints,
     ldm: &mut LDM,
     query_idx: usize,
 ) -> anyhow::Result<ConstraintSystemRef> {
@@ -20,23 +24,15 @@ pub fn generate_cs(
     let y: M31Var = ldm.read(format!("circle_point_y_{}", query_idx))?;
     let table = TableVar::new_constant(&cs, ())?;
 
-    let mut constant_queried_results = Vec::<(M31Var, M31Var)>::new();
-    constant_queried_results.push((
-        ldm.read(format!("constant_a_wire_queried_results_{}_l", query_idx))?,
-        ldm.read(format!("constant_a_wire_queried_results_{}_r", query_idx))?,
-    ));
-    constant_queried_results.push((
-        ldm.read(format!("constant_b_wire_queried_results_{}_l", query_idx))?,
-        ldm.read(format!("constant_b_wire_queried_results_{}_r", query_idx))?,
-    ));
-    constant_queried_results.push((
-        ldm.read(format!("constant_c_wire_queried_results_{}_l", query_idx))?,
-        ldm.read(format!("constant_c_wire_queried_results_{}_r", query_idx))?,
-    ));
-    constant_queried_results.push((
-        ldm.read(format!("constant_op_queried_results_{}_l", query_idx))?,
-        ldm.read(format!("constant_op_queried_results_{}_r", query_idx))?,
-    ));
+    // query the constant commitment, which part1 has pinned to the public constant columns
+    let query: M31Var = ldm.read(format!("query_{}", query_idx))?;
+    let constant_commitment: HashVar = ldm.read("constant_commitment")?;
+    let (left, right) = query_and_verify_merkle_twin_tree(
+        &constant_commitment,
+        &query,
+        &hints.fiat_shamir_hints.merkle_proofs_constants[query_idx],
+    )?;
+    let constant_queried_results = left.into_iter().zip(right).collect::<Vec<_>>();
 
     let mut column_line_constant_vars = Vec::<(CM31Var, CM31Var)>::new();
     for i in 0..4 {
@@ -86,15 +82,29 @@ pub fn generate_cs(
     let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
     let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
 
-    let mut sum_num_constant_l = &alpha3 * (&table, &numerator_constant_a_wire.0);
-    sum_num_constant_l = &sum_num_constant_l + &(&alpha2 * (&table, &numerator_constant_b_wire.0));
-    sum_num_constant_l = &sum_num_constant_l + &(&alpha * (&table, &numerator_constant_c_wire.0));
-    sum_num_constant_l = &sum_num_constant_l + &numerator_constant_op.0;
+    let coeff_powers = [alpha, alpha2, alpha3];
+
+    let sum_num_constant_l = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_constant_a_wire.0,
+            &numerator_constant_b_wire.0,
+            &numerator_constant_c_wire.0,
+            &numerator_constant_op.0,
+        ],
+    );
 
-    let mut sum_num_constant_r = &alpha3 * (&table, &numerator_constant_a_wire.1);
-    sum_num_constant_r = &sum_num_constant_r + &(&alpha2 * (&table, &numerator_constant_b_wire.1));
-    sum_num_constant_r = &sum_num_constant_r + &(&alpha * (&table, &numerator_constant_c_wire.1));
-    sum_num_constant_r = &sum_num_constant_r + &numerator_constant_op.1;
+    let sum_num_constant_r = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_constant_a_wire.1,
+            &numerator_constant_b_wire.1,
+            &numerator_constant_c_wire.1,
+            &numerator_constant_op.1,
+        ],
+    );
 
     let alpha8: QM31Var = ldm.read("line_batch_random_coeff_8")?;
 
@@ -1,4 +1,4 @@
-use crate::algorithms::quotient::apply_twin;
+use crate::algorithms::quotient::{aggregate_numerators, apply_twin};
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
@@ -69,19 +69,29 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<Constra
     let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
     let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
 
-    let mut sum_num_composition_l = &alpha3 * (&table, &numerator_composition_0.0);
-    sum_num_composition_l =
-        &sum_num_composition_l + &(&alpha2 * (&table, &numerator_composition_1.0));
-    sum_num_composition_l =
-        &sum_num_composition_l + &(&alpha * (&table, &numerator_composition_2.0));
-    sum_num_composition_l = &sum_num_composition_l + &numerator_composition_3.0;
-
-    let mut sum_num_composition_r = &alpha3 * (&table, &numerator_composition_0.1);
-    sum_num_composition_r =
-        &sum_num_composition_r + &(&alpha2 * (&table, &numerator_composition_1.1));
-    sum_num_composition_r =
-        &sum_num_composition_r + &(&alpha * (&table, &numerator_composition_2.1));
-    sum_num_composition_r = &sum_num_composition_r + &numerator_composition_3.1;
+    let coeff_powers = [alpha, alpha2, alpha3];
+
+    let sum_num_composition_l = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_composition_0.0,
+            &numerator_composition_1.0,
+            &numerator_composition_2.0,
+            &numerator_composition_3.0,
+        ],
+    );
+
+    let sum_num_composition_r = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_composition_0.1,
+            &numerator_composition_1.1,
+            &numerator_composition_2.1,
+            &numerator_composition_3.1,
+        ],
+    );
 
     let alpha4: QM31Var = ldm.read("line_batch_random_coeff_4")?;
 
@@ -1,4 +1,4 @@
-use crate::algorithms::quotient::apply_twin;
+use crate::algorithms::quotient::{aggregate_numerators, apply_twin};
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
@@ -69,21 +69,29 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<Constra
     let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
     let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
 
-    let mut sum_num_interaction_shifted_l = &alpha3 * (&table, &numerator_interaction_shifted_0.0);
-    sum_num_interaction_shifted_l =
-        &sum_num_interaction_shifted_l + &(&alpha2 * (&table, &numerator_interaction_shifted_1.0));
-    sum_num_interaction_shifted_l =
-        &sum_num_interaction_shifted_l + &(&alpha * (&table, &numerator_interaction_shifted_2.0));
-    sum_num_interaction_shifted_l =
-        &sum_num_interaction_shifted_l + &numerator_interaction_shifted_3.0;
-
-    let mut sum_num_interaction_shifted_r = &alpha3 * (&table, &numerator_interaction_shifted_0.1);
-    sum_num_interaction_shifted_r =
-        &sum_num_interaction_shifted_r + &(&alpha2 * (&table, &numerator_interaction_shifted_1.1));
-    sum_num_interaction_shifted_r =
-        &sum_num_interaction_shifted_r + &(&alpha * (&table, &numerator_interaction_shifted_2.1));
-    sum_num_interaction_shifted_r =
-        &sum_num_interaction_shifted_r + &numerator_interaction_shifted_3.1;
+    let coeff_powers = [alpha, alpha2, alpha3];
+
+    let sum_num_interaction_shifted_l = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_interaction_shifted_0.0,
+            &numerator_interaction_shifted_1.0,
+            &numerator_interaction_shifted_2.0,
+            &numerator_interaction_shifted_3.0,
+        ],
+    );
+
+    let sum_num_interaction_shifted_r = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_interaction_shifted_0.1,
+            &numerator_interaction_shifted_1.1,
+            &numerator_interaction_shifted_2.1,
+            &numerator_interaction_shifted_3.1,
+        ],
+    );
 
     let denominator_inverse_shifted_l: CM31Var =
         ldm.read(format!("denominator_inverse_shifted_{}_l", query_idx))?;
@@ -1,4 +1,4 @@
-use crate::algorithms::quotient::apply_twin;
+use crate::algorithms::quotient::{aggregate_numerators, apply_twin, coeff_powers};
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
@@ -9,7 +9,29 @@ use bitcoin_script_dsl::bvar::AllocVar;
 use bitcoin_script_dsl::constraint_system::{ConstraintSystem, ConstraintSystemRef};
 use bitcoin_script_dsl::ldm::LDM;
 
-pub fn generate_cs(_: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<ConstraintSystemRef> {
+pub fn generate_cs(hints: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<ConstraintSystemRef> {
+    generate_cs_internal(hints, ldm, query_idx, false)
+}
+
+/// Generate the same program as `generate_cs`, but only read the random coefficient from the LDM
+/// and recompute its powers in the script, instead of reading the powers stored by part 4.
+///
+/// This variant is not part of any covenant program, since `plonk_verifier` uses `generate_cs`,
+/// and its peak stack depth has not been measured; only its script size is reported.
+pub fn generate_cs_lazy(
+    hints: &Hints,
+    ldm: &mut LDM,
+    query_idx: usize,
+) -> Result<ConstraintSystemRef> {
+    generate_cs_internal(hints, ldm, query_idx, true)
+}
+
+fn generate_cs_internal(
+    _: &Hints,
+    ldm: &mut LDM,
+    query_idx: usize,
+    lazy: bool,
+) -> Result<ConstraintSystemRef> {
     let cs = ConstraintSystem::new_ref();
     ldm.init(&cs)?;
 
@@ -65,25 +87,44 @@ pub fn generate_cs(_: &Hints, ldm: &mut LDM, query_idx: usize) -> Result<Constra
         &column_line_interaction_vars[3].1,
     );
 
-    let alpha3: QM31Var = ldm.read("line_batch_random_coeff_3")?;
-    let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
-    let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
-
-    let mut sum_num_interaction_l = &alpha3 * (&table, &numerator_interaction_0.0);
-    sum_num_interaction_l =
-        &sum_num_interaction_l + &(&alpha2 * (&table, &numerator_interaction_1.0));
-    sum_num_interaction_l =
-        &sum_num_interaction_l + &(&alpha * (&table, &numerator_interaction_2.0));
-    sum_num_interaction_l = &sum_num_interaction_l + &numerator_interaction_3.0;
-
-    let mut sum_num_interaction_r = &alpha3 * (&table, &numerator_interaction_0.1);
-    sum_num_interaction_r =
-        &sum_num_interaction_r + &(&alpha2 * (&table, &numerator_interaction_1.1));
-    sum_num_interaction_r =
-        &sum_num_interaction_r + &(&alpha * (&table, &numerator_interaction_2.1));
-    sum_num_interaction_r = &sum_num_interaction_r + &numerator_interaction_3.1;
-
-    let alpha4: QM31Var = ldm.read("line_batch_random_coeff_4")?;
+    let (alpha_powers, lazy_alpha4) = if lazy {
+        let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
+        let mut powers = coeff_powers(&table, &alpha, 4);
+        let alpha4 = powers.pop();
+        (powers, alpha4)
+    } else {
+        let alpha3: QM31Var = ldm.read("line_batch_random_coeff_3")?;
+        let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
+        let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
+        (vec![alpha, alpha2, alpha3], None)
+    };
+
+    let sum_num_interaction_l = aggregate_numerators(
+        &table,
+        &alpha_powers,
+        &[
+            &numerator_interaction_0.0,
+            &numerator_interaction_1.0,
+            &numerator_interaction_2.0,
+            &numerator_interaction_3.0,
+        ],
+    );
+
+    let sum_num_interaction_r = aggregate_numerators(
+        &table,
+        &alpha_powers,
+        &[
+            &numerator_interaction_0.1,
+            &numerator_interaction_1.1,
+            &numerator_interaction_2.1,
+            &numerator_interaction_3.1,
+        ],
+    );
+
+    let alpha4: QM31Var = match lazy_alpha4 {
+        Some(alpha4) => alpha4,
+        None => ldm.read("line_batch_random_coeff_4")?,
+    };
     let alpha4interaction_ab_l = &alpha4 * (&table, &sum_num_interaction_l);
     let alpha4interaction_ab_r = &alpha4 * (&table, &sum_num_interaction_r);
 
@@ -1,4 +1,4 @@
-use crate::algorithms::quotient::apply_twin;
+use crate::algorithms::quotient::{aggregate_numerators, apply_twin};
 use crate::dsl::plonk::hints::Hints;
 use crate::dsl::primitives::cm31::CM31Var;
 use crate::dsl::primitives::m31::M31Var;
@@ -72,19 +72,29 @@ pub fn generate_cs(
     let alpha2: QM31Var = ldm.read("line_batch_random_coeff_2")?;
     let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
 
-    let mut sum_num_interaction_l = &alpha3 * (&table, &numerator_interaction_0.0);
-    sum_num_interaction_l =
-        &sum_num_interaction_l + &(&alpha2 * (&table, &numerator_interaction_1.0));
-    sum_num_interaction_l =
-        &sum_num_interaction_l + &(&alpha * (&table, &numerator_interaction_2.0));
-    sum_num_interaction_l = &sum_num_interaction_l + &numerator_interaction_3.0;
-
-    let mut sum_num_interaction_r = &alpha3 * (&table, &numerator_interaction_0.1);
-    sum_num_interaction_r =
-        &sum_num_interaction_r + &(&alpha2 * (&table, &numerator_interaction_1.1));
-    sum_num_interaction_r =
-        &sum_num_interaction_r + &(&alpha * (&table, &numerator_interaction_2.1));
-    sum_num_interaction_r = &sum_num_interaction_r + &numerator_interaction_3.1;
+    let coeff_powers = [alpha, alpha2, alpha3];
+
+    let sum_num_interaction_l = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_interaction_0.0,
+            &numerator_interaction_1.0,
+            &numerator_interaction_2.0,
+            &numerator_interaction_3.0,
+        ],
+    );
+
+    let sum_num_interaction_r = aggregate_numerators(
+        &table,
+        &coeff_powers,
+        &[
+            &numerator_interaction_0.1,
+            &numerator_interaction_1.1,
+            &numerator_interaction_2.1,
+            &numerator_interaction_3.1,
+        ],
+    );
 
     let alpha4interaction_ab_l: QM31Var =
         ldm.read(format!("alpha4interaction_ab_{}_l", query_idx))?;
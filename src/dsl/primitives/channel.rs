@@ -247,7 +247,9 @@ fn draw_hints_to_str_vars(cs: &ConstraintSystemRef, hint: DrawHints) -> Result<V
 mod test {
     use crate::channel::ChannelWithHint;
     use crate::dsl::primitives::channel::HashVarWithChannel;
+    use crate::dsl::primitives::qm31::QM31Var;
     use crate::treepp::*;
+    use crate::utils::get_rand_qm31;
     use bitcoin_script_dsl::builtins::hash::HashVar;
     use bitcoin_script_dsl::bvar::AllocVar;
     use bitcoin_script_dsl::constraint_system::ConstraintSystem;
@@ -288,6 +290,40 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn test_mix_last_layer_coefficients() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut init_state = [0u8; 32];
+        init_state.iter_mut().for_each(|v| *v = prng.gen());
+        let init_state = Sha256Hash::from(init_state.to_vec());
+
+        let last_layer_poly = vec![get_rand_qm31(&mut prng), get_rand_qm31(&mut prng)];
+
+        let mut channel = Sha256Channel::default();
+        channel.update_digest(init_state);
+        channel.mix_felts(&last_layer_poly);
+        let c = channel.digest;
+
+        let cs = ConstraintSystem::new_ref();
+
+        let mut channel_digest = HashVar::new_constant(&cs, init_state.as_ref().to_vec()).unwrap();
+        for &coeff in last_layer_poly.iter() {
+            let coeff_var = QM31Var::new_hint(&cs, coeff).unwrap();
+            channel_digest = &channel_digest + &coeff_var;
+        }
+
+        cs.set_program_output(&channel_digest).unwrap();
+
+        test_program(
+            cs,
+            script! {
+                { c }
+            },
+        )
+        .unwrap();
+    }
+
     #[test]
     fn test_draw_numbers() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
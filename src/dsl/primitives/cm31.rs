@@ -177,6 +177,29 @@ impl CM31Var {
         res_var
     }
 
+    /// Invert several elements with a single inverse hint using Montgomery batch inversion.
+    pub fn batch_inverse(values: &[CM31Var], table: &TableVar) -> Vec<CM31Var> {
+        assert!(!values.is_empty());
+
+        let mut prefix_products = vec![values[0].clone()];
+        for value in values.iter().skip(1) {
+            let product = prefix_products.last().unwrap() * (table, value);
+            prefix_products.push(product);
+        }
+
+        let mut cur_inverse = prefix_products.last().unwrap().inverse(table);
+
+        let mut inverses = vec![];
+        for i in (1..values.len()).rev() {
+            inverses.push(&cur_inverse * (table, &prefix_products[i - 1]));
+            cur_inverse = &cur_inverse * (table, &values[i]);
+        }
+        inverses.push(cur_inverse);
+        inverses.reverse();
+
+        inverses
+    }
+
     pub fn inverse_without_table(&self) -> Self {
         let cs = self.cs();
         let res = self.value().unwrap().inverse();
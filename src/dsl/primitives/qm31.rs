@@ -5,7 +5,7 @@ use crate::dsl::primitives::m31_limbs::M31LimbsVar;
 use crate::dsl::primitives::qm31_limbs::QM31LimbsVar;
 use crate::dsl::primitives::table::TableVar;
 use crate::treepp::*;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bitcoin_script_dsl::builtins::hash::HashVar;
 use bitcoin_script_dsl::bvar::{AllocVar, AllocationMode, BVar};
 use bitcoin_script_dsl::constraint_system::ConstraintSystemRef;
@@ -277,6 +277,21 @@ impl QM31Var {
         self.shift_by_i().shift_by_j()
     }
 
+    pub fn from_partial_evals(evals: [&QM31Var; 4]) -> QM31Var {
+        let mut res = evals[0] + &evals[1].shift_by_i();
+        res = &res + &evals[2].shift_by_j();
+        res = &res + &evals[3].shift_by_ij();
+        res
+    }
+
+    pub fn verify_from_partial_evals(evals: [&QM31Var; 4], claimed: &QM31Var) -> Result<()> {
+        let res = Self::from_partial_evals(evals);
+        if res.value()? != claimed.value()? {
+            bail!("the claimed value does not match the partial evaluations");
+        }
+        res.equalverify(claimed)
+    }
+
     pub fn inverse(&self, table: &TableVar) -> QM31Var {
         let cs = self.cs();
         let res = self.value().unwrap().inverse();
@@ -354,12 +369,15 @@ mod test {
     use crate::dsl::primitives::qm31::QM31Var;
     use crate::dsl::primitives::table::utils::rand_qm31;
     use crate::dsl::primitives::table::TableVar;
+    use crate::tests_utils::gadget::assert_rejects_tampered_hints;
     use crate::treepp::*;
     use bitcoin_script_dsl::bvar::AllocVar;
     use bitcoin_script_dsl::constraint_system::ConstraintSystem;
     use bitcoin_script_dsl::test_program;
+    use num_traits::One;
     use rand::SeedableRng;
     use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::fields::qm31::QM31;
 
     #[test]
     fn qm31_inverse() {
@@ -415,4 +433,54 @@ mod test {
         )
         .unwrap();
     }
+
+    #[test]
+    fn qm31_from_partial_evals() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let evals_val = [
+            rand_qm31(&mut prng),
+            rand_qm31(&mut prng),
+            rand_qm31(&mut prng),
+            rand_qm31(&mut prng),
+        ];
+        let expected = QM31::from_partial_evals(evals_val);
+
+        let cs = ConstraintSystem::new_ref();
+
+        let evals = evals_val.map(|v| QM31Var::new_hint(&cs, v).unwrap());
+        let claimed = QM31Var::new_hint(&cs, expected).unwrap();
+
+        QM31Var::verify_from_partial_evals([&evals[0], &evals[1], &evals[2], &evals[3]], &claimed)
+            .unwrap();
+
+        test_program(cs, script! {}).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+
+        let evals = evals_val.map(|v| QM31Var::new_hint(&cs, v).unwrap());
+        let wrong_claimed =
+            QM31Var::new_hint(&cs, expected + QM31::from_u32_unchecked(1, 0, 0, 0)).unwrap();
+
+        assert!(QM31Var::verify_from_partial_evals(
+            [&evals[0], &evals[1], &evals[2], &evals[3]],
+            &wrong_claimed,
+        )
+        .is_err());
+
+        let build = || {
+            let cs = ConstraintSystem::new_ref();
+            let evals = evals_val.map(|v| QM31Var::new_hint(&cs, v).unwrap());
+            let claimed = QM31Var::new_hint(&cs, expected).unwrap();
+            QM31Var::verify_from_partial_evals(
+                [&evals[0], &evals[1], &evals[2], &evals[3]],
+                &claimed,
+            )
+            .unwrap();
+            cs
+        };
+
+        let tampered = expected + QM31::one();
+        assert_rejects_tampered_hints(build, &expected.to_m31_array(), &tampered.to_m31_array());
+    }
 }
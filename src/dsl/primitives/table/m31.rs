@@ -34,9 +34,10 @@
 // - r = t
 
 use crate::dsl::primitives::table::lookup::Lookup8BitGadget;
-use crate::dsl::primitives::table::utils::{convert_m31_to_limbs, OP_256MUL};
+use crate::dsl::primitives::table::utils::{check_limb_format, convert_m31_to_limbs, OP_256MUL};
 use crate::treepp::*;
 use anyhow::{Error, Result};
+use rust_bitcoin_m31::m31_add;
 use stwo_prover::core::fields::m31::M31;
 
 pub struct M31Mult;
@@ -123,6 +124,16 @@ impl M31Mult {
         let q = (sum / ((1 << 31) - 1)) as u32;
         Ok(q)
     }
+
+    // Compute the quotient for `reduce_wide`, where the value is `(high << 16) + low`.
+    pub fn compute_q_wide(high: u32, low: u32) -> Result<u32> {
+        if high >= 1 << 24 || low >= 1 << 16 {
+            return Err(Error::msg("Unexpected overflow"));
+        }
+
+        let value = ((high as u64) << 16) + low as u64;
+        Ok((value / ((1 << 31) - 1)) as u32)
+    }
 }
 
 pub struct M31MultGadget;
@@ -252,6 +263,141 @@ impl M31MultGadget {
         }
     }
 
+    // Compute c from a, where b = a, which is the same as `compute_c_limbs` but uses only 10
+    // lookups (instead of 16), as a_i * a_j = a_j * a_i, and a_i * a_i only needs a single read of
+    // the table.
+    //
+    // Input:
+    // - table
+    // - (k elements)
+    // - a1, a2, a3, a4
+    //
+    // Output:
+    // - table
+    // - (k elements)
+    // - c4, c3, c2, c1
+    pub fn square(k: usize) -> Script {
+        // depth of a_i when there is nothing above the limbs
+        let d = |i: usize| 4 - i;
+
+        script! {
+            // c_1 = a1 * a1
+            { Self::lookup_square(d(1), k, 0) }
+            OP_TOALTSTACK
+
+            // c_2 = 2 * a1 * a2
+            { Self::lookup_product(d(1), d(2), k, 0) }
+            OP_DUP OP_ADD OP_TOALTSTACK
+
+            // c_3 = 2 * a1 * a3 + a2 * a2
+            { Self::lookup_product(d(1), d(3), k, 0) }
+            OP_DUP OP_ADD
+            { Self::lookup_square(d(2), k, 1) }
+            OP_ADD OP_TOALTSTACK
+
+            // c_4 = 2 * (a1 * a4 + a2 * a3)
+            { Self::lookup_product(d(1), d(4), k, 0) }
+            { Self::lookup_product(d(2), d(3), k, 1) }
+            OP_ADD OP_DUP OP_ADD OP_TOALTSTACK
+
+            // c_5 = 2 * a2 * a4 + a3 * a3
+            { Self::lookup_product(d(2), d(4), k, 0) }
+            OP_DUP OP_ADD
+            { Self::lookup_square(d(3), k, 1) }
+            OP_ADD OP_TOALTSTACK
+
+            // c_6 = 2 * a3 * a4
+            { Self::lookup_product(d(3), d(4), k, 0) }
+            OP_DUP OP_ADD OP_TOALTSTACK
+
+            // c_7 = a4 * a4
+            { Self::lookup_square(d(4), k, 0) }
+
+            // remove the limbs
+            OP_NIP OP_NIP OP_NIP OP_NIP
+
+            // double c_7
+            OP_DUP OP_ADD
+
+            OP_FROMALTSTACK
+
+            // double c_6
+            OP_DUP OP_ADD
+
+            OP_FROMALTSTACK
+
+            // double c_5
+            OP_DUP OP_ADD
+
+            // pull c_4
+            OP_FROMALTSTACK
+
+            // pull c_3
+            OP_FROMALTSTACK
+            4 OP_ROLL OP_ADD
+
+            // pull c_2
+            OP_FROMALTSTACK
+            4 OP_ROLL OP_ADD
+
+            // pull c_1
+            OP_FROMALTSTACK
+            4 OP_ROLL OP_ADD
+        }
+    }
+
+    // Look up the product of the limbs at the given depths, with `extra` elements above the limbs.
+    fn lookup_product(depth_a: usize, depth_b: usize, k: usize, extra: usize) -> Script {
+        script! {
+            { depth_a + extra } OP_PICK
+            { depth_b + extra + 1 } OP_PICK
+            { Lookup8BitGadget::lookup(k + 4 + extra) }
+        }
+    }
+
+    // Look up the square of the limb at the given depth, with `extra` elements above the limbs.
+    //
+    // Since the table stores `x^2 / 4`, the square of `a` is the table entry at `2a`.
+    fn lookup_square(depth: usize, k: usize, extra: usize) -> Script {
+        script! {
+            { depth + extra } OP_PICK
+            OP_DUP OP_ADD
+            { k + 4 + extra } OP_ADD
+            OP_PICK
+        }
+    }
+
+    // Multiply an M31 element by a small constant `c` through double-and-add, without the table.
+    //
+    // Input:
+    // - a
+    //
+    // Output:
+    // - a * c
+    pub fn mul_small_constant(c: u32) -> Script {
+        assert!(c < (1 << 15));
+
+        if c == 0 {
+            return script! {
+                OP_DROP 0
+            };
+        }
+
+        let n_bits = 32 - c.leading_zeros() as usize;
+
+        script! {
+            // keep a copy of a as the running result
+            OP_DUP
+            for i in (0..n_bits - 1).rev() {
+                OP_DUP m31_add
+                if (c >> i) & 1 == 1 {
+                    OP_OVER m31_add
+                }
+            }
+            OP_NIP
+        }
+    }
+
     pub fn reduce() -> Script {
         // Input:
         //   c4, c3, c2, c1
@@ -301,6 +447,46 @@ impl M31MultGadget {
             OP_DUP { (1i64 << 31) - 1 } OP_LESSTHAN OP_VERIFY
         }
     }
+
+    // Reduce a wide value `(high << 16) + low`, where high is 24-bit and low is 16-bit, using
+    // the hinted quotient q (see `M31Mult::compute_q_wide`).
+    //
+    // r = (high << 16) + low - q * ((1 << 31) - 1)
+    //   = ((high - (q << 15)) << 16) + low + q
+    //
+    // Input:
+    // - high, low
+    // - q
+    //
+    // Output:
+    // - r
+    pub fn reduce_wide() -> Script {
+        script! {
+            // save a copy of q in the altstack
+            OP_DUP OP_TOALTSTACK
+
+            // q <<= 15
+            for _ in 0..15 {
+                OP_DUP OP_ADD
+            }
+
+            // t = high - (q << 15)
+            OP_ROT OP_SWAP OP_SUB
+
+            // stack:
+            //   low, high - (q << 15)
+
+            OP_256MUL OP_256MUL
+            OP_ADD
+            OP_FROMALTSTACK OP_ADD
+
+            // enforce not negative
+            OP_DUP OP_DUP OP_ABS OP_EQUALVERIFY
+
+            // enforce smaller than the limit
+            OP_DUP { (1i64 << 31) - 1 } OP_LESSTHAN OP_VERIFY
+        }
+    }
 }
 
 pub struct M31Limbs;
@@ -401,6 +587,32 @@ impl M31LimbsGadget {
         }
     }
 
+    /// Verify that the limbs are the canonical decomposition of the m31 element, i.e., the lower
+    /// three limbs are in `[0, 256)`, the top limb is in `[0, 128)`, and they recombine into it.
+    ///
+    /// Input:
+    /// - a, a1, a2, a3, a4
+    ///
+    /// Output:
+    /// - (none)
+    pub fn assert_canonical() -> Script {
+        script! {
+            OP_DUP 0 OP_GREATERTHANOREQUAL OP_VERIFY
+            OP_DUP 128 OP_LESSTHAN OP_VERIFY
+
+            OP_256MUL OP_SWAP
+            check_limb_format OP_ADD
+
+            OP_256MUL OP_SWAP
+            check_limb_format OP_ADD
+
+            OP_256MUL OP_SWAP
+            check_limb_format OP_ADD
+
+            OP_EQUALVERIFY
+        }
+    }
+
     pub fn add_limbs_with_reduction() -> Script {
         script! {
             { Self::add_limbs() }
@@ -419,13 +631,15 @@ impl M31LimbsGadget {
 #[cfg(test)]
 mod test {
     use crate::dsl::primitives::table::get_table;
-    use crate::dsl::primitives::table::m31::{M31Limbs, M31Mult, M31MultGadget};
+    use crate::dsl::primitives::table::m31::{M31Limbs, M31LimbsGadget, M31Mult, M31MultGadget};
     use crate::dsl::primitives::table::utils::{convert_m31_to_limbs, rand_m31};
+    use crate::tests_utils::report::report_bitcoin_script_size;
     use crate::treepp::*;
     use bitcoin_script::script;
     use bitcoin_scriptexec::execute_script;
-    use rand::SeedableRng;
+    use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::fields::m31::M31;
 
     #[test]
     fn test_hypothesis() {
@@ -496,6 +710,98 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_square() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let table = get_table();
+
+        report_bitcoin_script_size(
+            "M31Mult",
+            "compute_c_limbs",
+            M31MultGadget::compute_c_limbs(0).len(),
+        );
+        report_bitcoin_script_size("M31Mult", "square", M31MultGadget::square(0).len());
+
+        for i in 0..20 {
+            let a = rand_m31(&mut prng);
+            let a_limbs = convert_m31_to_limbs(a);
+
+            let c_limbs = M31Mult::compute_c_limbs(a, a).unwrap();
+            let q = M31Mult::compute_q(&c_limbs).unwrap();
+            let r = a * a;
+
+            let script = script! {
+                { table }
+                for _ in 0..i {
+                    { 1 }
+                }
+                { a_limbs.to_vec() }
+                { M31MultGadget::square(i) }
+                for c_limb in c_limbs.iter() {
+                    { *c_limb }
+                    OP_EQUALVERIFY
+                }
+                for _ in 0..i {
+                    OP_DROP
+                }
+                for _ in 0..256 {
+                    OP_2DROP
+                }
+                OP_DROP
+                OP_TRUE
+            };
+
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                { table }
+                { a_limbs.to_vec() }
+                { M31MultGadget::square(0) }
+                { q }
+                { M31MultGadget::reduce() }
+                { r.0 }
+                OP_EQUALVERIFY
+                for _ in 0..256 {
+                    OP_2DROP
+                }
+                OP_DROP
+                OP_TRUE
+            };
+
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_mul_small_constant() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for c in [0u32, 1, 2, 3, 1000, 32767] {
+            report_bitcoin_script_size(
+                "M31Mult",
+                &format!("mul_small_constant({})", c),
+                M31MultGadget::mul_small_constant(c).len(),
+            );
+
+            for a in [rand_m31(&mut prng), M31::from(0), -M31::from(1)] {
+                let expected = a * M31::from(c);
+
+                let script = script! {
+                    { a.0 }
+                    { M31MultGadget::mul_small_constant(c) }
+                    { expected.0 }
+                    OP_EQUAL
+                };
+
+                let exec_result = execute_script(script);
+                assert!(exec_result.success);
+            }
+        }
+    }
+
     #[test]
     fn test_reduce() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -523,6 +829,50 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_reduce_wide() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        report_bitcoin_script_size("M31Mult", "reduce_wide", M31MultGadget::reduce_wide().len());
+
+        let mut cases = vec![(0, 0), ((1 << 24) - 1, (1 << 16) - 1), (1 << 15, 0)];
+        for _ in 0..100 {
+            cases.push((prng.gen_range(0..1 << 24), prng.gen_range(0..1 << 16)));
+        }
+
+        for (high, low) in cases {
+            let q = M31Mult::compute_q_wide(high, low).unwrap();
+            let r = ((((high as u64) << 16) + low as u64) % ((1 << 31) - 1)) as u32;
+
+            let script = script! {
+                { high }
+                { low }
+                { q }
+                { M31MultGadget::reduce_wide() }
+                { r }
+                OP_EQUAL
+            };
+
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            // a wrong quotient is rejected
+            let script = script! {
+                { high }
+                { low }
+                { q + 1 }
+                { M31MultGadget::reduce_wide() }
+                OP_DROP
+                OP_TRUE
+            };
+
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+
+        assert!(M31Mult::compute_q_wide(1 << 24, 0).is_err());
+    }
+
     #[test]
     fn test_add_limbs() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -566,4 +916,43 @@ mod test {
         let exec_result = execute_script(script);
         assert!(exec_result.success);
     }
+
+    #[test]
+    fn test_assert_canonical() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let a = rand_m31(&mut prng);
+            let a_limbs = convert_m31_to_limbs(a);
+
+            let script = script! {
+                { a }
+                { a_limbs.to_vec() }
+                { M31LimbsGadget::assert_canonical() }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            // move 1 from the second limb into the first limb, which recombines into the same
+            // element but leaves the first limb at least 256
+            let mut a = a;
+            let mut a_limbs = a_limbs;
+            if a_limbs[1] == 0 {
+                a_limbs[1] = 1;
+                a += M31::from(1 << 8);
+            }
+            a_limbs[0] += 256;
+            a_limbs[1] -= 1;
+
+            let script = script! {
+                { a }
+                { a_limbs.to_vec() }
+                { M31LimbsGadget::assert_canonical() }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
 }
@@ -0,0 +1,897 @@
+use crate::algorithms::column_line_coeffs::column_line_coeffs;
+use crate::algorithms::composition::assert_composition_matches;
+use crate::algorithms::folding::{
+    decompose_positions, fold_step, skip_one_and_extract_bits, FoldConfig,
+};
+use crate::algorithms::pair_vanishing::prepare_pair_vanishing_batch;
+use crate::algorithms::point::{
+    add_constant_m31_point, coset_vanishing, get_oods_point, SecureCirclePointVar,
+};
+use crate::algorithms::pow::verify_pow;
+use crate::algorithms::precomputed_tree::compute_query_point_and_twiddles;
+use crate::algorithms::quotient::{
+    aggregate_numerators, apply_twin, coeff_powers, denominator_inverses_batched,
+};
+use crate::algorithms::twin_tree::query_and_verify_merkle_twin_tree;
+use crate::dsl::primitives::channel::HashVarWithChannel;
+use crate::dsl::primitives::cm31::CM31Var;
+use crate::dsl::primitives::m31::M31Var;
+use crate::dsl::primitives::qm31::QM31Var;
+use crate::dsl::primitives::table::TableVar;
+use crate::dsl::verifier::CompiledVerifier;
+use crate::fri::num_fri_layers;
+use crate::merkle_tree::MerkleTreeTwinProof;
+use anyhow::{anyhow, bail, Result};
+use bitcoin_script_dsl::builtins::hash::HashVar;
+use bitcoin_script_dsl::builtins::str::StrVar;
+use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+use bitcoin_script_dsl::constraint_system::{ConstraintSystem, ConstraintSystemRef};
+use bitcoin_script_dsl::ldm::LDM;
+use num_traits::Zero;
+use std::collections::BTreeSet;
+use stwo_prover::core::channel::Sha256Channel;
+use stwo_prover::core::fields::cm31::CM31;
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::fields::secure_column::SECURE_EXTENSION_DEGREE;
+use stwo_prover::core::poly::circle::CanonicCoset;
+use stwo_prover::core::prover::{LOG_BLOWUP_FACTOR, LOG_LAST_LAYER_DEGREE_BOUND};
+use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+
+/// The mask of a column, i.e., the offsets of the points at which the column is sampled, in steps
+/// of the trace domain from the OODS point.
+pub type ColumnMask = Vec<isize>;
+
+/// The values that the composition closure evaluates the constraints on.
+pub struct CompositionInputs {
+    /// The elements drawn from the channel right after the first tree is committed, e.g., the
+    /// lookup elements.
+    pub draws: Vec<QM31Var>,
+    /// The OODS values, indexed by the tree, the column, and the mask entry.
+    pub oods_values: Vec<Vec<Vec<QM31Var>>>,
+    /// The AIR-specific values that are sent along with the proof, e.g., a LogUp claimed sum.
+    pub extra: Vec<QM31Var>,
+}
+
+/// Evaluate the constraints of an AIR at the OODS point, before they are divided by the vanishing
+/// polynomial of the trace domain.
+///
+/// The constraints are folded by the composition random coefficient in the order they are
+/// returned, the first one being multiplied by the highest power.
+pub type CompositionFn = Box<dyn Fn(&TableVar, &CompositionInputs) -> Result<Vec<QM31Var>>>;
+
+/// The hints of the generic verifier, which are read from the proof.
+///
+/// Every list indexed by tree ends with the composition tree.
+pub struct VerifierHints {
+    /// The commitments of the trees.
+    pub commitments: Vec<Sha256Hash>,
+    /// The OODS values of each tree, ordered by column and then by mask entry.
+    pub oods_values: Vec<Vec<QM31>>,
+    /// The AIR-specific values that are sent along with the proof.
+    pub extra: Vec<QM31>,
+    /// The commitments of the FRI inner layers.
+    pub fri_layer_commitments: Vec<Sha256Hash>,
+    /// The coefficients of the FRI last layer.
+    pub last_layer: Vec<QM31>,
+    /// The proof-of-work nonce.
+    pub pow_nonce: u64,
+    /// The Merkle proofs of each tree, one for each query.
+    pub merkle_proofs: Vec<Vec<MerkleTreeTwinProof>>,
+    /// The Merkle proofs of the FRI inner layers for each query, one for each layer.
+    pub fri_twin_proofs: Vec<Vec<MerkleTreeTwinProof>>,
+}
+
+/// A verifier assembled from the description of an AIR, rather than from hand-written parts.
+///
+/// The AIR is described by the log sizes of its columns, the mask of each column, and a closure
+/// evaluating its constraints. The Fiat-Shamir, composition, prepare, column line coefficients,
+/// folding, and quotient programs are generated from this description, so a new AIR does not
+/// need its own parts.
+///
+/// All the columns must have the same log size, and the constraints must have a degree of at most
+/// two, so that the composition polynomial has twice the degree bound of the columns.
+pub struct GenericVerifier {
+    /// The log sizes of the columns of each tree, excluding the composition tree.
+    pub column_log_sizes: Vec<Vec<u32>>,
+    /// The mask of each column of each tree, excluding the composition tree.
+    pub mask: Vec<Vec<ColumnMask>>,
+    /// The number of elements drawn from the channel right after the first tree is committed.
+    pub n_interaction_draws: usize,
+    /// The trees whose commitments are public, e.g., the constant columns, with their roots.
+    pub pinned_commitments: Vec<(usize, Sha256Hash)>,
+    /// Evaluate the constraints at the OODS point.
+    pub composition: CompositionFn,
+    /// The number of queries.
+    pub n_queries: usize,
+    /// The number of bits of proof of work.
+    pub pow_bits: u32,
+}
+
+/// The run of mask entries of one tree that are sampled at the same point.
+struct Piece {
+    tree: usize,
+    point: usize,
+    /// The (column, mask entry) pairs.
+    entries: Vec<(usize, usize)>,
+    /// The power of the line batch random coefficient that the aggregated numerators of the piece
+    /// are multiplied with.
+    exponent: usize,
+}
+
+/// The layout of the quotients, which follows the sample batches of stwo.
+struct Layout {
+    log_size: u32,
+    /// The masks of all the trees, including the composition tree.
+    masks: Vec<Vec<ColumnMask>>,
+    /// The offsets of the sample points in the order they first appear.
+    sample_offsets: Vec<isize>,
+    pieces: Vec<Piece>,
+    /// The powers of the line batch random coefficient that the per-query parts need.
+    alpha_powers: BTreeSet<usize>,
+    n_fri_layers: usize,
+    query_log_size: usize,
+}
+
+impl Layout {
+    fn n_trees(&self) -> usize {
+        self.masks.len()
+    }
+
+    fn pieces_of_tree(&self, tree: usize) -> impl Iterator<Item = &Piece> {
+        self.pieces.iter().filter(move |piece| piece.tree == tree)
+    }
+}
+
+impl GenericVerifier {
+    /// The number of programs that run once before the queries.
+    pub fn num_prologue_programs(&self) -> usize {
+        // Fiat-Shamir, constraints, composition, and prepare, followed by the column line
+        // coefficients of each tree including the composition tree
+        4 + self.mask.len() + 1
+    }
+
+    /// The number of programs that run for each query.
+    pub fn num_per_query_programs(&self) -> usize {
+        // folding, followed by the numerators of each tree and the last step
+        1 + self.mask.len() + 1 + 1
+    }
+
+    /// The total number of programs.
+    pub fn num_programs(&self) -> usize {
+        self.num_prologue_programs() + self.num_per_query_programs() * self.n_queries + 1
+    }
+
+    /// Compile all the programs of the verifier with the given hints.
+    pub fn compile(&self, hints: &VerifierHints) -> Result<CompiledVerifier> {
+        let layout = self.layout()?;
+        let mut ldm = LDM::new();
+
+        let mut compiled = CompiledVerifier {
+            scripts: vec![],
+            witnesses: vec![],
+            outputs: vec![],
+            n_prologue: self.num_prologue_programs(),
+            n_per_query: self.num_per_query_programs(),
+        };
+
+        let cs = self.fiat_shamir(&layout, hints, &mut ldm)?;
+        compiled.push(cs, &ldm)?;
+        let cs = self.constraints(hints, &mut ldm)?;
+        compiled.push(cs, &ldm)?;
+        let cs = composition(&layout, &mut ldm)?;
+        compiled.push(cs, &ldm)?;
+        let cs = prepare(&layout, &mut ldm)?;
+        compiled.push(cs, &ldm)?;
+        for tree in 0..layout.n_trees() {
+            let cs = column_line_coeffs_of_tree(&layout, &mut ldm, tree)?;
+            compiled.push(cs, &ldm)?;
+        }
+
+        for query_idx in 0..self.n_queries {
+            let cs = folding(&layout, hints, &mut ldm, query_idx)?;
+            compiled.push(cs, &ldm)?;
+            for tree in 0..layout.n_trees() {
+                let cs = numerators_of_tree(&layout, hints, &mut ldm, query_idx, tree)?;
+                compiled.push(cs, &ldm)?;
+            }
+            let cs = last_step(&layout, &mut ldm, query_idx)?;
+            compiled.push(cs, &ldm)?;
+        }
+
+        let cs = ConstraintSystem::new_ref();
+        ldm.init(&cs)?;
+        ldm.check()?;
+        ldm.save()?;
+        compiled.push(cs, &ldm)?;
+
+        assert_eq!(compiled.scripts.len(), self.num_programs());
+        Ok(compiled)
+    }
+
+    /// Check the description of the AIR and compute the layout of the quotients.
+    fn layout(&self) -> Result<Layout> {
+        if self.column_log_sizes.len() != self.mask.len() {
+            bail!("the column log sizes and the mask have a different number of trees");
+        }
+
+        let mut log_sizes = BTreeSet::new();
+        for (sizes, masks) in self.column_log_sizes.iter().zip(self.mask.iter()) {
+            if sizes.len() != masks.len() {
+                bail!("the column log sizes and the mask have a different number of columns");
+            }
+            if masks.iter().any(|mask| mask.is_empty()) {
+                bail!("every column must be sampled at least once");
+            }
+            log_sizes.extend(sizes.iter().copied());
+        }
+        if log_sizes.len() != 1 {
+            bail!("all the columns must have the same log size");
+        }
+        let log_size = log_sizes.pop_first().unwrap();
+
+        let mut masks = self.mask.clone();
+        masks.push(vec![vec![0]; SECURE_EXTENSION_DEGREE]);
+
+        // group the mask entries into batches by their sample point, in the order in which the
+        // points first appear
+        let mut sample_offsets = vec![];
+        let mut batches: Vec<Vec<(usize, usize, usize)>> = vec![];
+        for (tree, tree_masks) in masks.iter().enumerate() {
+            for (column, column_mask) in tree_masks.iter().enumerate() {
+                for (entry, offset) in column_mask.iter().enumerate() {
+                    let point = match sample_offsets.iter().position(|v| v == offset) {
+                        Some(point) => point,
+                        None => {
+                            sample_offsets.push(*offset);
+                            batches.push(vec![]);
+                            sample_offsets.len() - 1
+                        }
+                    };
+                    batches[point].push((tree, column, entry));
+                }
+            }
+        }
+
+        // the k-th of the n entries of a batch is multiplied by alpha^(n - 1 - k), and each batch
+        // by alpha to the number of entries in the batches after it, so a run of a tree ending
+        // before the k-th entry is multiplied by alpha^(later + n - k) after being aggregated
+        let mut pieces = vec![];
+        let mut later = batches.iter().map(|batch| batch.len()).sum::<usize>();
+        for (point, batch) in batches.iter().enumerate() {
+            later -= batch.len();
+
+            let mut start = 0;
+            while start < batch.len() {
+                let tree = batch[start].0;
+                let mut end = start;
+                while end < batch.len() && batch[end].0 == tree {
+                    end += 1;
+                }
+
+                pieces.push(Piece {
+                    tree,
+                    point,
+                    entries: batch[start..end]
+                        .iter()
+                        .map(|&(_, column, entry)| (column, entry))
+                        .collect(),
+                    exponent: later + batch.len() - end,
+                });
+                start = end;
+            }
+        }
+
+        let mut alpha_powers = BTreeSet::new();
+        for piece in pieces.iter() {
+            alpha_powers.extend(1..piece.entries.len());
+            if piece.exponent > 0 {
+                alpha_powers.insert(piece.exponent);
+            }
+        }
+
+        let n_fri_layers = num_fri_layers(log_size + 1, LOG_LAST_LAYER_DEGREE_BOUND)
+            .ok_or_else(|| anyhow!("the columns are too small for the FRI last layer"))?;
+        if n_fri_layers == 0 {
+            bail!("the columns are too small to be folded");
+        }
+
+        Ok(Layout {
+            log_size,
+            masks,
+            sample_offsets,
+            pieces,
+            alpha_powers,
+            n_fri_layers,
+            query_log_size: (log_size + LOG_BLOWUP_FACTOR + 1) as usize,
+        })
+    }
+
+    /// Replay the channel, check the proof of work, and draw the queries.
+    fn fiat_shamir(
+        &self,
+        layout: &Layout,
+        hints: &VerifierHints,
+        ldm: &mut LDM,
+    ) -> Result<ConstraintSystemRef> {
+        let cs = ConstraintSystem::new_ref();
+        ldm.init(&cs)?;
+
+        let n_trees = layout.n_trees();
+        if hints.commitments.len() != n_trees || hints.oods_values.len() != n_trees {
+            bail!("the hints do not have {} trees", n_trees);
+        }
+        if hints.merkle_proofs.len() != n_trees
+            || hints
+                .merkle_proofs
+                .iter()
+                .any(|proofs| proofs.len() != self.n_queries)
+            || hints.fri_twin_proofs.len() != self.n_queries
+        {
+            bail!(
+                "the hints do not have the proofs of {} queries",
+                self.n_queries
+            );
+        }
+
+        let mut commitment_vars = vec![];
+        for (tree, commitment) in hints.commitments.iter().enumerate() {
+            let commitment_var = HashVar::new_hint(&cs, commitment.as_ref().to_vec())?;
+            ldm.write(format!("commitment_{}", tree), &commitment_var)?;
+            commitment_vars.push(commitment_var);
+        }
+
+        // the public commitments are fixed by the AIR
+        for (tree, root) in self.pinned_commitments.iter() {
+            if hints.commitments[*tree] != *root {
+                bail!(
+                    "the commitment of tree {} does not match its public root",
+                    tree
+                );
+            }
+            let root_var = HashVar::new_constant(&cs, root.as_ref().to_vec())?;
+            StrVar::from(&commitment_vars[*tree]).equalverify(&StrVar::from(&root_var))?;
+        }
+
+        let channel = Sha256Channel::default();
+        let mut channel_var = HashVar::new_constant(&cs, channel.digest().as_ref().to_vec())?;
+
+        channel_var = &channel_var + &commitment_vars[0];
+        for i in 0..self.n_interaction_draws {
+            let draw_var = channel_var.draw_felt();
+            ldm.write(format!("draw_{}", i), &draw_var)?;
+        }
+
+        for commitment_var in commitment_vars[1..n_trees - 1].iter() {
+            channel_var = &channel_var + commitment_var;
+        }
+
+        let composition_fold_random_coeff_var = channel_var.draw_felt();
+        ldm.write(
+            "composition_fold_random_coeff",
+            &composition_fold_random_coeff_var,
+        )?;
+
+        channel_var = &channel_var + &commitment_vars[n_trees - 1];
+
+        // save a copy of the channel before drawing the OODS point
+        let mut channel_var_before_oods = channel_var.clone();
+        let _ = channel_var.draw_felt();
+
+        for (tree, (tree_masks, values)) in layout
+            .masks
+            .iter()
+            .zip(hints.oods_values.iter())
+            .enumerate()
+        {
+            let n_values = tree_masks.iter().map(|mask| mask.len()).sum::<usize>();
+            if values.len() != n_values {
+                bail!(
+                    "tree {} has {} OODS values, expected {}",
+                    tree,
+                    values.len(),
+                    n_values
+                );
+            }
+
+            let mut values = values.iter();
+            for (column, column_mask) in tree_masks.iter().enumerate() {
+                for entry in 0..column_mask.len() {
+                    let value_var = QM31Var::new_hint(&cs, *values.next().unwrap())?;
+                    channel_var = &channel_var + &value_var;
+                    ldm.write(
+                        format!("oods_value_{}_{}_{}", tree, column, entry),
+                        &value_var,
+                    )?;
+                }
+            }
+        }
+
+        let line_batch_random_coeff_var = channel_var.draw_felt();
+        ldm.write("line_batch_random_coeff", &line_batch_random_coeff_var)?;
+        let fri_fold_random_coeff_var = channel_var.draw_felt();
+        ldm.write("fri_fold_random_coeff", &fri_fold_random_coeff_var)?;
+
+        if hints.fri_layer_commitments.len() != layout.n_fri_layers {
+            bail!("the hints do not have {} FRI layers", layout.n_fri_layers);
+        }
+        for (i, fri_tree_commitment) in hints.fri_layer_commitments.iter().enumerate() {
+            let fri_tree_commitment_var =
+                HashVar::new_hint(&cs, fri_tree_commitment.as_ref().to_vec())?;
+            ldm.write(
+                format!("fri_tree_commitments_{}", i),
+                &fri_tree_commitment_var,
+            )?;
+
+            channel_var = &channel_var + &fri_tree_commitment_var;
+
+            let folding_alpha_var = channel_var.draw_felt();
+            ldm.write(format!("folding_alpha_{}", i), &folding_alpha_var)?;
+        }
+
+        // the per-query folding checks against a constant last layer
+        if hints.last_layer.len() != 1 {
+            bail!("the FRI last layer must be a constant");
+        }
+        let last_layer_var = QM31Var::new_hint(&cs, hints.last_layer[0])?;
+        ldm.write("last_layer", &last_layer_var)?;
+        channel_var = &channel_var + &last_layer_var;
+
+        verify_pow(&mut channel_var, self.pow_bits, hints.pow_nonce)?;
+
+        let queries = channel_var.draw_numbers(self.n_queries, layout.query_log_size);
+        for (i, query) in queries.iter().enumerate() {
+            ldm.write(format!("query_{}", i), query)?;
+        }
+
+        let table = TableVar::new_constant(&cs, ())?;
+        let point = get_oods_point(&mut channel_var_before_oods, &table);
+        ldm.write("oods_x", &point.x)?;
+        ldm.write("oods_y", &point.y)?;
+
+        ldm.save()?;
+        Ok(cs)
+    }
+
+    /// Evaluate the constraints at the OODS point and fold them into the constraint numerator.
+    fn constraints(&self, hints: &VerifierHints, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
+        let cs = ConstraintSystem::new_ref();
+        ldm.init(&cs)?;
+
+        let table = TableVar::new_constant(&cs, ())?;
+
+        let mut draws = vec![];
+        for i in 0..self.n_interaction_draws {
+            draws.push(ldm.read(format!("draw_{}", i))?);
+        }
+
+        let mut oods_values = vec![];
+        for (tree, tree_masks) in self.mask.iter().enumerate() {
+            let mut tree_values = vec![];
+            for (column, column_mask) in tree_masks.iter().enumerate() {
+                let mut column_values = vec![];
+                for entry in 0..column_mask.len() {
+                    column_values
+                        .push(ldm.read(format!("oods_value_{}_{}_{}", tree, column, entry))?);
+                }
+                tree_values.push(column_values);
+            }
+            oods_values.push(tree_values);
+        }
+
+        let mut extra = vec![];
+        for &value in hints.extra.iter() {
+            extra.push(QM31Var::new_hint(&cs, value)?);
+        }
+
+        let inputs = CompositionInputs {
+            draws,
+            oods_values,
+            extra,
+        };
+        let constraints = (self.composition)(&table, &inputs)?;
+        if constraints.is_empty() {
+            bail!("the AIR has no constraints");
+        }
+
+        let composition_fold_random_coeff: QM31Var = ldm.read("composition_fold_random_coeff")?;
+
+        let mut constraint_num = constraints[0].clone();
+        for constraint in constraints[1..].iter() {
+            constraint_num =
+                &(&constraint_num * (&table, &composition_fold_random_coeff)) + constraint;
+        }
+        ldm.write("constraint_num", &constraint_num)?;
+
+        ldm.save()?;
+        Ok(cs)
+    }
+}
+
+/// Check the composition values against the constraint numerator, and derive the sample points
+/// from the OODS point.
+fn composition(layout: &Layout, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
+    let cs = ConstraintSystem::new_ref();
+    ldm.init(&cs)?;
+
+    let oods_point = SecureCirclePointVar {
+        x: ldm.read("oods_x")?,
+        y: ldm.read("oods_y")?,
+    };
+
+    let table = TableVar::new_constant(&cs, ())?;
+
+    // the inverse also asserts that the OODS point is not in the trace domain
+    let constraint_denom = coset_vanishing(&oods_point, &table, layout.log_size).inverse(&table);
+
+    let constraint_num: QM31Var = ldm.read("constraint_num")?;
+    let computed_composition = &constraint_denom * (&table, &constraint_num);
+
+    let composition_tree = layout.n_trees() - 1;
+    let mut composition_vars = Vec::<QM31Var>::new();
+    for column in 0..SECURE_EXTENSION_DEGREE {
+        composition_vars.push(ldm.read(format!("oods_value_{}_{}_0", composition_tree, column))?);
+    }
+
+    assert_composition_matches(
+        &computed_composition,
+        [
+            &composition_vars[0],
+            &composition_vars[1],
+            &composition_vars[2],
+            &composition_vars[3],
+        ],
+    )?;
+
+    let trace_step = CanonicCoset::new(layout.log_size).step();
+    for (point, &offset) in layout.sample_offsets.iter().enumerate() {
+        let sample_point = if offset == 0 {
+            SecureCirclePointVar {
+                x: oods_point.x.clone(),
+                y: oods_point.y.clone(),
+            }
+        } else {
+            add_constant_m31_point(&oods_point, &table, trace_step.mul_signed(offset))
+        };
+        ldm.write(format!("sample_point_{}_x", point), &sample_point.x)?;
+        ldm.write(format!("sample_point_{}_y", point), &sample_point.y)?;
+    }
+
+    ldm.save()?;
+    Ok(cs)
+}
+
+/// Prepare the pair vanishing polynomials of the sample points, and the powers of the line batch
+/// random coefficient.
+fn prepare(layout: &Layout, ldm: &mut LDM) -> Result<ConstraintSystemRef> {
+    let cs = ConstraintSystem::new_ref();
+    ldm.init(&cs)?;
+
+    let table = TableVar::new_constant(&cs, ())?;
+
+    let mut sample_points = vec![];
+    for point in 0..layout.sample_offsets.len() {
+        sample_points.push(SecureCirclePointVar {
+            x: ldm.read(format!("sample_point_{}_x", point))?,
+            y: ldm.read(format!("sample_point_{}_y", point))?,
+        });
+    }
+
+    let prepared = prepare_pair_vanishing_batch(&sample_points, &table);
+    for (point, (a, b)) in prepared.iter().enumerate() {
+        ldm.write(format!("prepared_{}_a", point), a)?;
+        ldm.write(format!("prepared_{}_b", point), b)?;
+    }
+
+    let alpha: QM31Var = ldm.read("line_batch_random_coeff")?;
+    if let Some(&max_power) = layout.alpha_powers.last() {
+        let powers = coeff_powers(&table, &alpha, max_power);
+        for &power in layout.alpha_powers.iter() {
+            ldm.write(
+                format!("line_batch_random_coeff_{}", power),
+                &powers[power - 1],
+            )?;
+        }
+    }
+
+    ldm.save()?;
+    Ok(cs)
+}
+
+/// Compute the column line coefficients of the mask entries of a tree.
+fn column_line_coeffs_of_tree(
+    layout: &Layout,
+    ldm: &mut LDM,
+    tree: usize,
+) -> Result<ConstraintSystemRef> {
+    let cs = ConstraintSystem::new_ref();
+    ldm.init(&cs)?;
+
+    let table = TableVar::new_constant(&cs, ())?;
+
+    for piece in layout.pieces_of_tree(tree) {
+        let y: QM31Var = ldm.read(format!("sample_point_{}_y", piece.point))?;
+
+        let mut values = Vec::<QM31Var>::new();
+        for &(column, entry) in piece.entries.iter() {
+            values.push(ldm.read(format!("oods_value_{}_{}_{}", tree, column, entry))?);
+        }
+
+        let res = column_line_coeffs(&table, &y, &values)?;
+        for (&(column, entry), (a, b)) in piece.entries.iter().zip(res.iter()) {
+            ldm.write(
+                format!("column_line_coeffs_{}_{}_{}_a", tree, column, entry),
+                a,
+            )?;
+            ldm.write(
+                format!("column_line_coeffs_{}_{}_{}_b", tree, column, entry),
+                b,
+            )?;
+        }
+    }
+
+    ldm.save()?;
+    Ok(cs)
+}
+
+/// Query the FRI layers and fold them down to the last layer, and compute the circle point of the
+/// query.
+fn folding(
+    layout: &Layout,
+    hints: &VerifierHints,
+    ldm: &mut LDM,
+    query_idx: usize,
+) -> Result<ConstraintSystemRef> {
+    let cs = ConstraintSystem::new_ref();
+    ldm.init(&cs)?;
+
+    let n_layers = layout.n_fri_layers;
+
+    let query: M31Var = ldm.read(format!("query_{}", query_idx))?;
+    let queries = decompose_positions(&query, n_layers);
+
+    let proofs = &hints.fri_twin_proofs[query_idx];
+    if proofs.len() != n_layers {
+        bail!("query {} does not have {} FRI proofs", query_idx, n_layers);
+    }
+
+    let mut folding_intermediate_vars = vec![];
+    for (i, (proof, cur_query)) in proofs.iter().zip(queries.iter()).enumerate() {
+        let commitment: HashVar = ldm.read(format!("fri_tree_commitments_{}", i))?;
+        let res = query_and_verify_merkle_twin_tree(&commitment, cur_query, proof)?;
+        folding_intermediate_vars.push((qm31_from_limbs(&res.0)?, qm31_from_limbs(&res.1)?));
+    }
+
+    let swap_bits_vars = skip_one_and_extract_bits(&query, n_layers);
+
+    let point = compute_query_point_and_twiddles(&query, layout.query_log_size);
+    ldm.write(
+        format!("circle_point_x_{}", query_idx),
+        &point.circle_point_x_var,
+    )?;
+    ldm.write(
+        format!("circle_point_y_{}", query_idx),
+        &point.circle_point_y_var,
+    )?;
+
+    let table = TableVar::new_constant(&cs, ())?;
+    let fold_config = FoldConfig::default();
+
+    let mut folded_results_vars = vec![];
+    for (i, folding_intermediate_result) in folding_intermediate_vars.iter().enumerate() {
+        let folding_alpha_var: QM31Var = ldm.read(format!("folding_alpha_{}", i))?;
+        let twiddle_var = point.twiddles_var[layout.query_log_size - 2 - i].clone();
+        folded_results_vars.push(fold_step(
+            &table,
+            &fold_config,
+            &[
+                folding_intermediate_result.0.clone(),
+                folding_intermediate_result.1.clone(),
+            ],
+            &[vec![twiddle_var]],
+            &folding_alpha_var,
+        ));
+    }
+
+    for i in 0..n_layers - 1 {
+        let swapped_result = folding_intermediate_vars[i + 1]
+            .0
+            .conditional_swap(&folding_intermediate_vars[i + 1].1, &swap_bits_vars[i + 1])
+            .0;
+        swapped_result.equalverify(&folded_results_vars[i])?;
+    }
+
+    let expected_entry_quotient = folding_intermediate_vars[0]
+        .0
+        .conditional_swap(&folding_intermediate_vars[0].1, &swap_bits_vars[0])
+        .0;
+    ldm.write(
+        format!("expected_entry_quotient_{}", query_idx),
+        &expected_entry_quotient,
+    )?;
+
+    let last_layer_var: QM31Var = ldm.read("last_layer")?;
+    folded_results_vars[n_layers - 1].equalverify(&last_layer_var)?;
+
+    ldm.save()?;
+    Ok(cs)
+}
+
+/// Query a tree and compute the quotient numerators of its mask entries, aggregated for each
+/// sample point.
+fn numerators_of_tree(
+    layout: &Layout,
+    hints: &VerifierHints,
+    ldm: &mut LDM,
+    query_idx: usize,
+    tree: usize,
+) -> Result<ConstraintSystemRef> {
+    let cs = ConstraintSystem::new_ref();
+    ldm.init(&cs)?;
+
+    let query: M31Var = ldm.read(format!("query_{}", query_idx))?;
+    let commitment: HashVar = ldm.read(format!("commitment_{}", tree))?;
+    let (left, right) = query_and_verify_merkle_twin_tree(
+        &commitment,
+        &query,
+        &hints.merkle_proofs[tree][query_idx],
+    )?;
+    if left.len() != layout.masks[tree].len() {
+        bail!(
+            "the leaves of tree {} do not have {} columns",
+            tree,
+            layout.masks[tree].len()
+        );
+    }
+
+    let y: M31Var = ldm.read(format!("circle_point_y_{}", query_idx))?;
+    let table = TableVar::new_constant(&cs, ())?;
+
+    for piece in layout.pieces_of_tree(tree) {
+        let mut numerators_l = vec![];
+        let mut numerators_r = vec![];
+        for &(column, entry) in piece.entries.iter() {
+            let a: CM31Var = ldm.read(format!(
+                "column_line_coeffs_{}_{}_{}_a",
+                tree, column, entry
+            ))?;
+            let b: CM31Var = ldm.read(format!(
+                "column_line_coeffs_{}_{}_{}_b",
+                tree, column, entry
+            ))?;
+            let numerator = apply_twin(&table, &y, &left[column], &right[column], &a, &b);
+            numerators_l.push(numerator.0);
+            numerators_r.push(numerator.1);
+        }
+
+        let mut powers = Vec::<QM31Var>::new();
+        for power in 1..piece.entries.len() {
+            powers.push(ldm.read(format!("line_batch_random_coeff_{}", power))?);
+        }
+
+        let mut sum_l = aggregate(&table, &powers, &numerators_l)?;
+        let mut sum_r = aggregate(&table, &powers, &numerators_r)?;
+
+        if piece.exponent > 0 {
+            let alpha_power: QM31Var =
+                ldm.read(format!("line_batch_random_coeff_{}", piece.exponent))?;
+            sum_l = &alpha_power * (&table, &sum_l);
+            sum_r = &alpha_power * (&table, &sum_r);
+        }
+
+        ldm.write(
+            format!("quotient_num_{}_{}_{}_l", query_idx, tree, piece.point),
+            &sum_l,
+        )?;
+        ldm.write(
+            format!("quotient_num_{}_{}_{}_r", query_idx, tree, piece.point),
+            &sum_r,
+        )?;
+    }
+
+    ldm.save()?;
+    Ok(cs)
+}
+
+/// Divide the numerators by the denominators of their sample points, and check that the quotient
+/// folds into the entry of the first FRI layer.
+fn last_step(layout: &Layout, ldm: &mut LDM, query_idx: usize) -> Result<ConstraintSystemRef> {
+    let cs = ConstraintSystem::new_ref();
+    ldm.init(&cs)?;
+
+    let x: M31Var = ldm.read(format!("circle_point_x_{}", query_idx))?;
+    let y: M31Var = ldm.read(format!("circle_point_y_{}", query_idx))?;
+    let table = TableVar::new_constant(&cs, ())?;
+
+    let mut prepared = vec![];
+    for point in 0..layout.sample_offsets.len() {
+        prepared.push((
+            ldm.read(format!("prepared_{}_a", point))?,
+            ldm.read(format!("prepared_{}_b", point))?,
+        ));
+    }
+    let denominator_inverses = denominator_inverses_batched(&table, &prepared, &x, &y);
+
+    let mut quotient: Option<(QM31Var, QM31Var)> = None;
+    for (point, denominator_inverse) in denominator_inverses.iter().enumerate() {
+        let mut numerator: Option<(QM31Var, QM31Var)> = None;
+        for piece in layout.pieces.iter().filter(|piece| piece.point == point) {
+            let l: QM31Var = ldm.read(format!(
+                "quotient_num_{}_{}_{}_l",
+                query_idx, piece.tree, point
+            ))?;
+            let r: QM31Var = ldm.read(format!(
+                "quotient_num_{}_{}_{}_r",
+                query_idx, piece.tree, point
+            ))?;
+            numerator = Some(match numerator {
+                Some((sum_l, sum_r)) => (&sum_l + &l, &sum_r + &r),
+                None => (l, r),
+            });
+        }
+        let numerator = numerator.unwrap();
+
+        let term_l = &numerator.0 * (&table, &denominator_inverse.0);
+        let term_r = &numerator.1 * (&table, &denominator_inverse.1);
+        quotient = Some(match quotient {
+            Some((sum_l, sum_r)) => (&sum_l + &term_l, &sum_r + &term_r),
+            None => (term_l, term_r),
+        });
+    }
+    let (quotient_l, quotient_r) = quotient.unwrap();
+
+    let y_inv = y.inverse(&table);
+
+    let ifft_results_vars = {
+        let new_v0 = &quotient_l + &quotient_r;
+        let diff = &quotient_l - &quotient_r;
+        let new_v1 = &diff * (&table, &y_inv);
+        (new_v0, new_v1)
+    };
+
+    let fri_fold_random_coeff_var: QM31Var = ldm.read("fri_fold_random_coeff")?;
+    let mut folded_result = &fri_fold_random_coeff_var * (&table, &ifft_results_vars.1);
+    folded_result = &folded_result + &ifft_results_vars.0;
+
+    let expected_entry_quotient: QM31Var =
+        ldm.read(format!("expected_entry_quotient_{}", query_idx))?;
+    expected_entry_quotient.equalverify(&folded_result)?;
+
+    ldm.save()?;
+    Ok(cs)
+}
+
+/// Combine the four limbs of a queried secure field element.
+fn qm31_from_limbs(limbs: &[M31Var]) -> Result<QM31Var> {
+    if limbs.len() != SECURE_EXTENSION_DEGREE {
+        bail!("a FRI leaf must hold one secure field element");
+    }
+
+    Ok(QM31Var {
+        first: CM31Var {
+            real: limbs[0].clone(),
+            imag: limbs[1].clone(),
+        },
+        second: CM31Var {
+            real: limbs[2].clone(),
+            imag: limbs[3].clone(),
+        },
+    })
+}
+
+/// Aggregate the numerators with `powers` as `aggregate_numerators` does, which also accepts a
+/// single numerator.
+fn aggregate(table: &TableVar, powers: &[QM31Var], numerators: &[CM31Var]) -> Result<QM31Var> {
+    if numerators.len() == 1 {
+        let cs = numerators[0].cs();
+        return Ok(QM31Var {
+            first: numerators[0].clone(),
+            second: CM31Var::new_constant(&cs, CM31::zero())?,
+        });
+    }
+
+    Ok(aggregate_numerators(
+        table,
+        powers,
+        &numerators.iter().collect::<Vec<_>>(),
+    ))
+}
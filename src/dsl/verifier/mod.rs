@@ -0,0 +1,181 @@
+use crate::treepp::*;
+use anyhow::Result;
+use bitcoin::script::write_scriptint;
+use bitcoin_script_dsl::compiler::Compiler;
+use bitcoin_script_dsl::constraint_system::{ConstraintSystemRef, Element};
+use bitcoin_script_dsl::ldm::LDM;
+
+mod generic;
+
+pub use generic::{ColumnMask, CompositionFn, CompositionInputs, GenericVerifier, VerifierHints};
+
+/// The elements that a program takes from, or leaves on, the stack.
+pub type Witness = Vec<Vec<u8>>;
+
+/// A part of the verifier that runs once.
+pub type GlobalPartFn<H> = fn(&H, &mut LDM) -> Result<ConstraintSystemRef>;
+
+/// A part of the verifier that runs once for each query.
+pub type PerQueryPartFn<H> = fn(&H, &mut LDM, usize) -> Result<ConstraintSystemRef>;
+
+/// A verifier split into several hand-written programs that share their state through the LDM.
+///
+/// The programs are, in this order, the prologue, the per-query parts repeated for each query,
+/// and the epilogue. The hints `H` carry whatever the parts need. For a verifier assembled from
+/// the description of an AIR instead, see `GenericVerifier`.
+pub struct SplitVerifier<H> {
+    /// The parts before the queries, e.g., Fiat-Shamir, constraints, and column line coeffs.
+    pub prologue: Vec<GlobalPartFn<H>>,
+    /// The parts for each query, e.g., folding and quotients.
+    pub per_query: Vec<PerQueryPartFn<H>>,
+    /// The number of queries.
+    pub n_queries: usize,
+    /// The parts after all the queries.
+    pub epilogue: Vec<GlobalPartFn<H>>,
+}
+
+/// The compiled programs of a verifier.
+pub struct CompiledVerifier {
+    /// The scripts of the programs.
+    pub scripts: Vec<Script>,
+    /// The hints of the programs.
+    pub witnesses: Vec<Witness>,
+    /// The stack after each program, which is the input of the next program.
+    pub outputs: Vec<Witness>,
+    /// The number of programs in the prologue.
+    pub n_prologue: usize,
+    /// The number of programs for each query.
+    pub n_per_query: usize,
+}
+
+impl<H> SplitVerifier<H> {
+    /// The total number of programs.
+    pub fn num_programs(&self) -> usize {
+        self.prologue.len() + self.per_query.len() * self.n_queries + self.epilogue.len()
+    }
+
+    /// Compile all the programs of the verifier with the given hints.
+    pub fn compile(&self, hints: &H) -> Result<CompiledVerifier> {
+        let mut ldm = LDM::new();
+
+        let mut compiled = CompiledVerifier {
+            scripts: vec![],
+            witnesses: vec![],
+            outputs: vec![],
+            n_prologue: self.prologue.len(),
+            n_per_query: self.per_query.len(),
+        };
+
+        for f in self.prologue.iter() {
+            let cs = f(hints, &mut ldm)?;
+            compiled.push(cs, &ldm)?;
+        }
+
+        for query_idx in 0..self.n_queries {
+            for f in self.per_query.iter() {
+                let cs = f(hints, &mut ldm, query_idx)?;
+                compiled.push(cs, &ldm)?;
+            }
+        }
+
+        for f in self.epilogue.iter() {
+            let cs = f(hints, &mut ldm)?;
+            compiled.push(cs, &ldm)?;
+        }
+
+        assert_eq!(compiled.scripts.len(), self.num_programs());
+        assert_eq!(compiled.scripts.len(), compiled.witnesses.len());
+        assert_eq!(compiled.scripts.len(), compiled.outputs.len());
+
+        Ok(compiled)
+    }
+}
+
+impl CompiledVerifier {
+    /// The hints of the prologue programs, concatenated.
+    pub fn prologue_witness(&self) -> Witness {
+        self.witnesses[..self.n_prologue].concat()
+    }
+
+    /// The hints of the programs for the query `query_idx`, concatenated.
+    pub fn per_query_witness(&self, query_idx: usize) -> Witness {
+        let start = self.n_prologue + query_idx * self.n_per_query;
+        self.witnesses[start..start + self.n_per_query].concat()
+    }
+
+    /// The hints of the epilogue programs, concatenated.
+    pub fn epilogue_witness(&self, n_queries: usize) -> Witness {
+        self.witnesses[self.n_prologue + n_queries * self.n_per_query..].concat()
+    }
+
+    /// Run the program at `idx` with the output of the previous program followed by its hints.
+    #[cfg(any(test, feature = "exec"))]
+    pub fn execute(&self, idx: usize) -> VerifierExecutionResult {
+        let mut witness = if idx == 0 {
+            vec![]
+        } else {
+            self.outputs[idx - 1].clone()
+        };
+        witness.extend(self.witnesses[idx].iter().cloned());
+
+        execute_verifier(self.scripts[idx].clone(), witness)
+    }
+
+    fn push(&mut self, cs: ConstraintSystemRef, ldm: &LDM) -> Result<()> {
+        let program = Compiler::compile(cs)?;
+
+        self.scripts.push(program.script);
+        self.witnesses.push(hints_to_witness(&program.hint));
+
+        self.outputs.push(
+            convert_to_witness(script! {
+                { ldm.hash_var.as_ref().unwrap().value.clone() }
+            })
+            .unwrap(),
+        );
+
+        Ok(())
+    }
+}
+
+/// Convert the hints of a compiled program into the witness that pushes them.
+pub fn hints_to_witness(hints: &[Element]) -> Witness {
+    hints
+        .iter()
+        .map(|entry| match entry {
+            Element::Num(v) => {
+                let mut out = [0u8; 8];
+                let len = write_scriptint(&mut out, *v as i64);
+                out[0..len].to_vec()
+            }
+            Element::Str(v) => v.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dsl::plonk::covenant::plonk_verifier;
+    use crate::dsl::plonk::hints::Hints;
+
+    #[test]
+    fn test_compiled_verifier_executes() {
+        let verifier = plonk_verifier();
+
+        let hints = Hints::instance();
+        let compiled = verifier.compile(&hints).unwrap();
+
+        // each program accepts the output of the previous program and its own hints, and leaves
+        // the output that the next program takes
+        for idx in 0..verifier.num_programs() {
+            let result = compiled.execute(idx);
+            assert!(
+                result.error.is_none(),
+                "program {} fails: {:?}",
+                idx,
+                result.error
+            );
+            assert_eq!(result.final_stack, compiled.outputs[idx]);
+        }
+    }
+}
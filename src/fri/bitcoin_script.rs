@@ -0,0 +1,237 @@
+use crate::treepp::*;
+use crate::OP_HINT;
+
+/// Gadget for computing the parents of the queries, i.e., `query >> 1`, using hints.
+///
+/// Input:
+/// - query_0, query_1, ..., query_{n-1}
+///
+/// Output:
+/// - parent_0, parent_1, ..., parent_{n-1}
+pub fn queries_parents_gadget(n_queries: usize) -> Script {
+    script! {
+        for _ in 0..n_queries {
+            // pull the lowest bit and the parent
+            OP_HINT OP_HINT
+
+            // check that the parent is nonnegative and the lowest bit is either 0 or 1
+            OP_DUP 0 OP_GREATERTHANOREQUAL OP_VERIFY
+            OP_OVER 0 2 OP_WITHIN OP_VERIFY
+
+            // check that query = 2 * parent + bit
+            OP_DUP OP_TOALTSTACK
+            OP_DUP OP_ADD OP_ADD
+            OP_EQUALVERIFY
+        }
+        for _ in 0..n_queries {
+            OP_FROMALTSTACK
+        }
+    }
+}
+
+/// Gadget for checking that the query parents on the stack are sorted in a non-decreasing order,
+/// as the Merkle and folding hints are generated from the sorted query parents. Duplicates are
+/// allowed, since the queries drawn from the channel may repeat.
+///
+/// Input:
+/// - parent_0, parent_1, ..., parent_{n-1}
+///
+/// Output:
+/// - parent_0, parent_1, ..., parent_{n-1}
+pub fn assert_queries_sorted_gadget(n_queries: usize) -> Script {
+    script! {
+        for i in 0..n_queries.saturating_sub(1) {
+            { n_queries - 1 - i } OP_PICK
+            { n_queries - 1 - i } OP_PICK
+            OP_LESSTHANOREQUAL OP_VERIFY
+        }
+    }
+}
+
+/// Gadget for reorganizing the queried values into per-query blocks, each consisting of the two
+/// trace values followed by the eight composition values of that query.
+///
+/// Input:
+/// - trace_0, trace_1, ..., trace_{2n-1} (two per query)
+/// - composition_0, composition_1, ..., composition_{8n-1} (eight per query)
+///
+/// Output:
+/// - for each query i, trace_{2i}, trace_{2i+1}, composition_{8i}, ..., composition_{8i+7}
+pub fn interleave_query_values_gadget(n_queries: usize) -> Script {
+    script! {
+        for i in 0..n_queries {
+            // the remaining trace values are always at the bottom
+            for _ in 0..2 {
+                { 10 * n_queries - 1 } OP_ROLL
+            }
+            // followed by the remaining composition values
+            for _ in 0..8 {
+                { 8 * n_queries + 2 * i + 1 } OP_ROLL
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{ChannelWithHint, Sha256Channel};
+    use crate::fri::{
+        assert_queries_sorted_gadget, interleave_query_values_gadget, queries_parents_gadget,
+        queries_parents_with_hint,
+    };
+    use crate::tests_utils::report::report_bitcoin_script_size;
+    use crate::treepp::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::channel::Channel;
+    use stwo_prover::core::queries::Queries;
+    use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+
+    #[test]
+    fn test_queries_parents_gadget() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let n_queries = 8;
+        let logn = 10;
+
+        let gadget = queries_parents_gadget(n_queries);
+        report_bitcoin_script_size("FRI", "queries_parents_gadget(8)", gadget.len());
+
+        for _ in 0..10 {
+            let mut a = [0u8; 32];
+            a.iter_mut().for_each(|v| *v = prng.gen());
+            let a = Sha256Hash::from(a.to_vec());
+
+            let mut channel = Sha256Channel::default();
+            channel.update_digest(a);
+            let (queries, _) = channel.draw_queries_and_hints(n_queries, logn);
+
+            let (parents, hint) = queries_parents_with_hint(&queries);
+
+            // the parents, after sorting and deduplication, are the queries folded once
+            let mut sorted_queries = queries.clone();
+            sorted_queries.sort_unstable();
+            sorted_queries.dedup();
+            let folded = Queries {
+                positions: sorted_queries,
+                log_domain_size: logn as u32,
+            }
+            .fold(1);
+
+            let mut sorted_parents = parents.clone();
+            sorted_parents.sort_unstable();
+            sorted_parents.dedup();
+            assert_eq!(sorted_parents, folded.positions);
+
+            let script = script! {
+                { hint }
+                for &query in queries.iter() {
+                    { query }
+                }
+                { gadget.clone() }
+                for &parent in parents.iter().rev() {
+                    { parent }
+                    OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_queries_parents_gadget_wrong_hint() {
+        let queries = vec![5usize, 12, 7];
+        let (_, mut hint) = queries_parents_with_hint(&queries);
+
+        // claim that the parent of 7 is 2 with the lowest bit being 3
+        hint.0[0] = (3, 2);
+
+        let script = script! {
+            { hint }
+            for &query in queries.iter() {
+                { query }
+            }
+            { queries_parents_gadget(queries.len()) }
+            OP_2DROP OP_DROP
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+    }
+
+    #[test]
+    fn test_assert_queries_sorted_gadget() {
+        let gadget = assert_queries_sorted_gadget(8);
+        report_bitcoin_script_size("FRI", "assert_queries_sorted_gadget(8)", gadget.len());
+
+        let run = |parents: &[usize]| {
+            let script = script! {
+                for &parent in parents.iter() {
+                    { parent }
+                }
+                { assert_queries_sorted_gadget(parents.len()) }
+                for &parent in parents.iter().rev() {
+                    { parent } OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            execute_script(script).success
+        };
+
+        // sorted with duplicates
+        assert!(run(&[1, 3, 3, 7, 7, 7, 12, 500]));
+        assert!(run(&[4]));
+        assert!(run(&[]));
+
+        // out of order
+        assert!(!run(&[1, 3, 2, 7, 7, 7, 12, 500]));
+        assert!(!run(&[1, 3, 3, 7, 7, 7, 500, 12]));
+        assert!(!run(&[9, 3, 3, 7, 7, 7, 12, 500]));
+    }
+
+    #[test]
+    fn test_interleave_query_values() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for n_queries in [1, 8] {
+            let gadget = interleave_query_values_gadget(n_queries);
+            report_bitcoin_script_size(
+                "FRI",
+                format!("interleave_query_values_gadget({})", n_queries).as_str(),
+                gadget.len(),
+            );
+
+            let trace = (0..2 * n_queries)
+                .map(|_| prng.gen_range(0..1u32 << 31))
+                .collect::<Vec<_>>();
+            let composition = (0..8 * n_queries)
+                .map(|_| prng.gen_range(0..1u32 << 31))
+                .collect::<Vec<_>>();
+
+            let mut expected = vec![];
+            for i in 0..n_queries {
+                expected.extend_from_slice(&trace[2 * i..2 * i + 2]);
+                expected.extend_from_slice(&composition[8 * i..8 * i + 8]);
+            }
+
+            let script = script! {
+                for v in trace.iter() {
+                    { *v }
+                }
+                for v in composition.iter() {
+                    { *v }
+                }
+                { gadget.clone() }
+                for v in expected.iter().rev() {
+                    { *v }
+                    OP_EQUALVERIFY
+                }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+}
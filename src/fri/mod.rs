@@ -1,5 +1,16 @@
-use crate::channel::{ChannelWithHint, DrawHints};
+use crate::channel::{ChannelWithHint, DrawHints, Sha256Channel};
+use crate::merkle_tree::MerkleTreeTwinProof;
+use crate::treepp::pushable::{Builder, Pushable};
+use anyhow::bail;
+use std::collections::BTreeSet;
+use stwo_prover::core::channel::Channel;
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
 use stwo_prover::core::queries::Queries;
+use stwo_prover::core::vcs::sha256_hash::{Sha256Hash, Sha256Hasher};
+
+mod bitcoin_script;
+pub use bitcoin_script::*;
 
 /// A trait for generating the queries with hints.
 pub trait QueriesWithHint: Sized {
@@ -27,3 +38,218 @@ impl QueriesWithHint for Queries {
         )
     }
 }
+
+/// Compute, for each of the `n_layers` folding layers, the deduplicated and sorted positions
+/// touched by the queries, where a position `pos` at layer `i` folds into `pos >> 1` at layer `i + 1`.
+///
+/// The first entry corresponds to the queries themselves.
+pub fn fold_positions(queries: &[usize], n_layers: usize) -> Vec<Vec<usize>> {
+    let mut res = Vec::with_capacity(n_layers);
+    let mut cur = queries.iter().copied().collect::<BTreeSet<usize>>();
+
+    for _ in 0..n_layers {
+        let next = cur.iter().map(|pos| pos >> 1).collect::<BTreeSet<usize>>();
+        res.push(cur.into_iter().collect());
+        cur = next;
+    }
+
+    res
+}
+
+/// Compute the number of FRI inner layers for the given log degree bound of the largest column,
+/// which is first folded into a line and then folded once per layer until it reaches the degree
+/// bound of the last layer.
+///
+/// The blowup factor does not affect the count, since it only changes the layer domains. Returns
+/// `None` if the column bound is zero or the line bound is below the degree bound of the last
+/// layer.
+pub fn num_fri_layers(
+    max_column_log_degree_bound: u32,
+    log_last_layer_degree_bound: u32,
+) -> Option<usize> {
+    let line_log_degree_bound = max_column_log_degree_bound.checked_sub(1)?;
+    line_log_degree_bound
+        .checked_sub(log_last_layer_degree_bound)
+        .map(|n| n as usize)
+}
+
+/// Check that, for each query, the queried values are the concatenation of the leaves of that
+/// query's twin proofs in each tree, so that the i-th values come from the i-th query's proofs.
+///
+/// `twin_proofs` holds, for each tree, one twin proof per query in the order of `queries_parents`.
+pub fn assert_query_values_aligned(
+    queries_parents: &[usize],
+    twin_proofs: &[&[MerkleTreeTwinProof]],
+    queried_values_left: &[Vec<M31>],
+    queried_values_right: &[Vec<M31>],
+) -> anyhow::Result<()> {
+    let n_queries = queries_parents.len();
+    if queried_values_left.len() != n_queries || queried_values_right.len() != n_queries {
+        bail!(
+            "expected queried values for {} queries, found {} and {}",
+            n_queries,
+            queried_values_left.len(),
+            queried_values_right.len()
+        );
+    }
+    for (tree, proofs) in twin_proofs.iter().enumerate() {
+        if proofs.len() != n_queries {
+            bail!(
+                "expected {} twin proofs in tree {}, found {}",
+                n_queries,
+                tree,
+                proofs.len()
+            );
+        }
+    }
+
+    for (i, &parent) in queries_parents.iter().enumerate() {
+        let expected_left = twin_proofs
+            .iter()
+            .flat_map(|proofs| proofs[i].left.iter().copied())
+            .collect::<Vec<M31>>();
+        let expected_right = twin_proofs
+            .iter()
+            .flat_map(|proofs| proofs[i].right.iter().copied())
+            .collect::<Vec<M31>>();
+
+        if queried_values_left[i] != expected_left || queried_values_right[i] != expected_right {
+            bail!(
+                "the queried values at index {} do not come from the proofs of query {}",
+                i,
+                parent << 1
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Hints for computing the queries' parents, consisting of the lowest bit and the parent of
+/// each query, in the order the gadget consumes them (from the last query to the first).
+#[derive(Clone, Default)]
+pub struct QueriesParentsHint(pub Vec<(usize, usize)>);
+
+impl Pushable for QueriesParentsHint {
+    fn bitcoin_script_push(&self, mut builder: Builder) -> Builder {
+        for &(bit, parent) in self.0.iter() {
+            builder = (bit as u32).bitcoin_script_push(builder);
+            builder = (parent as u32).bitcoin_script_push(builder);
+        }
+        builder
+    }
+}
+
+/// Compute the queries' parents (`query >> 1`) and the hints for `queries_parents_gadget`.
+pub fn queries_parents_with_hint(queries: &[usize]) -> (Vec<usize>, QueriesParentsHint) {
+    let parents = queries
+        .iter()
+        .map(|query| query >> 1)
+        .collect::<Vec<usize>>();
+    let hint = QueriesParentsHint(
+        queries
+            .iter()
+            .rev()
+            .map(|&query| (query & 1, query >> 1))
+            .collect(),
+    );
+    (parents, hint)
+}
+
+/// Check that the FRI folding alphas are drawn from the channel, starting from `channel`'s state
+/// right before the first FRI layer, after mixing in each FRI layer commitment in order.
+pub fn verify_folding_alphas(
+    channel: &Sha256Channel,
+    commitments: &[Sha256Hash],
+    alphas: &[QM31],
+) -> bool {
+    if commitments.len() != alphas.len() {
+        return false;
+    }
+
+    let mut channel = channel.clone();
+    for (commitment, &alpha) in commitments.iter().zip(alphas.iter()) {
+        channel.update_digest(Sha256Hasher::concat_and_hash(commitment, &channel.digest()));
+        if channel.draw_felt() != alpha {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::Sha256Channel;
+    use crate::dsl::plonk::hints::{FiatShamirTrace, Hints, LOG_N_ROWS};
+    use crate::fri::{fold_positions, verify_folding_alphas};
+    use crate::utils::get_rand_qm31;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::pcs::PcsConfig;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleChannel;
+    use stwo_prover::examples::plonk::prove_fibonacci_plonk;
+
+    #[test]
+    fn test_fold_positions() {
+        let queries = [3, 17, 5, 16, 2, 63, 17];
+
+        let res = fold_positions(&queries, 4);
+        assert_eq!(res.len(), 4);
+
+        let mut expected = vec![];
+        let mut cur = queries.to_vec();
+        for _ in 0..4 {
+            let mut layer = cur.clone();
+            layer.sort_unstable();
+            layer.dedup();
+            expected.push(layer);
+
+            cur = cur.iter().map(|pos| pos >> 1).collect();
+        }
+        assert_eq!(res, expected);
+
+        assert_eq!(res[0], vec![2, 3, 5, 16, 17, 63]);
+        assert_eq!(res[1], vec![1, 2, 8, 31]);
+        assert_eq!(res[2], vec![0, 1, 4, 15]);
+        assert_eq!(res[3], vec![0, 2, 7]);
+    }
+
+    #[test]
+    fn test_verify_folding_alphas() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let config = PcsConfig::default();
+        let (plonk_component, proof) =
+            prove_fibonacci_plonk::<Sha256MerkleChannel>(LOG_N_ROWS, config);
+
+        let mut trace = FiatShamirTrace::default();
+        let hints = Hints::from_proof_with_trace(&plonk_component, proof, config, Some(&mut trace))
+            .unwrap()
+            .fiat_shamir_hints;
+
+        // the channel right before the first FRI layer, as recorded by the Fiat-Shamir hints
+        let mut channel = Sha256Channel::default();
+        channel.update_digest(trace.digests[FiatShamirTrace::NUM_DIGESTS_BEFORE_FRI - 1]);
+
+        let commitments = &hints.fri_layer_commitments;
+        let alphas = &hints.fri_layer_alphas;
+        assert!(verify_folding_alphas(&channel, commitments, alphas));
+
+        // a tampered alpha is detected
+        let mut tampered_alphas = alphas.clone();
+        tampered_alphas[2] = get_rand_qm31(&mut prng);
+        assert!(!verify_folding_alphas(
+            &channel,
+            commitments,
+            &tampered_alphas
+        ));
+
+        // a missing alpha is detected
+        assert!(!verify_folding_alphas(
+            &channel,
+            commitments,
+            &alphas[..alphas.len() - 1]
+        ));
+    }
+}
@@ -39,6 +39,31 @@ pub mod treepp {
 
     define_pushable!();
     pub use bitcoin::ScriptBuf as Script;
+
+    /// The result of running a verifier script.
+    #[cfg(any(test, feature = "exec"))]
+    #[derive(Debug, Clone)]
+    pub struct VerifierExecutionResult {
+        /// Whether the script succeeds.
+        pub success: bool,
+        /// The stack after the execution.
+        pub final_stack: Vec<Vec<u8>>,
+        /// The error, if the execution fails.
+        pub error: Option<String>,
+    }
+
+    /// Run a verifier script with its witness, without the stack size limit.
+    #[cfg(any(test, feature = "exec"))]
+    pub fn execute_verifier(script: Script, witness: Vec<Vec<u8>>) -> VerifierExecutionResult {
+        let exec_result =
+            bitcoin_scriptexec::execute_script_with_witness_unlimited_stack(script, witness);
+
+        VerifierExecutionResult {
+            success: exec_result.success,
+            final_stack: exec_result.final_stack.iter_str().collect(),
+            error: exec_result.error.map(|err| format!("{:?}", err)),
+        }
+    }
 }
 
 impl Pushable for M31 {
@@ -89,34 +114,22 @@ pub fn OP_HINT() -> treepp::Script {
 
 #[cfg(test)]
 mod test {
-    use crate::treepp::{
-        pushable::{Builder, Pushable},
-        *,
-    };
-    use crate::utils::get_rand_qm31;
-    use rand::{RngCore, SeedableRng};
+    use crate::tests_utils::pushable::assert_pushable_eq;
+    use crate::treepp::*;
+    use crate::utils::{get_rand_cm31, get_rand_m31, get_rand_qm31};
+    use rand::SeedableRng;
     use rand_chacha::ChaCha20Rng;
-    use stwo_prover::core::fields::cm31::CM31;
-    use stwo_prover::core::fields::m31::M31;
 
     #[test]
     fn test_pushable() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
 
-        let m31 = M31::reduce(prng.next_u64());
-        let cm31 = CM31::from_m31(M31::reduce(prng.next_u64()), M31::reduce(prng.next_u64()));
+        let m31 = get_rand_m31(&mut prng);
+        let cm31 = get_rand_cm31(&mut prng);
         let qm31 = get_rand_qm31(&mut prng);
 
-        let mut builder = Builder::new();
-        builder = m31.bitcoin_script_push(builder);
-        assert_eq!(script! { {m31} }.as_bytes(), builder.as_bytes());
-
-        let mut builder = Builder::new();
-        builder = cm31.bitcoin_script_push(builder);
-        assert_eq!(script! { {cm31} }.as_bytes(), builder.as_bytes());
-
-        let mut builder = Builder::new();
-        builder = qm31.bitcoin_script_push(builder);
-        assert_eq!(script! { {qm31} }.as_bytes(), builder.as_bytes());
+        assert_pushable_eq(m31);
+        assert_pushable_eq(cm31);
+        assert_pushable_eq(qm31);
     }
 }
@@ -1,7 +1,7 @@
 use crate::treepp::*;
 use crate::utils::{
-    dup_m31_vec_gadget, hash, hash_m31_vec_gadget, limb_to_be_bits_toaltstack_except_lowest_1bit,
-    m31_vec_from_bottom_gadget,
+    dup_m31_vec_gadget, hash, hash_checked, hash_m31_vec_gadget,
+    limb_to_be_bits_toaltstack_except_lowest_1bit, m31_vec_from_bottom_gadget,
 };
 use crate::OP_HINT;
 
@@ -10,7 +10,20 @@ pub struct MerkleTreeTwinGadget;
 
 impl MerkleTreeTwinGadget {
     pub(crate) fn query_and_verify_internal(len: usize, logn: usize) -> Script {
+        Self::query_and_verify_internal_with_width_check(len, logn, false)
+    }
+
+    fn query_and_verify_internal_with_width_check(
+        len: usize,
+        logn: usize,
+        check_width: bool,
+    ) -> Script {
         script! {
+            // check the width of the left
+            if check_width {
+                OP_HINT { len } OP_EQUALVERIFY
+            }
+
             // left
             { m31_vec_from_bottom_gadget(len) }
 
@@ -18,10 +31,14 @@ impl MerkleTreeTwinGadget {
             { dup_m31_vec_gadget(len) }
 
             // hash the left and keep the hash in the altstack
-            { hash_m31_vec_gadget(len) }
-            hash
+            { leaf_hash_gadget(len) }
             OP_TOALTSTACK
 
+            // check the width of the right
+            if check_width {
+                OP_HINT { len } OP_EQUALVERIFY
+            }
+
             // right
             { m31_vec_from_bottom_gadget(len) }
 
@@ -29,8 +46,7 @@ impl MerkleTreeTwinGadget {
             { dup_m31_vec_gadget(len) }
 
             // hash the right
-            { hash_m31_vec_gadget(len) }
-            hash
+            { leaf_hash_gadget(len) }
 
             // put the left hash out and merge into the parent hash
             OP_FROMALTSTACK
@@ -62,6 +78,46 @@ impl MerkleTreeTwinGadget {
     }
 }
 
+impl MerkleTreeTwinGadget {
+    /// Query and verify using the Merkle path as a hint, checking that the hinted leaves have
+    /// exactly `len` elements.
+    ///
+    /// Hint:
+    /// - width of the left, left, width of the right, right
+    /// - Merkle path
+    ///
+    /// Input:
+    /// - root_hash
+    /// - pos
+    ///
+    /// Output:
+    /// - vl (the element on the left)
+    /// - vr (the element on the right)
+    pub fn query_and_verify_checked(len: usize, logn: usize) -> Script {
+        script! {
+            // push the root hash to the altstack, first
+            OP_SWAP OP_TOALTSTACK
+            { limb_to_be_bits_toaltstack_except_lowest_1bit(logn as u32) }
+            { Self::query_and_verify_internal_with_width_check(len, logn, true) }
+        }
+    }
+}
+
+/// Compute the hash of a leaf with `n_columns` m31 elements, which matches
+/// `Sha256MerkleHasher::hash_node(None, &values)`.
+///
+/// Input:
+/// - values[0], values[1], ..., values[n_columns - 1]
+///
+/// Output:
+/// - leaf hash
+pub fn leaf_hash_gadget(n_columns: usize) -> Script {
+    script! {
+        { hash_m31_vec_gadget(n_columns) }
+        hash
+    }
+}
+
 /// Gadget that handles the path verification (non-leaf-related parts).
 pub struct MerkleTreePathGadget;
 
@@ -86,7 +142,7 @@ impl MerkleTreePathGadget {
             for _ in 0..path_len {
                 OP_HINT
                 OP_FROMALTSTACK OP_IF OP_SWAP OP_ENDIF
-                OP_CAT hash
+                OP_CAT { hash_checked(64) }
             }
 
             OP_FROMALTSTACK
@@ -97,13 +153,38 @@ impl MerkleTreePathGadget {
 
 #[cfg(test)]
 mod test {
-    use crate::merkle_tree::bitcoin_script::MerkleTreeTwinGadget;
-    use crate::merkle_tree::MerkleTreeTwinProof;
+    use crate::merkle_tree::bitcoin_script::{leaf_hash_gadget, MerkleTreeTwinGadget};
+    use crate::merkle_tree::{MerkleTreeTwinProof, MerkleTreeTwinProofWithWidth};
     use crate::treepp::*;
-    use crate::utils::get_rand_qm31;
+    use crate::utils::{get_rand_m31, get_rand_qm31};
     use crate::{merkle_tree::MerkleTree, tests_utils::report::report_bitcoin_script_size};
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::vcs::ops::MerkleHasher;
+    use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleHasher;
+
+    #[test]
+    fn test_leaf_hash() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for n_columns in [1, 2, 10] {
+            let values = (0..n_columns)
+                .map(|_| get_rand_m31(&mut prng))
+                .collect::<Vec<_>>();
+            let expected = Sha256MerkleHasher::hash_node(None, &values);
+
+            let script = script! {
+                for value in values.iter() {
+                    { *value }
+                }
+                { leaf_hash_gadget(n_columns) }
+                { expected.as_ref().to_vec() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
 
     #[test]
     fn test_merkle_tree_verify() {
@@ -155,4 +236,66 @@ mod test {
             assert!(exec_result.success);
         }
     }
+
+    #[test]
+    fn test_merkle_tree_verify_checked() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let logn = 12;
+        let verify_script = MerkleTreeTwinGadget::query_and_verify_checked(4, logn);
+
+        report_bitcoin_script_size(
+            "MerkleTreeTwin",
+            format!("verify_checked(2^{})", logn).as_str(),
+            verify_script.len(),
+        );
+
+        let mut last_layer = vec![];
+        for _ in 0..(1 << logn) {
+            let a = get_rand_qm31(&mut prng);
+            last_layer.push(a.to_m31_array().to_vec());
+        }
+
+        let merkle_tree = MerkleTree::new(last_layer.clone());
+
+        let mut pos: u32 = prng.gen();
+        pos &= (1 << logn) - 1;
+        if pos % 2 == 1 {
+            pos -= 1;
+        }
+
+        let proof = MerkleTreeTwinProof::query(&merkle_tree, pos as usize);
+
+        let script = script! {
+            { MerkleTreeTwinProofWithWidth(proof.clone()) }
+            { merkle_tree.root_hash }
+            { pos }
+            { verify_script.clone() }
+            for elem in last_layer[(pos | 1) as usize].iter().rev() {
+                { *elem }
+                OP_EQUALVERIFY
+            }
+            for elem in last_layer[pos as usize].iter().rev() {
+                { *elem }
+                OP_EQUALVERIFY
+            }
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+
+        // a witness whose left leaf has a wrong width is rejected
+        let mut wrong_proof = proof.clone();
+        wrong_proof.left.push(get_rand_qm31(&mut prng).0 .0);
+
+        let script = script! {
+            { MerkleTreeTwinProofWithWidth(wrong_proof) }
+            { merkle_tree.root_hash }
+            { pos }
+            { verify_script.clone() }
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+    }
 }
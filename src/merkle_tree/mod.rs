@@ -1,65 +1,149 @@
 use crate::treepp::pushable::{Builder, Pushable};
+use anyhow::bail;
 use std::collections::{BTreeSet, HashMap};
 use stwo_prover::core::fields::m31::{BaseField, M31};
+use stwo_prover::core::fields::qm31::QM31;
 use stwo_prover::core::vcs::ops::MerkleHasher;
 use stwo_prover::core::vcs::prover::MerkleDecommitment;
-use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
 use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleHasher;
+use stwo_prover::core::vcs::verifier::MerkleVerificationError;
 
 mod bitcoin_script;
 pub use bitcoin_script::*;
 
-/// A Merkle tree.
-pub struct MerkleTree {
+/// A Merkle tree, by default using SHA256.
+pub struct MerkleTree<H: MerkleHasher = Sha256MerkleHasher> {
     /// Leaf layers, consisting of m31 elements.
     pub leaf_layer: Vec<Vec<M31>>,
     /// Intermediate layers.
-    pub intermediate_layers: Vec<Vec<Sha256Hash>>,
+    pub intermediate_layers: Vec<Vec<H::Hash>>,
     /// Root hash.
-    pub root_hash: Sha256Hash,
+    pub root_hash: H::Hash,
+    /// Number of leaves before padding.
+    pub original_len: usize,
 }
 
-impl MerkleTree {
-    /// Create a new Merkle tree.
+impl MerkleTree<Sha256MerkleHasher> {
+    /// Create a new Merkle tree using SHA256.
     pub fn new(leaf_layer: Vec<Vec<M31>>) -> Self {
+        Self::new_with_hasher(leaf_layer)
+    }
+
+    /// Create a new Merkle tree whose leaves consist of qm31 elements, each of which is flattened
+    /// into its four m31 limbs.
+    pub fn new_qm31(leaf_layer: Vec<Vec<QM31>>) -> Self {
+        Self::new(
+            leaf_layer
+                .iter()
+                .map(|leaf| leaf.iter().flat_map(|v| v.to_m31_array()).collect())
+                .collect(),
+        )
+    }
+
+    /// Create a new Merkle tree whose leaf count does not need to be a power of two.
+    ///
+    /// The leaf layer is padded to the next power of two with empty leaves, each of which hashes
+    /// to `hash_node(None, &[])`.
+    pub fn new_padded(mut leaf_layer: Vec<Vec<M31>>) -> Self {
+        let original_len = leaf_layer.len();
+        assert!(original_len >= 2);
+
+        leaf_layer.resize(original_len.next_power_of_two(), vec![]);
+
+        let mut tree = Self::new(leaf_layer);
+        tree.original_len = original_len;
+        tree
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    /// Create a new Merkle tree using the given Merkle hasher.
+    pub fn new_with_hasher(leaf_layer: Vec<Vec<M31>>) -> Self {
         assert!(leaf_layer.len().is_power_of_two());
 
         let mut intermediate_layers = vec![];
         let mut cur = leaf_layer
             .chunks_exact(2)
             .map(|v| {
-                let commit_1 = Sha256MerkleHasher::hash_node(None, &v[0]);
-                let commit_2 = Sha256MerkleHasher::hash_node(None, &v[1]);
+                let commit_1 = H::hash_node(None, &v[0]);
+                let commit_2 = H::hash_node(None, &v[1]);
 
-                Sha256MerkleHasher::hash_node(Some((commit_1, commit_2)), &[])
+                H::hash_node(Some((commit_1, commit_2)), &[])
             })
-            .collect::<Vec<Sha256Hash>>();
+            .collect::<Vec<H::Hash>>();
         intermediate_layers.push(cur.clone());
 
         while cur.len() > 1 {
             cur = cur
                 .chunks_exact(2)
-                .map(|v| Sha256MerkleHasher::hash_node(Some((v[0], v[1])), &[]))
-                .collect::<Vec<Sha256Hash>>();
+                .map(|v| H::hash_node(Some((v[0], v[1])), &[]))
+                .collect::<Vec<H::Hash>>();
             intermediate_layers.push(cur.clone());
         }
 
+        let original_len = leaf_layer.len();
+
         Self {
             leaf_layer,
             intermediate_layers,
             root_hash: cur[0],
+            original_len,
         }
     }
+
+    /// Return the depth of the tree, which includes the padding.
+    pub fn logn(&self) -> usize {
+        self.intermediate_layers.len()
+    }
+
+    /// Return the number of intermediate layers, which is the same as `logn`.
+    pub fn depth(&self) -> usize {
+        self.logn()
+    }
+
+    /// Return the intermediate layer at the given depth, where depth 0 consists of the hashes of
+    /// pairs of leaves and the last layer consists of the root hash only.
+    pub fn layer(&self, depth: usize) -> Option<&[H::Hash]> {
+        self.intermediate_layers.get(depth).map(|v| v.as_slice())
+    }
+
+    /// Return the root hash.
+    pub fn root(&self) -> &H::Hash {
+        &self.root_hash
+    }
+
+    /// Iterate over the intermediate sibling nodes for the leaf pair at `pos`, from the bottom to
+    /// the top, as in `MerkleTreePath::query`.
+    pub fn siblings_for(&self, pos: usize) -> impl Iterator<Item = &H::Hash> + '_ {
+        let num_layers = self.intermediate_layers.len();
+        self.intermediate_layers[..num_layers - 1]
+            .iter()
+            .enumerate()
+            .map(move |(i, layer)| &layer[(pos >> (i + 1)) ^ 1])
+    }
+
+    /// Verify a single leaf against the root hash, given the Merkle tree path from the leaf,
+    /// which starts with the hash of the leaf's sibling (see `MerkleTreePath::query_leaf`).
+    pub fn verify_leaf(
+        root_hash: &H::Hash,
+        depth: usize,
+        leaf: &[M31],
+        path: &MerkleTreePath<H>,
+        query: usize,
+    ) -> bool {
+        let leaf_hash = H::hash_node(None, leaf);
+        path.verify(root_hash, depth, leaf_hash, query)
+    }
 }
 
 #[derive(Default, Clone, Debug)]
 /// An internal proof type that excludes the leaf (or leaves).
-pub struct MerkleTreePath {
+pub struct MerkleTreePath<H: MerkleHasher = Sha256MerkleHasher> {
     /// All the intermediate sibling nodes.
-    pub siblings: Vec<Sha256Hash>,
+    pub siblings: Vec<H::Hash>,
 }
 
-impl Pushable for MerkleTreePath {
+impl Pushable for MerkleTreePath<Sha256MerkleHasher> {
     fn bitcoin_script_push(&self, mut builder: Builder) -> Builder {
         for elem in self.siblings.iter() {
             builder = elem.bitcoin_script_push(builder);
@@ -68,16 +152,19 @@ impl Pushable for MerkleTreePath {
     }
 }
 
-impl MerkleTreePath {
+impl<H: MerkleHasher> MerkleTreePath<H> {
     /// Generate the Merkle tree path.
-    pub fn query(tree: &MerkleTree, mut pos: usize) -> Self {
-        let mut siblings = vec![];
-
-        let num_layers = tree.intermediate_layers.len();
-        for i in 0..num_layers - 1 {
-            pos >>= 1;
-            siblings.push(tree.intermediate_layers[i][pos ^ 1]);
+    pub fn query(tree: &MerkleTree<H>, pos: usize) -> Self {
+        Self {
+            siblings: tree.siblings_for(pos).copied().collect(),
         }
+    }
+
+    /// Generate the Merkle tree path for a single leaf, which, unlike `query`, also includes the
+    /// hash of the leaf's sibling.
+    pub fn query_leaf(tree: &MerkleTree<H>, pos: usize) -> Self {
+        let mut siblings = vec![H::hash_node(None, &tree.leaf_layer[pos ^ 1])];
+        siblings.extend(Self::query(tree, pos).siblings);
 
         Self { siblings }
     }
@@ -85,9 +172,9 @@ impl MerkleTreePath {
     /// Verify the Merkle tree path given the root hash, the considered depth, the leaf hash, and the query.
     pub fn verify(
         &self,
-        root_hash: &Sha256Hash,
+        root_hash: &H::Hash,
         depth: usize,
-        mut leaf_hash: Sha256Hash,
+        mut leaf_hash: H::Hash,
         mut query: usize,
     ) -> bool {
         assert_eq!(self.siblings.len(), depth);
@@ -99,7 +186,7 @@ impl MerkleTreePath {
                 (self.siblings[i], leaf_hash)
             };
 
-            leaf_hash = Sha256MerkleHasher::hash_node(Some((f0, f1)), &[]);
+            leaf_hash = H::hash_node(Some((f0, f1)), &[]);
             query >>= 1;
         }
 
@@ -109,16 +196,16 @@ impl MerkleTreePath {
 
 /// A Merkle tree proof.
 #[derive(Default, Clone, Debug)]
-pub struct MerkleTreeTwinProof {
+pub struct MerkleTreeTwinProof<H: MerkleHasher = Sha256MerkleHasher> {
     /// Leaf as an M31 array.
     pub left: Vec<M31>,
     /// Leaf sibling as an M31 array.
     pub right: Vec<M31>,
     /// Remaining path.
-    pub path: MerkleTreePath,
+    pub path: MerkleTreePath<H>,
 }
 
-impl Pushable for MerkleTreeTwinProof {
+impl Pushable for MerkleTreeTwinProof<Sha256MerkleHasher> {
     fn bitcoin_script_push(&self, mut builder: Builder) -> Builder {
         for v in self.left.iter() {
             builder = v.bitcoin_script_push(builder);
@@ -130,38 +217,120 @@ impl Pushable for MerkleTreeTwinProof {
     }
 }
 
-impl MerkleTreeTwinProof {
+/// A Merkle tree proof whose hint also includes the width of each leaf.
+#[derive(Default, Clone, Debug)]
+pub struct MerkleTreeTwinProofWithWidth(pub MerkleTreeTwinProof);
+
+impl Pushable for MerkleTreeTwinProofWithWidth {
+    fn bitcoin_script_push(&self, mut builder: Builder) -> Builder {
+        builder = (self.0.left.len() as u32).bitcoin_script_push(builder);
+        for v in self.0.left.iter() {
+            builder = v.bitcoin_script_push(builder);
+        }
+        builder = (self.0.right.len() as u32).bitcoin_script_push(builder);
+        for v in self.0.right.iter() {
+            builder = v.bitcoin_script_push(builder);
+        }
+        self.0.path.bitcoin_script_push(builder)
+    }
+}
+
+impl<H: MerkleHasher> MerkleTreeTwinProof<H> {
+    /// Construct a proof from the leaf, its sibling, and the remaining path for a tree of depth
+    /// `logn`, checking that the two leaves have the same width and that there are `logn - 1`
+    /// siblings.
+    pub fn new(
+        left: Vec<M31>,
+        right: Vec<M31>,
+        siblings: Vec<H::Hash>,
+        logn: usize,
+    ) -> anyhow::Result<Self> {
+        if left.len() != right.len() {
+            bail!(
+                "the leaf has width {} but its sibling has width {}",
+                left.len(),
+                right.len()
+            );
+        }
+        if logn == 0 || siblings.len() != logn - 1 {
+            bail!(
+                "expected {} siblings for a tree of depth {}, found {}",
+                logn.saturating_sub(1),
+                logn,
+                siblings.len()
+            );
+        }
+
+        Ok(Self {
+            left,
+            right,
+            path: MerkleTreePath { siblings },
+        })
+    }
+
     /// Query the Merkle tree and generate a corresponding proof.
-    pub fn query(tree: &MerkleTree, pos: usize) -> MerkleTreeTwinProof {
+    pub fn query(tree: &MerkleTree<H>, pos: usize) -> Self {
         assert_eq!(pos & 1, 0);
+        assert!(pos < tree.original_len);
 
         let left = tree.leaf_layer[pos].clone();
         let right = tree.leaf_layer[pos | 1].clone();
         let path = MerkleTreePath::query(tree, pos);
 
-        MerkleTreeTwinProof { left, right, path }
+        Self { left, right, path }
+    }
+
+    /// Return the leaf and its sibling as qm31 arrays, for trees built with `MerkleTree::new_qm31`.
+    pub fn qm31_leaves(&self) -> (Vec<QM31>, Vec<QM31>) {
+        let to_qm31 = |v: &[M31]| {
+            assert_eq!(v.len() % 4, 0);
+            v.chunks_exact(4)
+                .map(|c| QM31::from_m31(c[0], c[1], c[2], c[3]))
+                .collect::<Vec<QM31>>()
+        };
+        (to_qm31(&self.left), to_qm31(&self.right))
     }
 
     /// Verify a Merkle tree proof.
-    pub fn verify(&self, root_hash: &Sha256Hash, logn: usize, mut query: usize) -> bool {
+    pub fn verify(&self, root_hash: &H::Hash, logn: usize, mut query: usize) -> bool {
         assert_eq!(query & 1, 0);
 
-        let left_hash = Sha256MerkleHasher::hash_node(None, &self.left);
-        let right_hash = Sha256MerkleHasher::hash_node(None, &self.right);
+        let left_hash = H::hash_node(None, &self.left);
+        let right_hash = H::hash_node(None, &self.right);
 
-        let leaf_hash = Sha256MerkleHasher::hash_node(Some((left_hash, right_hash)), &[]);
+        let leaf_hash = H::hash_node(Some((left_hash, right_hash)), &[]);
         query >>= 1;
 
         self.path.verify(root_hash, logn - 1, leaf_hash, query)
     }
 
+    /// Verify a Merkle tree proof against a tree that has been padded from `original_len` leaves.
+    ///
+    /// A padded tree has at least two original leaves, so smaller lengths are rejected.
+    pub fn verify_padded(&self, root_hash: &H::Hash, original_len: usize, query: usize) -> bool {
+        if original_len < 2 || query >= original_len {
+            return false;
+        }
+
+        // the right leaf is the padding leaf if it lies beyond the original leaves
+        if (query | 1) >= original_len && !self.right.is_empty() {
+            return false;
+        }
+
+        let logn = original_len.next_power_of_two().trailing_zeros() as usize;
+        self.verify(root_hash, logn, query)
+    }
+
     /// Convert a stwo Merkle proof into twin proofs for each pairs of queries.
+    ///
+    /// The twin proofs carry whole leaves, so a decommitment with a non-empty column witness is
+    /// rejected as `WitnessTooLong`, and so is one with unused hash witness.
     pub fn from_stwo_proof(
         logn: usize,
         queries_parents: &[usize],
         values: &[Vec<BaseField>],
-        merkle_decommitment: &MerkleDecommitment<Sha256MerkleHasher>,
-    ) -> Vec<Self> {
+        merkle_decommitment: &MerkleDecommitment<H>,
+    ) -> Result<Vec<Self>, MerkleVerificationError> {
         // find out all the queried positions and sort them
         let mut queries = vec![];
         for &queries_parent in queries_parents.iter() {
@@ -185,18 +354,20 @@ impl MerkleTreeTwinProof {
         }
 
         // require the column witness to be empty
-        assert!(merkle_decommitment.column_witness.is_empty());
+        if !merkle_decommitment.column_witness.is_empty() {
+            return Err(MerkleVerificationError::WitnessTooLong);
+        }
 
         // turn hash witness into an iterator
         let mut hash_iterator = merkle_decommitment.hash_witness.iter();
 
         // create the merkle partial tree
-        let mut layers: Vec<HashMap<usize, Sha256Hash>> = vec![];
+        let mut layers: Vec<HashMap<usize, H::Hash>> = vec![];
 
         // create the leaf layer
         let mut layer = HashMap::new();
         for (&query, value) in queries_values_map.iter() {
-            layer.insert(query, Sha256MerkleHasher::hash_node(None, value));
+            layer.insert(query, H::hash_node(None, value));
         }
         layers.push(layer);
 
@@ -211,7 +382,7 @@ impl MerkleTreeTwinProof {
             for &position in positions.iter() {
                 layer.insert(
                     position,
-                    Sha256MerkleHasher::hash_node(
+                    H::hash_node(
                         Some((
                             *layers[i].get(&(position << 1)).unwrap(),
                             *layers[i].get(&((position << 1) + 1)).unwrap(),
@@ -221,7 +392,10 @@ impl MerkleTreeTwinProof {
                 );
 
                 if !positions.contains(&(position ^ 1)) && !layer.contains_key(&(position ^ 1)) {
-                    layer.insert(position ^ 1, *hash_iterator.next().unwrap());
+                    let sibling = hash_iterator
+                        .next()
+                        .ok_or(MerkleVerificationError::WitnessTooShort)?;
+                    layer.insert(position ^ 1, *sibling);
                 }
                 parents.insert(position >> 1);
             }
@@ -230,7 +404,9 @@ impl MerkleTreeTwinProof {
             positions = parents.iter().copied().collect::<Vec<usize>>();
         }
 
-        assert_eq!(hash_iterator.next(), None);
+        if hash_iterator.next().is_some() {
+            return Err(MerkleVerificationError::WitnessTooLong);
+        }
 
         // cheery-pick the Merkle tree paths to construct the deterministic proofs
         let mut res = vec![];
@@ -255,13 +431,14 @@ impl MerkleTreeTwinProof {
                 path: MerkleTreePath { siblings },
             });
         }
-        res
+        Ok(res)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::merkle_tree::{MerkleTree, MerkleTreeTwinProof};
+    use crate::merkle_tree::{MerkleTree, MerkleTreePath, MerkleTreeTwinProof};
+    use crate::tests_utils::pushable::assert_pushable_eq;
     use crate::utils::get_rand_qm31;
     use itertools::Itertools;
     use rand::{Rng, RngCore, SeedableRng};
@@ -269,8 +446,10 @@ mod test {
     use std::collections::BTreeMap;
     use stwo_prover::core::backend::CpuBackend;
     use stwo_prover::core::fields::m31::BaseField;
+    use stwo_prover::core::vcs::blake2_merkle::Blake2sMerkleHasher;
     use stwo_prover::core::vcs::prover::MerkleProver;
     use stwo_prover::core::vcs::sha256_merkle::Sha256MerkleHasher;
+    use stwo_prover::core::vcs::verifier::MerkleVerificationError;
 
     #[test]
     fn test_merkle_tree() {
@@ -292,9 +471,189 @@ mod test {
 
             let proof = MerkleTreeTwinProof::query(&merkle_tree, query);
             assert!(proof.verify(&merkle_tree.root_hash, 12, query));
+            assert_pushable_eq(proof);
         }
     }
 
+    #[test]
+    fn test_layer_accessors() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut last_layer = vec![];
+        for _ in 0..1 << 6 {
+            let a = get_rand_qm31(&mut prng);
+            last_layer.push(a.to_m31_array().to_vec());
+        }
+
+        let merkle_tree = MerkleTree::new(last_layer);
+        assert_eq!(merkle_tree.depth(), 6);
+        assert_eq!(merkle_tree.depth(), merkle_tree.logn());
+        assert_eq!(merkle_tree.root(), &merkle_tree.root_hash);
+
+        for depth in 0..6 {
+            assert_eq!(merkle_tree.layer(depth).unwrap().len(), 1 << (5 - depth));
+        }
+        assert_eq!(merkle_tree.layer(5).unwrap(), &[merkle_tree.root_hash]);
+        assert!(merkle_tree.layer(6).is_none());
+
+        for pos in 0..1 << 6 {
+            let mut expected = vec![];
+            for i in 0..5 {
+                expected.push(merkle_tree.intermediate_layers[i][(pos >> (i + 1)) ^ 1]);
+            }
+
+            let siblings = merkle_tree.siblings_for(pos).copied().collect::<Vec<_>>();
+            assert_eq!(siblings, expected);
+            assert_eq!(siblings, MerkleTreePath::query(&merkle_tree, pos).siblings);
+        }
+    }
+
+    #[test]
+    fn test_twin_proof_new() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut last_layer = vec![];
+        for _ in 0..1 << 6 {
+            let a = get_rand_qm31(&mut prng);
+            last_layer.push(a.to_m31_array().to_vec());
+        }
+        let merkle_tree = MerkleTree::new(last_layer);
+
+        let expected = MerkleTreeTwinProof::query(&merkle_tree, 10);
+        let proof = MerkleTreeTwinProof::<Sha256MerkleHasher>::new(
+            expected.left.clone(),
+            expected.right.clone(),
+            expected.path.siblings.clone(),
+            6,
+        )
+        .unwrap();
+        assert!(proof.verify(&merkle_tree.root_hash, 6, 10));
+
+        // the leaf and its sibling have different widths
+        let res = MerkleTreeTwinProof::<Sha256MerkleHasher>::new(
+            expected.left.clone(),
+            expected.right[..3].to_vec(),
+            expected.path.siblings.clone(),
+            6,
+        );
+        assert!(res.is_err());
+
+        // the path is one sibling short
+        let res = MerkleTreeTwinProof::<Sha256MerkleHasher>::new(
+            expected.left.clone(),
+            expected.right.clone(),
+            expected.path.siblings[1..].to_vec(),
+            6,
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_merkle_tree_qm31() {
+        const LOG_SIZE: usize = 10;
+        const N_QM31: usize = 3;
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let qm31_layer = (0..1 << LOG_SIZE)
+            .map(|_| (0..N_QM31).map(|_| get_rand_qm31(&mut prng)).collect())
+            .collect::<Vec<Vec<_>>>();
+        let qm31_tree = MerkleTree::new_qm31(qm31_layer.clone());
+
+        // commit the same values in stwo, as four coordinate columns for each qm31 column
+        let mut columns = vec![];
+        for i in 0..N_QM31 {
+            for coordinate in 0..4 {
+                columns.push(
+                    qm31_layer
+                        .iter()
+                        .map(|leaf| leaf[i].to_m31_array()[coordinate])
+                        .collect::<Vec<BaseField>>(),
+                );
+            }
+        }
+        let prover = MerkleProver::<CpuBackend, Sha256MerkleHasher>::commit(
+            columns.iter().collect::<Vec<&Vec<BaseField>>>(),
+        );
+        assert_eq!(qm31_tree.root_hash, prover.root());
+
+        let query = 6;
+        let proof = MerkleTreeTwinProof::query(&qm31_tree, query);
+        assert!(proof.verify(&prover.root(), LOG_SIZE, query));
+
+        let (left, right) = proof.qm31_leaves();
+        assert_eq!(left, qm31_layer[query]);
+        assert_eq!(right, qm31_layer[query + 1]);
+    }
+
+    #[test]
+    fn test_verify_leaf() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut last_layer = vec![];
+        for _ in 0..1 << 12 {
+            let a = get_rand_qm31(&mut prng);
+            last_layer.push(a.to_m31_array().to_vec());
+        }
+
+        let merkle_tree = MerkleTree::new(last_layer.clone());
+
+        for _ in 0..10 {
+            let query = (prng.gen::<u32>() % (1 << 12)) as usize;
+
+            let path = MerkleTreePath::query_leaf(&merkle_tree, query);
+            assert!(MerkleTree::verify_leaf(
+                &merkle_tree.root_hash,
+                12,
+                &last_layer[query],
+                &path,
+                query
+            ));
+            assert!(!MerkleTree::verify_leaf(
+                &merkle_tree.root_hash,
+                12,
+                &last_layer[query ^ 1],
+                &path,
+                query
+            ));
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_padded() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut last_layer = vec![];
+        for _ in 0..3000 {
+            let a = get_rand_qm31(&mut prng);
+            last_layer.push(a.to_m31_array().to_vec());
+        }
+
+        let merkle_tree = MerkleTree::new_padded(last_layer.clone());
+        assert_eq!(merkle_tree.original_len, 3000);
+        assert_eq!(merkle_tree.leaf_layer.len(), 4096);
+        assert_eq!(merkle_tree.logn(), 12);
+
+        for _ in 0..10 {
+            let mut query = (prng.gen::<u32>() % 3000) as usize;
+            if query & 1 != 0 {
+                query ^= 1;
+            }
+
+            let proof = MerkleTreeTwinProof::query(&merkle_tree, query);
+            assert_eq!(proof.left, last_layer[query]);
+            assert!(proof.verify_padded(&merkle_tree.root_hash, 3000, query));
+        }
+
+        // the padding leaves are not part of the original leaves
+        let proof = MerkleTreeTwinProof::query(&merkle_tree, 2998);
+        assert!(!proof.verify_padded(&merkle_tree.root_hash, 3000, 3000));
+
+        // a padded tree never has fewer than two original leaves
+        let proof = MerkleTreeTwinProof::query(&merkle_tree, 0);
+        assert!(!proof.verify_padded(&merkle_tree.root_hash, 1, 0));
+        assert!(!proof.verify_padded(&merkle_tree.root_hash, 0, 0));
+    }
+
     #[test]
     fn test_from_stwo_proof() {
         const LOG_SIZE: usize = 12;
@@ -334,10 +693,132 @@ mod test {
             );
 
             let proofs =
-                MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &decommitment);
+                MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &decommitment)
+                    .unwrap();
+            for (&query, proof) in queries.iter().zip(proofs.iter()) {
+                assert!(proof.verify(&prover.root(), LOG_SIZE, query << 1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_stwo_proof_malformed() {
+        const LOG_SIZE: usize = 8;
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut polynomials = vec![];
+        for _ in 0..4 {
+            let mut polynomial = vec![];
+            for _ in 0..(1 << LOG_SIZE) {
+                polynomial.push(BaseField::reduce(prng.next_u64()));
+            }
+            polynomials.push(polynomial);
+        }
+
+        let polynomials_ref = polynomials.iter().collect::<Vec<&Vec<BaseField>>>();
+
+        let prover =
+            MerkleProver::<CpuBackend, Sha256MerkleHasher>::commit(polynomials_ref.clone());
+
+        let queries = vec![3, 40, 77];
+
+        let (values, decommitment) = prover.decommit(
+            BTreeMap::from([(
+                LOG_SIZE as u32,
+                queries
+                    .iter()
+                    .flat_map(|&x| [x << 1, (x << 1) + 1])
+                    .collect::<Vec<usize>>(),
+            )]),
+            polynomials_ref,
+        );
+
+        // a column witness is not supported
+        let mut with_column_witness = decommitment.clone();
+        with_column_witness
+            .column_witness
+            .push(BaseField::reduce(prng.next_u64()));
+        assert!(matches!(
+            MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &with_column_witness),
+            Err(MerkleVerificationError::WitnessTooLong)
+        ));
+
+        // missing hash witness
+        let mut short = decommitment.clone();
+        short.hash_witness.pop();
+        assert!(matches!(
+            MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &short),
+            Err(MerkleVerificationError::WitnessTooShort)
+        ));
+
+        // extra hash witness
+        let mut long = decommitment.clone();
+        long.hash_witness.push(prover.root());
+        assert!(matches!(
+            MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &long),
+            Err(MerkleVerificationError::WitnessTooLong)
+        ));
+
+        assert!(
+            MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &decommitment)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_from_stwo_proof_blake2s() {
+        const LOG_SIZE: usize = 12;
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let mut polynomials = vec![];
+            for _ in 0..4 {
+                let mut polynomial = vec![];
+                for _ in 0..(1 << LOG_SIZE) {
+                    polynomial.push(BaseField::reduce(prng.next_u64()));
+                }
+                polynomials.push(polynomial);
+            }
+
+            let polynomials_ref = polynomials.iter().collect::<Vec<&Vec<BaseField>>>();
+
+            let prover =
+                MerkleProver::<CpuBackend, Blake2sMerkleHasher>::commit(polynomials_ref.clone());
+
+            let queries = (0..20)
+                .map(|_| prng.gen::<usize>() % (1 << LOG_SIZE))
+                .map(|x| x >> 1)
+                .collect::<Vec<usize>>();
+
+            let (values, decommitment) = prover.decommit(
+                BTreeMap::from([(
+                    LOG_SIZE as u32,
+                    queries
+                        .iter()
+                        .sorted()
+                        .dedup()
+                        .flat_map(|&x| [x << 1, (x << 1) + 1])
+                        .collect::<Vec<usize>>(),
+                )]),
+                polynomials_ref,
+            );
+
+            let proofs =
+                MerkleTreeTwinProof::from_stwo_proof(LOG_SIZE, &queries, &values, &decommitment)
+                    .unwrap();
             for (&query, proof) in queries.iter().zip(proofs.iter()) {
                 assert!(proof.verify(&prover.root(), LOG_SIZE, query << 1));
             }
         }
+
+        // a Blake2s tree built locally matches the proofs it produces
+        let mut last_layer = vec![];
+        for _ in 0..1 << 8 {
+            let a = get_rand_qm31(&mut prng);
+            last_layer.push(a.to_m31_array().to_vec());
+        }
+        let merkle_tree = MerkleTree::<Blake2sMerkleHasher>::new_with_hasher(last_layer);
+        let proof = MerkleTreeTwinProof::query(&merkle_tree, 6);
+        assert!(proof.verify(&merkle_tree.root_hash, 8, 6));
     }
 }
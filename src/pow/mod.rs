@@ -58,3 +58,26 @@ impl Pushable for PoWHint {
         builder
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::pow::PoWHint;
+    use crate::tests_utils::pushable::assert_pushable_eq;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::vcs::sha256_hash::Sha256Hash;
+
+    #[test]
+    fn test_pow_hint_pushable() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut channel_digest = [0u8; 32];
+        prng.fill(&mut channel_digest);
+        let channel_digest = Sha256Hash::from(channel_digest.as_slice());
+
+        for n_bits in [8, 10] {
+            let hint = PoWHint::new(channel_digest, prng.gen(), n_bits);
+            assert_pushable_eq(hint);
+        }
+    }
+}
@@ -1,9 +1,14 @@
+use crate::precomputed_merkle_tree::PrecomputedMerkleTree;
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
 
 /// The roots of the precomputed Merkle trees.
 pub static PRECOMPUTED_MERKLE_TREE_ROOTS: OnceLock<HashMap<u32, [u8; 32]>> = OnceLock::new();
 
+/// The roots of the precomputed Merkle trees that are computed on demand by `ensure_roots`.
+static ON_DEMAND_MERKLE_TREE_ROOTS: OnceLock<Mutex<HashMap<u32, [u8; 32]>>> = OnceLock::new();
+
 /// Initialize the precomputed Merkle tree roots.
 pub fn get_precomputed_merkle_tree_roots() -> HashMap<u32, [u8; 32]> {
     let mut res = HashMap::new();
@@ -117,6 +122,55 @@ pub fn get_precomputed_merkle_tree_roots() -> HashMap<u32, [u8; 32]> {
     res
 }
 
+/// Make sure that the roots of the precomputed Merkle trees of sizes `2^logn` for all the given
+/// `logn` are available, computing the missing ones.
+pub fn ensure_roots(sizes: &[u32]) {
+    let precomputed = PRECOMPUTED_MERKLE_TREE_ROOTS.get_or_init(get_precomputed_merkle_tree_roots);
+    let on_demand = ON_DEMAND_MERKLE_TREE_ROOTS.get_or_init(|| Mutex::new(HashMap::new()));
+
+    let missing = {
+        let on_demand = on_demand.lock().unwrap();
+        sizes
+            .iter()
+            .copied()
+            .filter(|logn| !precomputed.contains_key(logn) && !on_demand.contains_key(logn))
+            .collect::<Vec<u32>>()
+    };
+
+    // build the trees without holding the lock, as it can take a while for large sizes
+    let roots = missing
+        .into_iter()
+        .map(|logn| (logn, PrecomputedMerkleTree::new(logn as usize).root_hash))
+        .collect::<Vec<_>>();
+
+    let mut on_demand = on_demand.lock().unwrap();
+    for (logn, root) in roots {
+        on_demand.entry(logn).or_insert(root);
+    }
+}
+
+/// Get the root of the precomputed Merkle tree of size `2^logn`, failing with an error naming the
+/// size if its root has not been precomputed (or computed by `ensure_roots`).
+pub fn require_root(logn: u32) -> Result<[u8; 32]> {
+    let precomputed = PRECOMPUTED_MERKLE_TREE_ROOTS.get_or_init(get_precomputed_merkle_tree_roots);
+    if let Some(root) = precomputed.get(&logn) {
+        return Ok(*root);
+    }
+
+    ON_DEMAND_MERKLE_TREE_ROOTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap()
+        .get(&logn)
+        .copied()
+        .ok_or_else(|| {
+            anyhow!(
+                "the root of the precomputed Merkle tree of size 2^{} has not been precomputed",
+                logn
+            )
+        })
+}
+
 #[cfg(test)]
 mod test {
     use crate::precomputed_merkle_tree::*;
@@ -133,4 +187,27 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_require_root() {
+        let roots = PRECOMPUTED_MERKLE_TREE_ROOTS.get_or_init(get_precomputed_merkle_tree_roots);
+        assert_eq!(require_root(15).unwrap(), *roots.get(&15).unwrap());
+
+        let err = require_root(40).unwrap_err();
+        assert!(err.to_string().contains("2^40"));
+    }
+
+    #[test]
+    fn test_ensure_roots() {
+        ensure_roots(&[25]);
+        let roots = PRECOMPUTED_MERKLE_TREE_ROOTS.get_or_init(get_precomputed_merkle_tree_roots);
+        assert_eq!(require_root(25).unwrap(), *roots.get(&25).unwrap());
+
+        assert!(require_root(10).is_err());
+        ensure_roots(&[10]);
+        assert_eq!(
+            require_root(10).unwrap(),
+            PrecomputedMerkleTree::new(10).root_hash
+        );
+    }
 }
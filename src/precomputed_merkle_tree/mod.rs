@@ -1,9 +1,11 @@
 use crate::treepp::pushable::*;
 use crate::utils::{bit_reverse_index, get_twiddles};
 use crate::utils::{hash_m31_vec, num_to_bytes};
+use anyhow::{bail, Result};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use stwo_prover::core::circle::CirclePoint;
-use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::m31::{M31, P};
 use stwo_prover::core::fields::FieldExpOps;
 use stwo_prover::core::poly::circle::CanonicCoset;
 
@@ -128,6 +130,24 @@ impl PrecomputedMerkleTree {
         }
     }
 
+    /// Query the twiddle Merkle tree at multiple positions and generate a proof for each of them.
+    ///
+    /// Twin positions share the same path, so the proof for each distinct `pos >> 1` is only
+    /// computed once.
+    pub fn query_batch(&self, positions: &[usize]) -> Vec<PrecomputedMerkleTreeProof> {
+        let mut cache = BTreeMap::<usize, PrecomputedMerkleTreeProof>::new();
+
+        positions
+            .iter()
+            .map(|&pos| {
+                cache
+                    .entry(pos >> 1)
+                    .or_insert_with(|| self.query(pos))
+                    .clone()
+            })
+            .collect()
+    }
+
     /// Verify a twiddle Merkle tree proof.
     pub fn verify(
         root_hash: [u8; 32],
@@ -185,6 +205,79 @@ pub struct PrecomputedMerkleTreeProof {
     pub siblings: Vec<[u8; 32]>,
 }
 
+impl PrecomputedMerkleTreeProof {
+    /// Serialize the proof into bytes.
+    ///
+    /// The layout is the circle point's x and y, the number of twiddle factors, the twiddle
+    /// factors, and then the siblings, where all the numbers are 4-byte little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.circle_point.x.0.to_le_bytes());
+        bytes.extend_from_slice(&self.circle_point.y.0.to_le_bytes());
+        bytes.extend_from_slice(&(self.twiddles_elements.len() as u32).to_le_bytes());
+        for element in self.twiddles_elements.iter() {
+            bytes.extend_from_slice(&element.0.to_le_bytes());
+        }
+        for sibling in self.siblings.iter() {
+            bytes.extend_from_slice(sibling);
+        }
+        bytes
+    }
+
+    /// Deserialize the proof from bytes, which requires one sibling per twiddle factor.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = bytes;
+
+        let x = read_m31(&mut reader)?;
+        let y = read_m31(&mut reader)?;
+
+        // each twiddle factor takes 4 bytes and each sibling takes 32 bytes, which is checked
+        // before allocating so that a forged count cannot cause a huge allocation
+        let n = read_u32(&mut reader)? as usize;
+        if n.checked_mul(4 + 32) != Some(reader.len()) {
+            bail!(
+                "expected {} twiddle factors and {} siblings, but got {} bytes",
+                n,
+                n,
+                reader.len()
+            );
+        }
+
+        let mut twiddles_elements = Vec::with_capacity(n);
+        for _ in 0..n {
+            twiddles_elements.push(read_m31(&mut reader)?);
+        }
+
+        let siblings = reader
+            .chunks_exact(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect::<Vec<[u8; 32]>>();
+
+        Ok(Self {
+            circle_point: CirclePoint { x, y },
+            twiddles_elements,
+            siblings,
+        })
+    }
+}
+
+fn read_u32(reader: &mut &[u8]) -> Result<u32> {
+    if reader.len() < 4 {
+        bail!("unexpected end of the precomputed Merkle tree proof");
+    }
+    let (head, tail) = reader.split_at(4);
+    *reader = tail;
+    Ok(u32::from_le_bytes(head.try_into()?))
+}
+
+fn read_m31(reader: &mut &[u8]) -> Result<M31> {
+    let v = read_u32(reader)?;
+    if v >= P {
+        bail!("{} is not a valid m31 element", v);
+    }
+    Ok(M31::from_u32_unchecked(v))
+}
+
 impl Pushable for PrecomputedMerkleTreeProof {
     fn bitcoin_script_push(&self, mut builder: Builder) -> Builder {
         builder = self.circle_point.x.bitcoin_script_push(builder);
@@ -214,11 +307,12 @@ impl Pushable for PrecomputedMerkleTreeProof {
 
 #[cfg(test)]
 mod test {
-    use crate::precomputed_merkle_tree::PrecomputedMerkleTree;
+    use crate::precomputed_merkle_tree::{PrecomputedMerkleTree, PrecomputedMerkleTreeProof};
     use crate::utils::bit_reverse_index;
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
     use std::ops::Neg;
+    use stwo_prover::core::fields::m31::P;
     use stwo_prover::core::poly::circle::CanonicCoset;
 
     #[test]
@@ -240,6 +334,75 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_query_batch() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let precomputed_merkle_tree = PrecomputedMerkleTree::new(20);
+
+        let mut queries = (0..16)
+            .map(|_| (prng.gen::<u32>() % (1 << 21)) as usize)
+            .collect::<Vec<_>>();
+        // include a twin and a repeated position
+        queries.push(queries[0] ^ 1);
+        queries.push(queries[1]);
+
+        let proofs = precomputed_merkle_tree.query_batch(&queries);
+        assert_eq!(proofs.len(), queries.len());
+
+        for (&query, proof) in queries.iter().zip(proofs.iter()) {
+            let expected = precomputed_merkle_tree.query(query);
+            assert_eq!(proof.circle_point, expected.circle_point);
+            assert_eq!(proof.twiddles_elements, expected.twiddles_elements);
+            assert_eq!(proof.siblings, expected.siblings);
+
+            assert!(PrecomputedMerkleTree::verify(
+                precomputed_merkle_tree.root_hash,
+                20,
+                proof,
+                query
+            ));
+        }
+    }
+
+    #[test]
+    fn test_proof_serialization() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let precomputed_merkle_tree = PrecomputedMerkleTree::new(20);
+
+        let query = (prng.gen::<u32>() % (1 << 21)) as usize;
+        let proof = precomputed_merkle_tree.query(query);
+
+        let bytes = proof.to_bytes();
+        let decoded = PrecomputedMerkleTreeProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.circle_point, proof.circle_point);
+        assert_eq!(decoded.twiddles_elements, proof.twiddles_elements);
+        assert_eq!(decoded.siblings, proof.siblings);
+        assert!(PrecomputedMerkleTree::verify(
+            precomputed_merkle_tree.root_hash,
+            20,
+            &decoded,
+            query
+        ));
+
+        // one sibling fewer than the twiddle factors
+        let mut wrong_proof = proof.clone();
+        wrong_proof.siblings.pop();
+        assert!(PrecomputedMerkleTreeProof::from_bytes(&wrong_proof.to_bytes()).is_err());
+
+        // truncated bytes
+        assert!(PrecomputedMerkleTreeProof::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // a huge count of twiddle factors is rejected before allocating
+        for n in [u32::MAX, P - 1] {
+            let mut huge = bytes[..8].to_vec();
+            huge.extend_from_slice(&n.to_le_bytes());
+            huge.extend_from_slice(&bytes[12..]);
+            assert!(PrecomputedMerkleTreeProof::from_bytes(&huge).is_err());
+        }
+    }
+
     #[test]
     fn test_consistency() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -0,0 +1,50 @@
+use bitcoin_scriptexec::ExecutionResult;
+
+/// Explain why a script failed, using the error and the opcode at which the execution stopped.
+pub fn explain_failure(result: &ExecutionResult) -> String {
+    if result.success {
+        return "the script succeeded".to_string();
+    }
+
+    let error = match &result.error {
+        Some(err) => format!("{:?}", err),
+        None => "the final stack is not a single true element".to_string(),
+    };
+    let opcode = match &result.opcode {
+        Some(opcode) => format!("{}", opcode),
+        None => "the end of the script".to_string(),
+    };
+
+    format!(
+        "the script failed at {} with {}, leaving {} elements on the stack",
+        opcode,
+        error,
+        result.final_stack.len()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tests_utils::explain::explain_failure;
+    use crate::treepp::*;
+
+    #[test]
+    fn test_explain_failure() {
+        let script = script! {
+            1 2 OP_EQUALVERIFY
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+
+        let explanation = explain_failure(&exec_result);
+        assert!(explanation.contains("OP_EQUALVERIFY"));
+
+        let script = script! {
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+        assert_eq!(explain_failure(&exec_result), "the script succeeded");
+    }
+}
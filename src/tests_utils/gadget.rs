@@ -0,0 +1,160 @@
+use crate::dsl::verifier::{hints_to_witness, Witness};
+use crate::treepp::*;
+use bitcoin::script::read_scriptint;
+use bitcoin_script_dsl::compiler::Compiler;
+use bitcoin_script_dsl::constraint_system::{ConstraintSystemRef, Element};
+use stwo_prover::core::fields::m31::M31;
+
+/// The result of running a gadget with an explicit witness.
+#[derive(Debug, Clone)]
+pub struct GadgetResult {
+    /// Whether the script succeeds.
+    pub success: bool,
+    /// The stack after the execution, with the top element last.
+    pub final_stack: Vec<Vec<u8>>,
+    /// The size of the gadget script, excluding the witness.
+    pub script_size: usize,
+}
+
+impl GadgetResult {
+    /// Decode the `depth`-th element from the top of the final stack as an M31 element.
+    pub fn stack_m31(&self, depth: usize) -> M31 {
+        let elem = &self.final_stack[self.final_stack.len() - 1 - depth];
+        M31::from(read_scriptint(elem).unwrap() as u32)
+    }
+
+    /// Return the `depth`-th element from the top of the final stack as raw bytes.
+    pub fn stack_bytes(&self, depth: usize) -> &[u8] {
+        &self.final_stack[self.final_stack.len() - 1 - depth]
+    }
+}
+
+/// Run a gadget with the witness given as a script of pushes.
+pub fn run_gadget(script: Script, witness_script: Script) -> GadgetResult {
+    run_gadget_with_witness(script, convert_to_witness(witness_script).unwrap())
+}
+
+/// Run a gadget with the given witness.
+pub fn run_gadget_with_witness(script: Script, witness: Witness) -> GadgetResult {
+    let script_size = script.len();
+    let exec_result = execute_verifier(script, witness);
+
+    GadgetResult {
+        success: exec_result.success,
+        final_stack: exec_result.final_stack,
+        script_size,
+    }
+}
+
+/// Compile a constraint system and run its script with the hints computed by the compiler, after
+/// `tamper` has modified them.
+///
+/// `OP_TRUE` is appended to the script, so a program without outputs succeeds if and only if
+/// none of its checks fails.
+pub fn run_program_with_tampered_hints(
+    cs: ConstraintSystemRef,
+    tamper: impl FnOnce(&mut [Element]),
+) -> GadgetResult {
+    let program = Compiler::compile(cs).unwrap();
+
+    let mut hints = program.hint;
+    tamper(&mut hints);
+
+    run_gadget_with_witness(
+        script! {
+            { program.script }
+            OP_TRUE
+        },
+        hints_to_witness(&hints),
+    )
+}
+
+/// Find the index at which the m31 values `values` appear as consecutive hints.
+///
+/// Panics unless they appear exactly once, so that a tampered hint cannot be a different element
+/// that happens to carry the same value.
+pub fn hint_index(hints: &[Element], values: &[M31]) -> usize {
+    let mut matches = hints
+        .windows(values.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(values.iter())
+                .all(|(entry, value)| matches!(entry, Element::Num(v) if *v as u32 == value.0))
+        });
+
+    let (index, _) = matches.next().expect("the values are not in the hints");
+    assert!(
+        matches.next().is_none(),
+        "the values appear more than once in the hints"
+    );
+    index
+}
+
+/// Check that the program built by `build` accepts its own hints, and rejects them once the
+/// consecutive m31 hints `from` are replaced with `to`.
+pub fn assert_rejects_tampered_hints(
+    build: impl Fn() -> ConstraintSystemRef,
+    from: &[M31],
+    to: &[M31],
+) {
+    assert_eq!(from.len(), to.len());
+    assert!(run_program_with_tampered_hints(build(), |_| {}).success);
+
+    let res = run_program_with_tampered_hints(build(), |hints| {
+        let index = hint_index(hints, from);
+        for (entry, value) in hints[index..index + to.len()].iter_mut().zip(to.iter()) {
+            *entry = Element::Num(value.0 as i32);
+        }
+    });
+    assert!(!res.success);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::algorithms::column_line_coeffs::column_line_coeffs;
+    use crate::dsl::primitives::qm31::QM31Var;
+    use crate::dsl::primitives::table::utils::rand_qm31;
+    use crate::dsl::primitives::table::TableVar;
+    use crate::dsl::verifier::hints_to_witness;
+    use crate::tests_utils::gadget::run_gadget_with_witness;
+    use crate::treepp::*;
+    use bitcoin_script_dsl::bvar::{AllocVar, BVar};
+    use bitcoin_script_dsl::compiler::Compiler;
+    use bitcoin_script_dsl::constraint_system::ConstraintSystem;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_run_gadget_column_line_coeffs() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let y = rand_qm31(&mut prng);
+        let eval = rand_qm31(&mut prng);
+
+        let cs = ConstraintSystem::new_ref();
+        let table = TableVar::new_constant(&cs, ()).unwrap();
+        let y_var = QM31Var::new_hint(&cs, y).unwrap();
+        let eval_var = QM31Var::new_hint(&cs, eval).unwrap();
+
+        let res = column_line_coeffs(&table, &y_var, &[eval_var]).unwrap();
+        let expected = res[0].0.value().unwrap();
+        cs.set_program_output(&res[0].0).unwrap();
+
+        let program = Compiler::compile(cs).unwrap();
+
+        let result = run_gadget_with_witness(
+            script! {
+                { program.script }
+                OP_SWAP { expected.1 } OP_EQUALVERIFY
+            },
+            hints_to_witness(&program.hint),
+        );
+
+        assert!(result.success);
+        assert_eq!(result.final_stack.len(), 1);
+        assert_eq!(result.stack_m31(0), expected.0);
+        assert!(result.script_size > 0);
+    }
+}
@@ -1,3 +1,16 @@
 #[cfg(not(tarpaulin_include))]
 /// This module contains functions for reporting test results to a CSV file.
 pub mod report;
+
+/// This module contains a helper for checking `Pushable` implementations.
+pub mod pushable;
+
+/// This module contains a helper for dumping the witness of a hint.
+pub mod witness;
+
+/// This module contains a helper for explaining why a script failed.
+pub mod explain;
+
+/// This module contains a helper for running a gadget with an explicit witness.
+#[cfg(any(test, feature = "exec"))]
+pub mod gadget;
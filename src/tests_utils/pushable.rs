@@ -0,0 +1,9 @@
+//! This module contains a helper for checking `Pushable` implementations.
+use crate::treepp::pushable::{Builder, Pushable};
+use crate::treepp::*;
+
+/// Assert that pushing the value through a `Builder` produces the same bytes as `script!`.
+pub fn assert_pushable_eq<T: Pushable + Clone>(value: T) {
+    let builder = value.clone().bitcoin_script_push(Builder::new());
+    assert_eq!(script! { { value } }.as_bytes(), builder.as_bytes());
+}
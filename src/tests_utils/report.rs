@@ -1,6 +1,7 @@
 //! This module contains functions for reporting test results to a CSV file.
 //!
 //! The CSV file is used to track the size of bitcoin scripts.
+use std::collections::BTreeMap;
 use std::io::{BufRead, Write};
 use std::sync::Mutex;
 use std::{
@@ -47,6 +48,57 @@ pub fn report_bitcoin_script_size(category: &str, name: &str, script_size_bytes:
     writeln!(file, "{},{},{}", category, name, script_size_bytes).unwrap();
 }
 
+/// A collector of script sizes that can be used as a regression guard against script growth.
+#[derive(Default, Clone, Debug)]
+pub struct ScriptSizeReport {
+    /// The recorded sizes, indexed by (module, name).
+    pub entries: BTreeMap<(String, String), usize>,
+}
+
+impl ScriptSizeReport {
+    /// Create an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the size of a script, which is also reported to the CSV file.
+    pub fn record(&mut self, module: &str, name: &str, script_size_bytes: usize) {
+        report_bitcoin_script_size(module, name, script_size_bytes);
+        self.entries
+            .insert((module.to_string(), name.to_string()), script_size_bytes);
+    }
+
+    /// Assert that the script recorded under `name` is at most `max` bytes.
+    pub fn assert_under(&self, name: &str, max: usize) {
+        let matches = self
+            .entries
+            .iter()
+            .filter(|((_, entry_name), _)| entry_name == name)
+            .collect::<Vec<_>>();
+        assert!(!matches.is_empty(), "no script recorded under {}", name);
+
+        for ((module, name), &size) in matches {
+            assert!(
+                size <= max,
+                "{}.{} = {} bytes exceeds the budget of {} bytes",
+                module,
+                name,
+                size,
+                max
+            );
+        }
+    }
+
+    /// Summarize the recorded sizes as a table sorted by module and name.
+    pub fn summary(&self) -> String {
+        let mut res = String::new();
+        for ((module, name), size) in self.entries.iter() {
+            res.push_str(&format!("{:<24} {:<48} {:>10}\n", module, name, size));
+        }
+        res
+    }
+}
+
 // Function to sort the CSV file by the first column
 fn sort_csv_file(file_path: &str) {
     let mut rows: Vec<Vec<String>> = BufReader::new(File::open(file_path).unwrap())
@@ -73,3 +125,37 @@ fn sort_csv_file(file_path: &str) {
         writeln!(file, "{},{},{}", row[0], row[1], row[2]).unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::channel::Sha256ChannelGadget;
+    use crate::tests_utils::report::ScriptSizeReport;
+    use crate::utils::hash_qm31_gadget;
+
+    #[test]
+    fn test_script_size_report() {
+        let mut report = ScriptSizeReport::new();
+        report.record(
+            "Channel",
+            "mix_digest",
+            Sha256ChannelGadget::mix_digest().len(),
+        );
+        report.record("Channel", "mix_felt", Sha256ChannelGadget::mix_felt().len());
+        report.record("Utils", "hash_qm31_gadget", hash_qm31_gadget().len());
+
+        report.assert_under("mix_digest", 10);
+
+        let summary = report.summary();
+        assert_eq!(summary.lines().count(), 3);
+        assert!(summary.lines().next().unwrap().starts_with("Channel"));
+        assert!(summary.lines().last().unwrap().starts_with("Utils"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_script_size_report_over_budget() {
+        let mut report = ScriptSizeReport::new();
+        report.record("Channel", "mix_felt", Sha256ChannelGadget::mix_felt().len());
+        report.assert_under("mix_felt", 1);
+    }
+}
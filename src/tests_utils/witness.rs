@@ -0,0 +1,46 @@
+//! This module contains a helper for inspecting the witness produced by a hint.
+use crate::treepp::pushable::{Builder, Pushable};
+use crate::treepp::*;
+
+/// Push the hints into a script, convert it into a witness, and return each witness element
+/// as a hex string.
+pub fn dump_witness(hints: &impl Pushable) -> Vec<String> {
+    let script = hints.bitcoin_script_push(Builder::new()).into_script();
+    convert_to_witness(script)
+        .unwrap()
+        .iter()
+        .map(hex::encode)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dsl::plonk::hints::Hints;
+    use crate::tests_utils::witness::dump_witness;
+
+    #[test]
+    fn test_dump_witness() {
+        let hints = Hints::instance();
+        let fiat_shamir_hints = &hints.fiat_shamir_hints;
+
+        let proof = &fiat_shamir_hints.merkle_proofs_traces[0];
+        let witness = dump_witness(proof);
+        assert_eq!(
+            witness.len(),
+            proof.left.len() + proof.right.len() + proof.path.siblings.len()
+        );
+        for (elem, sibling) in witness
+            .iter()
+            .skip(proof.left.len() + proof.right.len())
+            .zip(proof.path.siblings.iter())
+        {
+            assert_eq!(*elem, hex::encode(sibling.as_ref()));
+        }
+
+        let pow_hint = &fiat_shamir_hints.pow_hint;
+        let witness = dump_witness(pow_hint);
+        assert_eq!(witness.len(), 2 + pow_hint.msb.is_some() as usize);
+        assert_eq!(witness[0], hex::encode(pow_hint.nonce.to_le_bytes()));
+        assert_eq!(witness[1], hex::encode(&pow_hint.prefix));
+    }
+}
@@ -1,8 +1,14 @@
 use crate::treepp::*;
 use crate::OP_HINT;
 use bitcoin_scriptexec::{profiler_end, profiler_start};
+use rust_bitcoin_m31::{
+    cm31_equalverify, m31_add, m31_mul, m31_neg, qm31_equalverify, qm31_mul, MOD,
+};
 use sha2::{Digest, Sha256};
 use std::cmp::min;
+use stwo_prover::core::fields::cm31::CM31;
+use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::poly::circle::CanonicCoset;
 
 /// Call the selected hash function.
 pub fn hash() -> Script {
@@ -11,6 +17,32 @@ pub fn hash() -> Script {
     }
 }
 
+/// Call the selected hash function, after checking that the preimage is exactly
+/// `expected_input_len` bytes long, which rejects malformed witnesses when a fixed-width preimage
+/// (e.g., the 64-byte concatenation of two child nodes) is expected.
+pub fn hash_checked(expected_input_len: usize) -> Script {
+    script! {
+        OP_SIZE { expected_input_len } OP_EQUALVERIFY
+        hash
+    }
+}
+
+/// Gadget for checking that a byte string is the minimal encoding of an m31 element, as produced by
+/// `num_to_bytes`, i.e., a nonnegative number below the modulus without a superfluous zero byte.
+///
+/// Input:
+/// - the encoding
+///
+/// Output:
+/// - the encoding
+pub fn assert_minimal_encoding_gadget() -> Script {
+    script! {
+        // the sum is always minimally encoded
+        OP_DUP OP_DUP 0 OP_ADD OP_EQUALVERIFY
+        OP_DUP 0 { MOD } OP_WITHIN OP_VERIFY
+    }
+}
+
 /// Gadget for trimming away a m31 element to keep only logn bits.
 pub fn trim_m31_gadget(logn: usize) -> Script {
     if logn == 31 {
@@ -32,6 +64,18 @@ pub fn trim_m31_gadget(logn: usize) -> Script {
     }
 }
 
+/// Push the step point of the canonic coset of size `2^log_size`, i.e., the trace step.
+///
+/// Output:
+/// - step.x, step.y
+pub fn push_canonic_coset_step(log_size: u32) -> Script {
+    let step = CanonicCoset::new(log_size).step();
+    script! {
+        { step.x }
+        { step.y }
+    }
+}
+
 /// Convert the column representation back to the field element.
 ///
 /// Input:
@@ -47,6 +91,113 @@ pub fn qm31_reverse() -> Script {
     }
 }
 
+/// Select one of two qm31 elements based on a bit.
+///
+/// Input:
+/// - a (4 elements), b (4 elements), bit
+///
+/// Output:
+/// - b if bit = 1, a if bit = 0
+pub fn qm31_select_gadget() -> Script {
+    script! {
+        OP_IF
+            OP_2ROT OP_2DROP
+            OP_2ROT OP_2DROP
+        OP_ELSE
+            OP_2DROP OP_2DROP
+        OP_ENDIF
+    }
+}
+
+/// Verify that the hinted qm31 element is a square root of the given value. Not every qm31
+/// element is a square, so the root is only checked, not computed.
+///
+/// Hint:
+/// - r
+///
+/// Input:
+/// - value
+///
+/// Output:
+/// - r, where r * r = value
+pub fn qm31_sqrt_with_hint_gadget() -> Script {
+    script! {
+        { m31_vec_from_bottom_gadget(4) }
+        { dup_m31_vec_gadget(4) }
+        qm31_mul
+
+        // stack: value, r, r * r
+        for _ in 0..4 {
+            11 OP_ROLL
+        }
+        qm31_equalverify
+    }
+}
+
+/// Compute the complex conjugate of a cm31 element.
+///
+/// Input:
+/// - a.imag, a.real
+///
+/// Output:
+/// - -a.imag, a.real
+pub fn cm31_conjugate_gadget() -> Script {
+    script! {
+        OP_SWAP
+        m31_neg
+        OP_SWAP
+    }
+}
+
+/// Compute the norm of a cm31 element, which is an m31 element.
+///
+/// Input:
+/// - a.imag, a.real
+///
+/// Output:
+/// - a.real^2 + a.imag^2
+///
+/// This uses the plain `m31_mul` rather than the table multiplier in `dsl::primitives::table`,
+/// because the table multiplier needs the 8-bit lookup table and the limb hints on the stack,
+/// which none of the stack-only gadgets in this module assume. Its script size is reported by
+/// `test_cm31_norm`.
+pub fn cm31_norm_gadget() -> Script {
+    script! {
+        OP_DUP m31_mul
+        OP_SWAP
+        OP_DUP m31_mul
+        m31_add
+    }
+}
+
+/// Verify that the cm31 element on the stack equals a constant.
+///
+/// Input:
+/// - a.imag, a.real
+///
+/// Output:
+/// - (none)
+pub fn cm31_equalverify_const(c: CM31) -> Script {
+    script! {
+        { c }
+        cm31_equalverify
+    }
+}
+
+/// Verify that the qm31 element on the stack equals a constant.
+///
+/// Input:
+/// - a (qm31, four elements)
+///
+/// Output:
+/// - (none)
+pub fn qm31_equalverify_const(c: QM31) -> Script {
+    script! {
+        { c }
+        qm31_equalverify
+    }
+}
+
 /// Copy some stack elements to the altstack, where the stack top is being inserted first.
 pub fn copy_to_altstack_top_item_first_in_gadget(n: usize) -> Script {
     script! {
@@ -255,14 +406,22 @@ pub fn limb_to_be_bits_toaltstack_except_lowest_2bits(num_bits: u32) -> Script {
 
 #[cfg(test)]
 mod test {
+    use crate::tests_utils::report::report_bitcoin_script_size;
     use crate::treepp::*;
     use crate::utils::{
-        dup_m31_vec_gadget, get_rand_qm31, hash_m31_vec, hash_m31_vec_gadget, hash_qm31,
-        hash_qm31_gadget, trim_m31, trim_m31_gadget,
+        assert_minimal_encoding_gadget, cm31_conjugate_gadget, cm31_equalverify_const,
+        cm31_norm_gadget, dup_m31_vec_gadget, get_rand_cm31, get_rand_m31, get_rand_qm31,
+        hash_checked, hash_m31_vec, hash_m31_vec_gadget, hash_qm31, hash_qm31_gadget, num_to_bytes,
+        push_canonic_coset_step, qm31_equalverify_const, qm31_select_gadget,
+        qm31_sqrt_with_hint_gadget, trim_m31, trim_m31_gadget,
     };
-    use rand::{RngCore, SeedableRng};
+    use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
+    use rust_bitcoin_m31::qm31_equalverify;
+    use sha2::{Digest, Sha256};
     use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::ComplexConjugate;
+    use stwo_prover::core::poly::circle::CanonicCoset;
 
     #[test]
     fn test_trim_m31() {
@@ -272,7 +431,7 @@ mod test {
             let trim_script = trim_m31_gadget(i);
             println!("M31.trim({}) = {} bytes", i, trim_script.len());
 
-            let a = M31::reduce(prng.next_u64());
+            let a = get_rand_m31(&mut prng);
             let b = trim_m31(a.0, i);
 
             let script = script! {
@@ -286,6 +445,255 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_hash_checked() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut preimage = [0u8; 64];
+        prng.fill(&mut preimage);
+
+        let expected = Sha256::digest(preimage).to_vec();
+
+        let script = script! {
+            { preimage.to_vec() }
+            { hash_checked(64) }
+            { expected }
+            OP_EQUAL
+        };
+        let exec_result = execute_script(script);
+        assert!(exec_result.success);
+
+        let script = script! {
+            { preimage[..63].to_vec() }
+            { hash_checked(64) }
+            OP_DROP
+            OP_TRUE
+        };
+        let exec_result = execute_script(script);
+        assert!(!exec_result.success);
+    }
+
+    #[test]
+    fn test_assert_minimal_encoding() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        let mut values = vec![
+            M31::from(0),
+            M31::from(0x7f),
+            M31::from(0x80),
+            -M31::from(1),
+        ];
+        for _ in 0..10 {
+            values.push(get_rand_m31(&mut prng));
+        }
+
+        for v in values.iter() {
+            let bytes = num_to_bytes(*v);
+
+            let script = script! {
+                { bytes.clone() }
+                { assert_minimal_encoding_gadget() }
+                { bytes.clone() }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            // an extra trailing zero byte is superfluous
+            let mut padded = bytes.clone();
+            padded.push(0);
+
+            let script = script! {
+                { padded }
+                { assert_minimal_encoding_gadget() }
+                OP_DROP
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+
+        // a negative number or the modulus is not an m31 encoding
+        for bytes in [vec![0x81u8], vec![0xff, 0xff, 0xff, 0x7f]] {
+            let script = script! {
+                { bytes }
+                { assert_minimal_encoding_gadget() }
+                OP_DROP
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_push_canonic_coset_step() {
+        for log_size in 1..=20 {
+            let step = CanonicCoset::new(log_size).step_size().to_point();
+
+            let script = script! {
+                { push_canonic_coset_step(log_size) }
+                { step.y }
+                OP_EQUALVERIFY
+                { step.x }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_sqrt_with_hint() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..20 {
+            let r = get_rand_qm31(&mut prng);
+            let value = r * r;
+
+            for root in [r, -r] {
+                let script = script! {
+                    { root }
+                    { value }
+                    qm31_sqrt_with_hint_gadget
+                    { root }
+                    qm31_equalverify
+                    OP_TRUE
+                };
+                let exec_result = execute_script(script);
+                assert!(exec_result.success);
+            }
+
+            let wrong_root = r + get_rand_qm31(&mut prng);
+            let script = script! {
+                { wrong_root }
+                { value }
+                qm31_sqrt_with_hint_gadget
+                { wrong_root }
+                qm31_equalverify
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_qm31_select() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..20 {
+            let a = get_rand_qm31(&mut prng);
+            let b = get_rand_qm31(&mut prng);
+
+            for (bit, expected) in [(0, a), (1, b)] {
+                let script = script! {
+                    { a }
+                    { b }
+                    { bit }
+                    qm31_select_gadget
+                    { expected }
+                    qm31_equalverify
+                    OP_DEPTH OP_NOT
+                };
+                let exec_result = execute_script(script);
+                assert!(exec_result.success);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cm31_conjugate() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..20 {
+            let a = get_rand_cm31(&mut prng);
+            let b = a.complex_conjugate();
+
+            let script = script! {
+                { a }
+                cm31_conjugate_gadget
+                { b.0 }
+                OP_EQUALVERIFY
+                { b.1 }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_cm31_norm() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        report_bitcoin_script_size("CM31", "norm", cm31_norm_gadget().len());
+
+        for _ in 0..20 {
+            let a = get_rand_cm31(&mut prng);
+            let norm = a.0 * a.0 + a.1 * a.1;
+
+            // the norm is the real part of a * conj(a)
+            let expected = a * a.complex_conjugate();
+            assert_eq!(expected.0, norm);
+            assert_eq!(expected.1, M31::from(0));
+
+            let script = script! {
+                { a }
+                cm31_norm_gadget
+                { norm }
+                OP_EQUAL
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+        }
+    }
+
+    #[test]
+    fn test_equalverify_const() {
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let a = get_rand_cm31(&mut prng);
+            let b = get_rand_cm31(&mut prng);
+
+            let script = script! {
+                { a }
+                { cm31_equalverify_const(a) }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                { a }
+                { cm31_equalverify_const(b) }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+
+            let a = get_rand_qm31(&mut prng);
+            let b = get_rand_qm31(&mut prng);
+
+            let script = script! {
+                { a }
+                { qm31_equalverify_const(a) }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(exec_result.success);
+
+            let script = script! {
+                { a }
+                { qm31_equalverify_const(b) }
+                OP_TRUE
+            };
+            let exec_result = execute_script(script);
+            assert!(!exec_result.success);
+        }
+    }
+
     #[test]
     fn test_copy_m31_vec() {
         let mut prng = ChaCha20Rng::seed_from_u64(0);
@@ -307,7 +715,7 @@ mod test {
             };
             let exec_result = execute_script(script);
             assert!(exec_result.success);
-            v.push(M31::reduce(prng.next_u64()));
+            v.push(get_rand_m31(&mut prng));
         }
     }
 
@@ -347,7 +755,7 @@ mod test {
             };
             let exec_result = execute_script(script);
             assert!(exec_result.success);
-            v.push(M31::reduce(prng.next_u64()));
+            v.push(get_rand_m31(&mut prng));
         }
     }
 }
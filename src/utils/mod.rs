@@ -4,6 +4,7 @@ pub use bitcoin_script::*;
 use rand::RngCore;
 use sha2::{Digest, Sha256};
 use stwo_prover::core::circle::CirclePointIndex;
+use stwo_prover::core::fields::cm31::CM31;
 use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::fields::qm31::QM31;
 
@@ -91,12 +92,94 @@ pub fn get_twiddles(mut logn: usize) -> Vec<Vec<M31>> {
     twiddles
 }
 
+/// Get a random m31 element.
+pub fn get_rand_m31<R: RngCore>(prng: &mut R) -> M31 {
+    M31::reduce(prng.next_u64())
+}
+
+/// Get a random cm31 element.
+pub fn get_rand_cm31<R: RngCore>(prng: &mut R) -> CM31 {
+    CM31::from_m31(get_rand_m31(prng), get_rand_m31(prng))
+}
+
 /// Get a random qm31 element.
 pub fn get_rand_qm31<R: RngCore>(prng: &mut R) -> QM31 {
     QM31::from_m31(
-        M31::reduce(prng.next_u64()),
-        M31::reduce(prng.next_u64()),
-        M31::reduce(prng.next_u64()),
-        M31::reduce(prng.next_u64()),
+        get_rand_m31(prng),
+        get_rand_m31(prng),
+        get_rand_m31(prng),
+        get_rand_m31(prng),
     )
 }
+
+#[cfg(test)]
+mod test {
+    use crate::utils::{get_rand_cm31, get_rand_m31};
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use stwo_prover::core::fields::cm31::CM31;
+    use stwo_prover::core::fields::m31::{M31, P};
+
+    /// An rng that replays a fixed list of u64 values.
+    struct ReplayRng {
+        values: Vec<u64>,
+        idx: usize,
+    }
+
+    impl RngCore for ReplayRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let v = self.values[self.idx % self.values.len()];
+            self.idx += 1;
+            v
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_get_rand_m31_range() {
+        let p = P as u64;
+        let mut rng = ReplayRng {
+            values: vec![0, p, 2 * p, p - 1, 2 * p - 1],
+            idx: 0,
+        };
+
+        assert_eq!(get_rand_m31(&mut rng), M31::from_u32_unchecked(0));
+        assert_eq!(get_rand_m31(&mut rng), M31::from_u32_unchecked(0));
+        assert_eq!(get_rand_m31(&mut rng), M31::from_u32_unchecked(0));
+        assert_eq!(get_rand_m31(&mut rng), M31::from_u32_unchecked(P - 1));
+        assert_eq!(get_rand_m31(&mut rng), M31::from_u32_unchecked(P - 1));
+
+        let mut rng = ReplayRng {
+            values: vec![p - 1, p],
+            idx: 0,
+        };
+        assert_eq!(
+            get_rand_cm31(&mut rng),
+            CM31::from_m31(M31::from_u32_unchecked(P - 1), M31::from_u32_unchecked(0))
+        );
+
+        let mut prng = ChaCha20Rng::seed_from_u64(0);
+        let mut min = P;
+        let mut max = 0;
+        for _ in 0..10000 {
+            let v = get_rand_m31(&mut prng).0;
+            assert!(v < P);
+            min = min.min(v);
+            max = max.max(v);
+        }
+        assert!(min < P / 1000);
+        assert!(max > P - P / 1000);
+    }
+}